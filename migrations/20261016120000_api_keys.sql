@@ -0,0 +1,29 @@
+create table api_keys
+(
+    id           uuid                     default gen_random_uuid() not null
+        primary key,
+    created_at   timestamp with time zone default now()             not null,
+    updated_at   timestamp with time zone default now()             not null,
+    member_id    uuid                                               not null
+        references members
+            on update cascade on delete cascade,
+    name         text                                               not null,
+    prefix       varchar                                            not null,
+    key_hash     varchar                                            not null
+        unique,
+    scope        varchar                                            not null,
+    project_id   uuid
+        references projects
+            on update cascade on delete cascade,
+    last_used_at timestamp with time zone,
+    revoked_at   timestamp with time zone
+);
+
+create index api_keys_member_id_idx
+    on api_keys (member_id);
+
+create trigger set_public_api_keys_updated_at
+    before update
+    on api_keys
+    for each row
+execute procedure set_current_timestamp_updated_at();
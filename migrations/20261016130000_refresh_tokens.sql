@@ -0,0 +1,19 @@
+create table refresh_tokens
+(
+    id         uuid                     default gen_random_uuid() not null
+        primary key,
+    created_at timestamp with time zone default now()             not null,
+    family_id  uuid                                               not null,
+    member_id  uuid                                               not null
+        references members
+            on update cascade on delete cascade,
+    expires_at timestamp with time zone                           not null,
+    used_at    timestamp with time zone,
+    revoked_at timestamp with time zone
+);
+
+create index refresh_tokens_family_id_idx
+    on refresh_tokens (family_id);
+
+create index refresh_tokens_member_id_idx
+    on refresh_tokens (member_id);
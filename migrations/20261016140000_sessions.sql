@@ -0,0 +1,32 @@
+create table sessions
+(
+    id         uuid                     default gen_random_uuid() not null
+        primary key,
+    created_at timestamp with time zone default now()             not null,
+    updated_at timestamp with time zone default now()             not null,
+    member_id  uuid                                               not null
+        references members
+            on update cascade on delete cascade,
+    expires_at timestamp with time zone                           not null,
+    revoked_at timestamp with time zone
+);
+
+create index sessions_member_id_idx
+    on sessions (member_id);
+
+create trigger set_public_sessions_updated_at
+    before update
+    on sessions
+    for each row
+execute procedure set_current_timestamp_updated_at();
+
+-- Refresh token families are now sessions; families opened before the registry existed
+-- can't be tracked and are dropped, so those members sign in again.
+delete
+from refresh_tokens
+where family_id not in (select id from sessions);
+
+alter table refresh_tokens
+    add constraint refresh_tokens_family_id_fkey
+        foreign key (family_id) references sessions
+            on update cascade on delete cascade;
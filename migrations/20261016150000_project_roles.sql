@@ -0,0 +1,22 @@
+create table project_roles
+(
+    project_id uuid                                   not null
+        references projects
+            on update cascade on delete cascade,
+    member_id  uuid                                   not null
+        references members
+            on update cascade on delete cascade,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null,
+    role       varchar                                not null,
+    primary key (project_id, member_id)
+);
+
+create index project_roles_member_id_idx
+    on project_roles (member_id);
+
+create trigger set_public_project_roles_updated_at
+    before update
+    on project_roles
+    for each row
+execute procedure set_current_timestamp_updated_at();
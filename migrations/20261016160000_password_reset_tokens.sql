@@ -0,0 +1,16 @@
+create table password_reset_tokens
+(
+    id         uuid                     default gen_random_uuid() not null
+        primary key,
+    created_at timestamp with time zone default now()             not null,
+    member_id  uuid                                               not null
+        references members
+            on update cascade on delete cascade,
+    token_hash varchar                                            not null
+        unique,
+    expires_at timestamp with time zone                           not null,
+    used_at    timestamp with time zone
+);
+
+create index password_reset_tokens_member_id_idx
+    on password_reset_tokens (member_id);
@@ -0,0 +1,30 @@
+create table invitations
+(
+    id            uuid                     default gen_random_uuid() not null
+        primary key,
+    created_at    timestamp with time zone default now()             not null,
+    updated_at    timestamp with time zone default now()             not null,
+    email         varchar                                            not null,
+    role          varchar                                            not null,
+    project_ids   uuid[]                   default '{}'              not null,
+    invited_by_id uuid                                               not null
+        references members
+            on update cascade on delete cascade,
+    token_hash    varchar                                            not null
+        unique,
+    expires_at    timestamp with time zone                           not null,
+    accepted_at   timestamp with time zone,
+    revoked_at    timestamp with time zone,
+    member_id     uuid
+        references members
+            on update cascade on delete set null
+);
+
+create index invitations_email_idx
+    on invitations (email);
+
+create trigger set_public_invitations_updated_at
+    before update
+    on invitations
+    for each row
+execute procedure set_current_timestamp_updated_at();
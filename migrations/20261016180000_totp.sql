@@ -0,0 +1,33 @@
+create table member_totp
+(
+    member_id      uuid                                   not null
+        primary key
+        references members
+            on update cascade on delete cascade,
+    created_at     timestamp with time zone default now() not null,
+    updated_at     timestamp with time zone default now() not null,
+    secret         varchar                                not null,
+    last_used_step bigint,
+    enabled_at     timestamp with time zone
+);
+
+create trigger set_public_member_totp_updated_at
+    before update
+    on member_totp
+    for each row
+execute procedure set_current_timestamp_updated_at();
+
+create table totp_recovery_codes
+(
+    id         uuid                     default gen_random_uuid() not null
+        primary key,
+    created_at timestamp with time zone default now()             not null,
+    member_id  uuid                                               not null
+        references members
+            on update cascade on delete cascade,
+    code_hash  varchar                                            not null,
+    used_at    timestamp with time zone
+);
+
+create index totp_recovery_codes_member_id_idx
+    on totp_recovery_codes (member_id);
@@ -0,0 +1,39 @@
+create table login_attempts
+(
+    id         uuid                     default gen_random_uuid() not null
+        primary key,
+    created_at timestamp with time zone default now()             not null,
+    email      varchar                                            not null,
+    ip_address varchar,
+    succeeded  boolean                                            not null
+);
+
+create index login_attempts_email_created_at_idx
+    on login_attempts (email, created_at);
+
+create index login_attempts_ip_address_created_at_idx
+    on login_attempts (ip_address, created_at);
+
+create table login_lockouts
+(
+    id              uuid                     default gen_random_uuid() not null
+        primary key,
+    created_at      timestamp with time zone default now()             not null,
+    email           varchar,
+    member_id       uuid
+        references members
+            on update cascade on delete cascade,
+    ip_address      varchar,
+    failed_attempts integer                                            not null,
+    locked_until    timestamp with time zone                           not null,
+    unlocked_at     timestamp with time zone,
+    unlocked_by_id  uuid
+        references members
+            on update cascade on delete set null
+);
+
+create index login_lockouts_email_idx
+    on login_lockouts (email);
+
+create index login_lockouts_ip_address_idx
+    on login_lockouts (ip_address);
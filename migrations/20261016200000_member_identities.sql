@@ -0,0 +1,23 @@
+create table member_identities
+(
+    id         uuid                     default gen_random_uuid() not null
+        primary key,
+    created_at timestamp with time zone default now()             not null,
+    updated_at timestamp with time zone default now()             not null,
+    member_id  uuid                                               not null
+        references members
+            on update cascade on delete cascade,
+    provider   varchar                                            not null,
+    subject    varchar                                            not null,
+    email      varchar,
+    unique (provider, subject)
+);
+
+create index member_identities_member_id_idx
+    on member_identities (member_id);
+
+create trigger set_public_member_identities_updated_at
+    before update
+    on member_identities
+    for each row
+execute procedure set_current_timestamp_updated_at();
@@ -0,0 +1,8 @@
+insert into member_identities (member_id, provider, subject)
+select id, 'github', github_id
+from members
+where github_id is not null
+on conflict do nothing;
+
+alter table member_identities
+    add unique (member_id, provider);
@@ -0,0 +1,21 @@
+create table service_accounts
+(
+    member_id     uuid                                               not null
+        primary key
+        references members
+            on update cascade on delete cascade,
+    created_at    timestamp with time zone default now()             not null,
+    updated_at    timestamp with time zone default now()             not null,
+    description   varchar,
+    scope         varchar                  default 'ReadOnly'        not null,
+    project_ids   uuid[]                   default '{}'              not null,
+    created_by_id uuid
+        references members
+            on update cascade on delete set null
+);
+
+create trigger set_public_service_accounts_updated_at
+    before update
+    on service_accounts
+    for each row
+execute procedure set_current_timestamp_updated_at();
@@ -0,0 +1,11 @@
+create table member_deactivations
+(
+    member_id         uuid                                   not null
+        primary key
+        references members
+            on update cascade on delete cascade,
+    deactivated_at    timestamp with time zone default now() not null,
+    deactivated_by_id uuid
+        references members
+            on update cascade on delete set null
+);
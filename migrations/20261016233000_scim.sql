@@ -0,0 +1,35 @@
+create table scim_users
+(
+    member_id   uuid                                   not null
+        primary key
+        references members
+            on update cascade on delete cascade,
+    created_at  timestamp with time zone default now() not null,
+    updated_at  timestamp with time zone default now() not null,
+    external_id varchar
+        unique
+);
+
+create trigger set_public_scim_users_updated_at
+    before update
+    on scim_users
+    for each row
+execute procedure set_current_timestamp_updated_at();
+
+create table scim_groups
+(
+    team_id     uuid                                   not null
+        primary key
+        references teams
+            on update cascade on delete cascade,
+    created_at  timestamp with time zone default now() not null,
+    updated_at  timestamp with time zone default now() not null,
+    external_id varchar
+        unique
+);
+
+create trigger set_public_scim_groups_updated_at
+    before update
+    on scim_groups
+    for each row
+execute procedure set_current_timestamp_updated_at();
@@ -0,0 +1,35 @@
+create table member_email_verifications
+(
+    member_id   uuid                                               not null
+        primary key
+        references members
+            on update cascade on delete cascade,
+    created_at  timestamp with time zone default now()             not null,
+    updated_at  timestamp with time zone default now()             not null,
+    email       varchar                                            not null,
+    verified_at timestamp with time zone
+);
+
+create trigger set_public_member_email_verifications_updated_at
+    before update
+    on member_email_verifications
+    for each row
+execute procedure set_current_timestamp_updated_at();
+
+create table email_verification_tokens
+(
+    id         uuid                     default gen_random_uuid() not null
+        primary key,
+    created_at timestamp with time zone default now()             not null,
+    member_id  uuid                                               not null
+        references members
+            on update cascade on delete cascade,
+    email      varchar                                            not null,
+    token_hash varchar                                            not null
+        unique,
+    expires_at timestamp with time zone                           not null,
+    used_at    timestamp with time zone
+);
+
+create index email_verification_tokens_member_id_idx
+    on email_verification_tokens (member_id);
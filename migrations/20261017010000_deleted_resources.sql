@@ -0,0 +1,64 @@
+-- Subscriptions are notified of a deletion once the row is gone, so the project it belonged
+-- to, and for projects who could see them, is kept here for a while.
+create table deleted_resources
+(
+    id            uuid                                   not null
+        primary key,
+    deleted_at    timestamp with time zone default now() not null,
+    resource_type varchar                                not null,
+    project_id    uuid,
+    private       boolean                  default false not null,
+    member_ids    uuid[]                   default '{}'  not null
+);
+
+create index deleted_resources_deleted_at_idx
+    on deleted_resources (deleted_at);
+
+create or replace function record_deleted_resource() returns trigger as
+$$
+begin
+    delete from deleted_resources where deleted_at < now() - interval '1 day';
+
+    if (TG_TABLE_NAME = 'projects') then
+        insert into deleted_resources (id, resource_type, project_id, private, member_ids)
+        values (
+            OLD.id,
+            TG_TABLE_NAME,
+            OLD.id,
+            coalesce(OLD.visibility = 'Private', false),
+            array_remove(
+                array[OLD.owner_id, OLD.lead_id]
+                    || array(select r.member_id from project_roles r where r.project_id = OLD.id)
+                    || array(select m.member_id from members_by_projects m where m.project_id = OLD.id),
+                null
+            )
+        )
+        on conflict (id) do nothing;
+    else
+        insert into deleted_resources (id, resource_type, project_id)
+        values (OLD.id, TG_TABLE_NAME, OLD.project_id)
+        on conflict (id) do nothing;
+    end if;
+
+    return OLD;
+end;
+$$ language plpgsql;
+
+-- Before the delete, while the project's roles haven't been cascaded away yet
+create trigger record_deleted_projects
+    before delete
+    on projects
+    for each row
+execute procedure record_deleted_resource();
+
+create trigger record_deleted_tasks
+    before delete
+    on tasks
+    for each row
+execute procedure record_deleted_resource();
+
+create trigger record_deleted_assets
+    before delete
+    on assets
+    for each row
+execute procedure record_deleted_resource();
@@ -1,4 +1,4 @@
-use async_graphql::{Context, Result};
+use async_graphql::{Context, ErrorExtensions, Result, ServerError};
 use plexo_sdk::resources::changes::{
     change::{Change, ChangeOperation, ChangeResourceType},
     operations::{ChangeCrudOperations, CreateChangeInputBuilder},
@@ -6,20 +6,50 @@ use plexo_sdk::resources::changes::{
 use tracing::info;
 use uuid::Uuid;
 
-use crate::{auth::resources::PlexoAuthToken, core::app::Core, errors::app::PlexoAppError};
+use crate::{
+    auth::{
+        principal::PlexoPrincipal,
+        resources::{AuthenticationFailure, ClientIp, PlexoAuthToken},
+    },
+    core::app::Core,
+    errors::app::PlexoAppError,
+};
+
+/// Errors raised by extensions, outside resolvers, have no position in the query.
+pub fn server_error(err: async_graphql::Error) -> ServerError {
+    let mut server_error = ServerError::new(err.message, None);
+    server_error.extensions = err.extensions;
+
+    server_error
+}
 
 pub fn extract_context(ctx: &Context<'_>) -> Result<(Core, Uuid)> {
-    let Ok(auth_token) = &ctx.data::<PlexoAuthToken>() else {
-        return Err(PlexoAppError::MissingAuthorizationToken.into());
+    let (plexo_engine, principal) = extract_principal(ctx)?;
+
+    Ok((plexo_engine, principal.member_id))
+}
+
+pub fn extract_principal(ctx: &Context<'_>) -> Result<(Core, PlexoPrincipal)> {
+    let Ok(principal) = ctx.data::<PlexoPrincipal>() else {
+        if let Ok(AuthenticationFailure(err)) = ctx.data::<AuthenticationFailure>() {
+            return Err(err.extend());
+        }
+
+        if ctx.data::<PlexoAuthToken>().is_ok() {
+            return Err(PlexoAppError::InvalidAuthorizationToken.extend());
+        }
+
+        return Err(PlexoAppError::MissingAuthorizationToken.extend());
     };
 
     let plexo_engine = ctx.data::<Core>()?.to_owned();
 
-    let claims = plexo_engine.auth.extract_claims(auth_token)?;
-
-    let member_id = claims.member_id();
+    Ok((plexo_engine, principal.to_owned()))
+}
 
-    Ok((plexo_engine, member_id))
+/// The address of the client, recorded when the request or the websocket came in.
+pub fn extract_client_ip(ctx: &Context<'_>) -> ClientIp {
+    ctx.data_opt::<ClientIp>().cloned().unwrap_or(ClientIp(None))
 }
 
 pub async fn create_change(
@@ -0,0 +1,62 @@
+use async_graphql::{Context, Guard, Result};
+
+use crate::errors::app::PlexoAppError;
+
+use super::commons::extract_principal;
+
+/// Rejects `ReadOnly` members and callers authenticated with a read-only API key.
+pub struct WriteGuard;
+
+impl Guard for WriteGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        let (_core, principal) = extract_principal(ctx)?;
+
+        principal.authorize_write()?;
+
+        Ok(())
+    }
+}
+
+/// Only checks the API key scope, used for members managing their own account, which
+/// `ReadOnly` members are still allowed to do.
+pub struct WriteScopeGuard;
+
+impl Guard for WriteScopeGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        let (_core, principal) = extract_principal(ctx)?;
+
+        if !principal.can_write() {
+            return Err(PlexoAppError::InsufficientScope.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Like [`WriteGuard`], but also rejects API keys limited to a single project, used for
+/// resources that belong to the whole organization.
+pub struct OrganizationWriteGuard;
+
+impl Guard for OrganizationWriteGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        let (_core, principal) = extract_principal(ctx)?;
+
+        principal.authorize_write()?;
+        principal.authorize_project(None)?;
+
+        Ok(())
+    }
+}
+
+/// Only lets organization admins through.
+pub struct AdminGuard;
+
+impl Guard for AdminGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        let (_core, principal) = extract_principal(ctx)?;
+
+        principal.authorize_admin()?;
+
+        Ok(())
+    }
+}
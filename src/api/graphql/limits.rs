@@ -0,0 +1,163 @@
+use std::{sync::Arc, time::Duration};
+
+use async_graphql::{
+    async_trait::async_trait,
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextExecute, NextValidation},
+    ErrorExtensions, Response, ServerError, ValidationResult,
+};
+use tracing::{info, warn};
+
+use crate::{
+    core::config::{GRAPHQL_MAX_COMPLEXITY, GRAPHQL_MAX_DEPTH, GRAPHQL_REQUEST_TIMEOUT_SECONDS},
+    errors::app::PlexoAppError,
+};
+
+use super::{
+    commons::server_error,
+    pagination::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE},
+};
+
+/// How many items a list without pagination is assumed to hold when estimating the cost of
+/// a query. Most of them are small, but nesting them multiplies quickly.
+pub const LIST_COMPLEXITY_FACTOR: usize = 10;
+
+/// Cost of a field returning an unpaginated list. Saturates, so deep nesting can't wrap
+/// around under the limit.
+pub fn list_complexity(child_complexity: usize) -> usize {
+    LIST_COMPLEXITY_FACTOR.saturating_mul(child_complexity).saturating_add(1)
+}
+
+/// Cost of a connection field, which returns as many items as the page asks for, up to
+/// `MAX_PAGE_SIZE`.
+pub fn connection_complexity(first: Option<i32>, last: Option<i32>, child_complexity: usize) -> usize {
+    let page_size = last
+        .or(first)
+        .map(|size| (size.max(0) as usize).min(MAX_PAGE_SIZE))
+        .unwrap_or(DEFAULT_PAGE_SIZE);
+
+    page_size.saturating_mul(child_complexity).saturating_add(1)
+}
+
+/// Rejects queries nested deeper than `GRAPHQL_MAX_DEPTH` or costlier than
+/// `GRAPHQL_MAX_COMPLEXITY` before they run, and stops queries and mutations still running
+/// after `GRAPHQL_REQUEST_TIMEOUT_SECONDS`. Every query's depth and complexity is logged so
+/// the limits can be tuned.
+///
+/// The schema's own limits reject with a bare message, so they're enforced here, where
+/// rejections carry a `code` extension along with the limit that was hit.
+pub struct QueryLimits;
+
+impl ExtensionFactory for QueryLimits {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(QueryLimitsExtension)
+    }
+}
+
+struct QueryLimitsExtension;
+
+fn limit_error(err: PlexoAppError, limit: usize, value: usize) -> ServerError {
+    server_error(err.extend().extend_with(|_, extensions| {
+        extensions.set("limit", limit);
+        extensions.set("value", value);
+    }))
+}
+
+#[async_trait]
+impl Extension for QueryLimitsExtension {
+    async fn validation(&self, ctx: &ExtensionContext<'_>, next: NextValidation<'_>) -> Result<ValidationResult, Vec<ServerError>> {
+        let result = next.run(ctx).await?;
+
+        info!(
+            "graphql query analyzed, depth: {}, complexity: {}",
+            result.depth, result.complexity
+        );
+
+        if result.depth > *GRAPHQL_MAX_DEPTH {
+            warn!("graphql query rejected, depth {} over {}", result.depth, *GRAPHQL_MAX_DEPTH);
+
+            return Err(vec![limit_error(
+                PlexoAppError::QueryTooDeep {
+                    depth: result.depth,
+                    max_depth: *GRAPHQL_MAX_DEPTH,
+                },
+                *GRAPHQL_MAX_DEPTH,
+                result.depth,
+            )]);
+        }
+
+        if result.complexity > *GRAPHQL_MAX_COMPLEXITY {
+            warn!(
+                "graphql query rejected, complexity {} over {}",
+                result.complexity, *GRAPHQL_MAX_COMPLEXITY
+            );
+
+            return Err(vec![limit_error(
+                PlexoAppError::QueryTooComplex {
+                    complexity: result.complexity,
+                    max_complexity: *GRAPHQL_MAX_COMPLEXITY,
+                },
+                *GRAPHQL_MAX_COMPLEXITY,
+                result.complexity,
+            )]);
+        }
+
+        Ok(result)
+    }
+
+    /// Subscriptions aren't executed through here, so they stay open as long as the client
+    /// listens. A mutation cut short keeps whatever it already saved.
+    async fn execute(&self, ctx: &ExtensionContext<'_>, operation_name: Option<&str>, next: NextExecute<'_>) -> Response {
+        let timeout = Duration::from_secs(*GRAPHQL_REQUEST_TIMEOUT_SECONDS);
+
+        match tokio::time::timeout(timeout, next.run(ctx, operation_name)).await {
+            Ok(response) => response,
+            Err(_) => {
+                warn!(
+                    "graphql operation {:?} timed out after {} seconds",
+                    operation_name, *GRAPHQL_REQUEST_TIMEOUT_SECONDS
+                );
+
+                Response::from_errors(vec![server_error(
+                    PlexoAppError::QueryTimeout(*GRAPHQL_REQUEST_TIMEOUT_SECONDS).extend(),
+                )])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_complexity_multiplies_children() {
+        assert_eq!(list_complexity(0), 1);
+        assert_eq!(list_complexity(3), 1 + LIST_COMPLEXITY_FACTOR * 3);
+    }
+
+    #[test]
+    fn connection_complexity_uses_the_requested_page_size() {
+        assert_eq!(connection_complexity(Some(20), None, 2), 41);
+        assert_eq!(connection_complexity(Some(20), Some(5), 2), 11);
+        assert_eq!(connection_complexity(None, None, 1), 1 + DEFAULT_PAGE_SIZE);
+        assert_eq!(connection_complexity(Some(-5), None, 7), 1);
+    }
+
+    #[test]
+    fn connection_complexity_caps_the_page_size() {
+        assert_eq!(connection_complexity(Some(i32::MAX), None, 1), 1 + MAX_PAGE_SIZE);
+        assert_eq!(connection_complexity(None, Some(i32::MAX), 1), 1 + MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn nested_connections_saturate_instead_of_wrapping() {
+        let mut complexity = 1;
+
+        for _ in 0..15 {
+            complexity = connection_complexity(Some(i32::MAX), None, list_complexity(complexity));
+        }
+
+        assert_eq!(complexity, usize::MAX);
+        assert!(complexity > *GRAPHQL_MAX_COMPLEXITY);
+    }
+}
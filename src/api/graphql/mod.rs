@@ -1,7 +1,12 @@
 // pub mod auth;
 pub mod commons;
+pub mod guards;
+pub mod limits;
 pub mod loaders;
 pub mod operations;
+pub mod pagination;
+pub mod persisted_queries;
 pub mod processors;
 pub mod resources;
 pub mod schema;
+pub mod subscriptions;
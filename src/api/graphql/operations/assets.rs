@@ -1,6 +1,13 @@
-use crate::api::graphql::{
-    commons::{create_change, extract_context},
-    resources::assets::Asset,
+use crate::{
+    api::graphql::{
+        commons::{create_change, extract_principal},
+        guards::WriteGuard,
+        limits::{connection_complexity, list_complexity},
+        pagination::{keyset_connection, KeysetConnection},
+        resources::assets::Asset,
+        subscriptions::{listen_events, resource_events, EventFilter, ResourceEvent},
+    },
+    auth::project_roles::ProjectRole,
 };
 use async_graphql::{Context, Object, Result, Subscription};
 
@@ -10,7 +17,7 @@ use plexo_sdk::resources::{
 };
 use serde_json::json;
 use tokio::task;
-use tokio_stream::{Stream, StreamExt};
+use tokio_stream::Stream;
 use uuid::Uuid;
 
 #[derive(Default)]
@@ -18,35 +25,78 @@ pub struct AssetsGraphQLQuery;
 
 #[Object]
 impl AssetsGraphQLQuery {
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn assets(&self, ctx: &Context<'_>, input: Option<GetAssetsInput>) -> Result<Vec<Asset>> {
-        let (core, _member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+        let visible_projects = core.visible_projects(&principal).await?;
 
         core.engine
             .get_assets(input.unwrap_or_default())
             .await
             .map_err(|err| async_graphql::Error::new(err.to_string()))
-            .map(|assets| assets.into_iter().map(|asset| asset.into()).collect())
+            .map(|assets| {
+                assets
+                    .into_iter()
+                    .filter(|asset| visible_projects.contains(asset.project_id))
+                    .map(|asset| asset.into())
+                    .collect()
+            })
+    }
+
+    /// Pages through `assets`, oldest first.
+    #[graphql(complexity = "connection_complexity(first, last, child_complexity)")]
+    async fn assets_connection(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Asset>> {
+        let (core, principal) = extract_principal(ctx)?;
+        let visible_projects = core.visible_projects(&principal).await?;
+
+        keyset_connection(
+            ctx,
+            "assets",
+            move |query| visible_projects.push_conditions(query, "assets.project_id"),
+            &core.loaders.asset_loader,
+            after,
+            before,
+            first,
+            last,
+        )
+        .await
     }
 
     async fn asset(&self, ctx: &Context<'_>, id: Uuid) -> Result<Asset> {
-        let (core, _member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
 
-        core.engine
+        let asset = core
+            .engine
             .get_asset(id)
             .await
-            .map_err(|err| async_graphql::Error::new(err.to_string()))
-            .map(|asset| asset.into())
+            .map_err(|err| async_graphql::Error::new(err.to_string()))?;
+
+        core.authorize_project_role(&principal, asset.project_id, ProjectRole::Viewer)
+            .await?;
+
+        Ok(asset.into())
     }
 }
 
 #[derive(Default)]
 pub struct AssetsGraphQLMutation;
 
-#[Object]
+#[Object(guard = "WriteGuard")]
 impl AssetsGraphQLMutation {
     // TODO: It's possible that this method may not work correctly, as the owner_id is being ignored by async_graphql
     async fn create_asset(&self, ctx: &Context<'_>, input: CreateAssetInput) -> Result<Asset> {
-        let (core, member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+        let member_id = principal.member_id;
+
+        core.authorize_project_role(&principal, input.project_id, ProjectRole::Member)
+            .await?;
 
         let mut input = input;
         input.owner_id = member_id;
@@ -79,7 +129,17 @@ impl AssetsGraphQLMutation {
     }
 
     async fn update_asset(&self, ctx: &Context<'_>, id: Uuid, input: UpdateAssetInput) -> Result<Asset> {
-        let (core, member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+        let member_id = principal.member_id;
+
+        let asset = core.engine.get_asset(id).await?;
+        core.authorize_project_role(&principal, asset.project_id, ProjectRole::Member)
+            .await?;
+
+        if input.project_id.is_some() {
+            core.authorize_project_role(&principal, input.project_id, ProjectRole::Member)
+                .await?;
+        }
 
         let saved_input = input.clone();
 
@@ -109,7 +169,11 @@ impl AssetsGraphQLMutation {
     }
 
     async fn delete_asset(&self, ctx: &Context<'_>, id: Uuid) -> Result<Asset> {
-        let (core, _member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+
+        let asset = core.engine.get_asset(id).await?;
+        core.authorize_project_role(&principal, asset.project_id, ProjectRole::Member)
+            .await?;
 
         let asset = core.engine.delete_asset(id).await?;
         let saved_asset = asset.clone();
@@ -139,13 +203,24 @@ pub struct AssetsGraphQLSubscription;
 
 #[Subscription]
 impl AssetsGraphQLSubscription {
-    async fn assets(&self, ctx: &Context<'_>) -> impl Stream<Item = ListenEvent> {
-        let (core, _member_id) = extract_context(ctx).unwrap();
+    async fn assets(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = ListenEvent>> {
+        listen_events(ctx, ChangeResourceType::Assets, EventFilter::default()).await
+    }
 
-        core.engine
-            .listen(ChangeResourceType::Assets)
-            .await
-            .unwrap()
-            .map(|x| x.unwrap())
+    /// Changes to assets along with the asset as it ends up, optionally only in a project.
+    async fn asset_subscription(
+        &self,
+        ctx: &Context<'_>,
+        project_id: Option<Uuid>,
+        asset_ids: Option<Vec<Uuid>>,
+        operations: Option<Vec<ChangeOperation>>,
+    ) -> Result<impl Stream<Item = ResourceEvent<Asset>>> {
+        resource_events(
+            ctx,
+            ChangeResourceType::Assets,
+            |loaders| &loaders.asset_loader,
+            EventFilter::new(asset_ids, operations).in_project(project_id),
+        )
+        .await
     }
 }
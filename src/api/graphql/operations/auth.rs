@@ -1,51 +1,103 @@
 use async_graphql::{Context, Object, Result, SimpleObject};
 use plexo_sdk::resources::members::extensions::{CreateMemberFromEmailInputBuilder, MembersExtensionOperations};
 
-use crate::{api::graphql::commons::extract_context, core::app::Core, errors::app::PlexoAppError};
+use crate::{
+    api::graphql::{
+        commons::{extract_client_ip, extract_principal},
+        guards::OrganizationWriteGuard,
+    },
+    auth::{commons::validate_email, invitations::InvitationCredentials, refresh_tokens::SessionTokens},
+    core::app::Core,
+    errors::app::PlexoAppError,
+};
 
 #[derive(Default)]
 pub struct AuthMutation;
 
 #[derive(SimpleObject)]
 struct LoginResponse {
-    token: String,
+    /// Missing while a two-factor challenge or an email verification is pending.
+    token: Option<String>,
+    refresh_token: Option<String>,
     member_id: String,
+    /// Set when the member has two-factor authentication enabled. Exchange it with
+    /// `verifyLogin` and a code to get the session tokens.
+    challenge_token: Option<String>,
+    /// Set after registering when the organization requires a verified email to log in.
+    /// The member logs in once they've followed the link sent to their email.
+    email_verification_pending: bool,
+}
+
+impl From<SessionTokens> for LoginResponse {
+    fn from(session_tokens: SessionTokens) -> Self {
+        LoginResponse {
+            token: Some(session_tokens.access_token),
+            refresh_token: Some(session_tokens.refresh_token),
+            member_id: session_tokens.member_id.to_string(),
+            challenge_token: None,
+            email_verification_pending: false,
+        }
+    }
 }
 
 #[Object]
 impl AuthMutation {
     async fn login(&self, ctx: &Context<'_>, email: String, password: String) -> Result<LoginResponse> {
         let plexo_engine = ctx.data::<Core>()?.to_owned();
+        let client_ip = extract_client_ip(ctx);
 
-        let Ok(Some(member)) = plexo_engine.engine.get_member_by_email(email.clone()).await else {
-            return Err(PlexoAppError::EmailNotFound.into());
-        };
+        let member = plexo_engine
+            .login_with_password(email.as_str(), password.as_str(), &client_ip)
+            .await?;
 
-        let Some(password_hash) = member.password_hash.clone() else {
-            return Err(PlexoAppError::InvalidPassword.into());
-        };
+        if let Some(challenge_token) = plexo_engine.create_login_challenge(member.id).await? {
+            return Ok(LoginResponse {
+                token: None,
+                refresh_token: None,
+                member_id: member.id.to_string(),
+                challenge_token: Some(challenge_token),
+                email_verification_pending: false,
+            });
+        }
 
-        if !plexo_engine.auth.validate_password(password.as_str(), password_hash.as_str()) {
-            return Err(PlexoAppError::InvalidPassword.into());
-        };
+        let session_tokens = plexo_engine.create_session_tokens(&member).await?;
 
-        let Ok(session_token) = plexo_engine.auth.jwt_engine.create_session_token(&member) else {
-            return Err(PlexoAppError::InvalidPassword.into());
-        };
+        Ok(session_tokens.into())
+    }
+
+    /// Second step of `login` for members with two-factor authentication enabled. The code
+    /// comes from their authenticator app, or is one of their recovery codes.
+    async fn verify_login(&self, ctx: &Context<'_>, challenge_token: String, code: String) -> Result<LoginResponse> {
+        let plexo_engine = ctx.data::<Core>()?.to_owned();
+        let client_ip = extract_client_ip(ctx);
+
+        let member = plexo_engine
+            .complete_login_challenge(challenge_token.as_str(), code.as_str(), &client_ip)
+            .await?;
 
-        Ok(LoginResponse {
-            token: session_token,
-            member_id: member.id.to_string(),
-        })
+        let session_tokens = plexo_engine.create_session_tokens(&member).await?;
+
+        Ok(session_tokens.into())
     }
 
+    /// Creates a member on behalf of the caller, who needs write access to the organization.
+    /// Service accounts can't add members.
+    #[graphql(guard = "OrganizationWriteGuard")]
     async fn register(&self, ctx: &Context<'_>, email: String, name: String, password: String) -> Result<LoginResponse> {
-        let (plexo_engine, _member_id) = extract_context(ctx)?;
+        let (plexo_engine, principal) = extract_principal(ctx)?;
+
+        if principal.service_account.is_some() {
+            return Err(PlexoAppError::Forbidden.into());
+        }
+
+        validate_email(email.as_str())?;
 
         if (plexo_engine.engine.get_member_by_email(email.clone()).await).is_ok_and(|member| member.is_some()) {
             return Err(PlexoAppError::EmailAlreadyExists.into());
         };
 
+        plexo_engine.ensure_email_domain_allowed(email.as_str()).await?;
+
         let password_hash = plexo_engine.auth.hash_password(password.as_str());
 
         let Ok(member) = plexo_engine
@@ -62,13 +114,69 @@ impl AuthMutation {
             return Err(PlexoAppError::EmailAlreadyExists.into());
         };
 
-        let Ok(session_token) = plexo_engine.auth.jwt_engine.create_session_token(&member) else {
-            return Err(PlexoAppError::InvalidPassword.into());
-        };
+        plexo_engine.start_email_verification(&member).await?;
+
+        if plexo_engine.get_self_registration_settings().await?.require_verified_email {
+            return Ok(LoginResponse {
+                token: None,
+                refresh_token: None,
+                member_id: member.id.to_string(),
+                challenge_token: None,
+                email_verification_pending: true,
+            });
+        }
+
+        let session_tokens = plexo_engine.create_session_tokens(&member).await?;
+
+        Ok(session_tokens.into())
+    }
+
+    async fn refresh_token(&self, ctx: &Context<'_>, refresh_token: String) -> Result<LoginResponse> {
+        let plexo_engine = ctx.data::<Core>()?.to_owned();
+
+        let session_tokens = plexo_engine.refresh_session_tokens(refresh_token.as_str()).await?;
+
+        Ok(session_tokens.into())
+    }
+
+    /// Emails a password reset link. Returns `true` whether or not the email belongs to a
+    /// member, so it can't be used to discover accounts.
+    async fn forgot_password(&self, ctx: &Context<'_>, email: String) -> Result<bool> {
+        let plexo_engine = ctx.data::<Core>()?.to_owned();
+
+        plexo_engine.request_password_reset(email);
+
+        Ok(true)
+    }
+
+    async fn reset_password(&self, ctx: &Context<'_>, token: String, password: String) -> Result<bool> {
+        let plexo_engine = ctx.data::<Core>()?.to_owned();
+
+        plexo_engine.reset_password(token.as_str(), password.as_str()).await?;
+
+        Ok(true)
+    }
+
+    /// Confirms the email a verification link was sent to, on registration or after changing
+    /// it from the profile.
+    async fn verify_email(&self, ctx: &Context<'_>, token: String) -> Result<bool> {
+        let plexo_engine = ctx.data::<Core>()?.to_owned();
+
+        plexo_engine.verify_email(token.as_str()).await?;
+
+        Ok(true)
+    }
+
+    /// Creates the invited member with a password and signs them in.
+    async fn accept_invitation(&self, ctx: &Context<'_>, token: String, name: String, password: String) -> Result<LoginResponse> {
+        let plexo_engine = ctx.data::<Core>()?.to_owned();
+
+        let member = plexo_engine
+            .accept_invitation(token.as_str(), name, InvitationCredentials::Password(password))
+            .await?;
+
+        let session_tokens = plexo_engine.create_session_tokens(&member).await?;
 
-        Ok(LoginResponse {
-            token: session_token,
-            member_id: member.id.to_string(),
-        })
+        Ok(session_tokens.into())
     }
 }
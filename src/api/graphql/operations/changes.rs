@@ -1,14 +1,21 @@
-use crate::api::graphql::{
-    commons::{create_change, extract_context},
-    resources::changes::Change,
+use crate::{
+    api::graphql::{
+        commons::{extract_context, extract_principal},
+        guards::{AdminGuard, OrganizationWriteGuard},
+        limits::{connection_complexity, list_complexity},
+        pagination::{changes_connection, KeysetConnection},
+        resources::changes::Change,
+        subscriptions::change_events,
+    },
+    errors::app::PlexoAppError,
 };
-use async_graphql::{Context, Object, Result};
+use async_graphql::{Context, Object, Result, Subscription};
 
 use plexo_sdk::resources::changes::{
-    change::{ChangeOperation, ChangeResourceType},
-    operations::{ChangeCrudOperations, CreateChangeInput, GetChangesInput, UpdateChangeInput},
+    change::ChangeResourceType,
+    operations::{ChangeCrudOperations, GetChangesInput},
 };
-use serde_json::json;
+use tokio_stream::Stream;
 use uuid::Uuid;
 
 #[derive(Default)]
@@ -16,104 +23,85 @@ pub struct ChangesGraphQLQuery;
 
 #[Object]
 impl ChangesGraphQLQuery {
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn changes(&self, ctx: &Context<'_>, input: Option<GetChangesInput>) -> Result<Vec<Change>> {
-        let (core, _member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
 
-        core.engine
+        let changes = core
+            .engine
             .get_changes(input.unwrap_or_default())
             .await
-            .map_err(|err| async_graphql::Error::new(err.to_string()))
-            .map(|changes| changes.into_iter().map(|change| change.into()).collect())
+            .map_err(|err| async_graphql::Error::new(err.to_string()))?;
+
+        let changes = core.filter_visible_changes(&principal, changes).await?;
+
+        Ok(changes.into_iter().map(|change| change.into()).collect())
+    }
+
+    /// Pages through `changes`, oldest first.
+    #[graphql(complexity = "connection_complexity(first, last, child_complexity)")]
+    async fn changes_connection(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Change>> {
+        let (_core, principal) = extract_principal(ctx)?;
+
+        changes_connection(ctx, &principal, |_query| {}, after, before, first, last).await
     }
 
     async fn change(&self, ctx: &Context<'_>, id: Uuid) -> Result<Change> {
-        let (core, _member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
 
-        core.engine
+        let change = core
+            .engine
             .get_change(id)
             .await
-            .map_err(|err| async_graphql::Error::new(err.to_string()))
-            .map(|change| change.into())
+            .map_err(|err| async_graphql::Error::new(err.to_string()))?;
+
+        let Some(change) = core.filter_visible_changes(&principal, vec![change]).await?.pop() else {
+            return Err(PlexoAppError::Forbidden.into());
+        };
+
+        Ok(change.into())
     }
 }
 
 #[derive(Default)]
 pub struct ChangesGraphQLMutation;
 
-#[Object]
+/// The change log is an audit trail, so its entries can't be created, edited or deleted
+/// through the API. Admins can only redact what an entry recorded.
+#[Object(guard = "OrganizationWriteGuard.and(AdminGuard)")]
 impl ChangesGraphQLMutation {
-    // TODO: It's possible that this method may not work correctly, as the owner_id is being ignored by async_graphql
-    async fn create_change(&self, ctx: &Context<'_>, input: CreateChangeInput) -> Result<Change> {
+    /// Erases the content of a change, keeping that it happened. The redaction shows up in
+    /// the change log as a change about the entry, along with the reason.
+    async fn redact_change(&self, ctx: &Context<'_>, id: Uuid, reason: Option<String>) -> Result<Change> {
         let (core, member_id) = extract_context(ctx)?;
 
-        let mut input = input;
-        input.owner_id = member_id;
-
-        core.engine
-            .create_change(input)
+        core.redact_change(id, member_id, reason)
             .await
-            .map_err(|err| async_graphql::Error::new(err.to_string()))
             .map(|change| change.into())
-    }
-
-    async fn update_change(&self, ctx: &Context<'_>, id: Uuid, input: UpdateChangeInput) -> Result<Change> {
-        let (core, member_id) = extract_context(ctx)?;
-
-        let saved_input = input.clone();
-
-        let change = core.engine.update_change(id, input).await?;
-
-        let change = change.clone();
-        let saved_change = change.clone();
-
-        create_change(
-            &core,
-            member_id,
-            change.id,
-            ChangeOperation::Update,
-            ChangeResourceType::Changes,
-            serde_json::to_string(&json!({
-                "input": saved_input,
-                "result": change,
-            }))
-            .unwrap(),
-        )
-        .await
-        .unwrap();
-
-        Ok(saved_change.into())
-    }
-
-    async fn delete_change(&self, ctx: &Context<'_>, id: Uuid) -> Result<Change> {
-        let (core, _member_id) = extract_context(ctx)?;
-
-        let change = core.engine.delete_change(id).await?;
-        let saved_change = change.clone();
-
-        create_change(
-            &core,
-            change.owner_id,
-            change.id,
-            ChangeOperation::Delete,
-            ChangeResourceType::Changes,
-            serde_json::to_string(&json!({
-                "result": change,
-            }))
-            .unwrap(),
-        )
-        .await
-        .unwrap();
-
-        Ok(saved_change.into())
+            .map_err(|err| err.into())
     }
 }
 
 #[derive(Default)]
 pub struct ChangesGraphQLSubscription;
 
-// #[Subscription]
-// impl ChangesGraphQLSubscription {
-//     async fn events_change(&self) -> impl Stream<Item = i32> {
-//         todo!()
-//     }
-// }
+#[Subscription]
+impl ChangesGraphQLSubscription {
+    /// New change log entries, for activity feeds.
+    async fn changes(
+        &self,
+        ctx: &Context<'_>,
+        resource_type: Option<ChangeResourceType>,
+        owner_id: Option<Uuid>,
+        project_id: Option<Uuid>,
+    ) -> Result<impl Stream<Item = Change>> {
+        change_events(ctx, resource_type, owner_id, project_id).await
+    }
+}
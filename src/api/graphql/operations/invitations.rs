@@ -0,0 +1,58 @@
+use async_graphql::{Context, InputObject, Object, Result};
+use plexo_sdk::resources::members::member::MemberRole;
+use uuid::Uuid;
+
+use crate::{
+    api::graphql::{
+        commons::extract_context,
+        guards::{AdminGuard, OrganizationWriteGuard},
+    },
+    auth::invitations::Invitation,
+};
+
+#[derive(Default)]
+pub struct InvitationsGraphQLQuery;
+
+#[Object]
+impl InvitationsGraphQLQuery {
+    #[graphql(guard = "AdminGuard")]
+    async fn invitations(&self, ctx: &Context<'_>) -> Result<Vec<Invitation>> {
+        let (core, _member_id) = extract_context(ctx)?;
+
+        core.get_pending_invitations().await.map_err(|err| err.into())
+    }
+}
+
+#[derive(Default)]
+pub struct InvitationsGraphQLMutation;
+
+#[derive(InputObject)]
+struct InviteMemberInput {
+    email: String,
+    role: MemberRole,
+    #[graphql(default)]
+    project_ids: Vec<Uuid>,
+}
+
+#[Object(guard = "OrganizationWriteGuard.and(AdminGuard)")]
+impl InvitationsGraphQLMutation {
+    async fn invite_member(&self, ctx: &Context<'_>, input: InviteMemberInput) -> Result<Invitation> {
+        let (core, member_id) = extract_context(ctx)?;
+
+        core.invite_member(member_id, input.email, input.role, input.project_ids)
+            .await
+            .map_err(|err| err.into())
+    }
+
+    async fn resend_invitation(&self, ctx: &Context<'_>, id: Uuid) -> Result<Invitation> {
+        let (core, _member_id) = extract_context(ctx)?;
+
+        core.resend_invitation(id).await.map_err(|err| err.into())
+    }
+
+    async fn revoke_invitation(&self, ctx: &Context<'_>, id: Uuid) -> Result<Invitation> {
+        let (core, _member_id) = extract_context(ctx)?;
+
+        core.revoke_invitation(id).await.map_err(|err| err.into())
+    }
+}
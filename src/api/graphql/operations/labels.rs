@@ -1,6 +1,10 @@
 use crate::api::graphql::{
     commons::{create_change, extract_context},
+    guards::OrganizationWriteGuard,
+    limits::{connection_complexity, list_complexity},
+    pagination::{keyset_connection, KeysetConnection},
     resources::labels::Label,
+    subscriptions::{listen_events, resource_events, EventFilter, ResourceEvent},
 };
 use async_graphql::{Context, Object, Result, Subscription};
 
@@ -10,7 +14,7 @@ use plexo_sdk::resources::{
 };
 use serde_json::json;
 use tokio::task;
-use tokio_stream::{Stream, StreamExt};
+use tokio_stream::Stream;
 use uuid::Uuid;
 
 #[derive(Default)]
@@ -18,6 +22,7 @@ pub struct LabelsGraphQLQuery;
 
 #[Object]
 impl LabelsGraphQLQuery {
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn labels(&self, ctx: &Context<'_>, input: Option<GetLabelsInput>) -> Result<Vec<Label>> {
         let (core, _member_id) = extract_context(ctx)?;
 
@@ -28,6 +33,31 @@ impl LabelsGraphQLQuery {
             .map(|labels| labels.into_iter().map(|label| label.into()).collect())
     }
 
+    /// Pages through `labels`, oldest first.
+    #[graphql(complexity = "connection_complexity(first, last, child_complexity)")]
+    async fn labels_connection(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Label>> {
+        let (core, _member_id) = extract_context(ctx)?;
+
+        keyset_connection(
+            ctx,
+            "labels",
+            |_query| {},
+            &core.loaders.label_loader,
+            after,
+            before,
+            first,
+            last,
+        )
+        .await
+    }
+
     async fn label(&self, ctx: &Context<'_>, id: Uuid) -> Result<Label> {
         let (core, _member_id) = extract_context(ctx)?;
 
@@ -42,7 +72,7 @@ impl LabelsGraphQLQuery {
 #[derive(Default)]
 pub struct LabelsGraphQLMutation;
 
-#[Object]
+#[Object(guard = "OrganizationWriteGuard")]
 impl LabelsGraphQLMutation {
     // TODO: It's possible that this method may not work correctly, as the owner_id is being ignored by async_graphql
     async fn create_label(&self, ctx: &Context<'_>, input: CreateLabelInput) -> Result<Label> {
@@ -139,13 +169,23 @@ pub struct LabelsGraphQLSubscription;
 
 #[Subscription]
 impl LabelsGraphQLSubscription {
-    async fn labels(&self, ctx: &Context<'_>) -> impl Stream<Item = ListenEvent> {
-        let (core, _member_id) = extract_context(ctx).unwrap();
+    async fn labels(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = ListenEvent>> {
+        listen_events(ctx, ChangeResourceType::Labels, EventFilter::default()).await
+    }
 
-        core.engine
-            .listen(ChangeResourceType::Labels)
-            .await
-            .unwrap()
-            .map(|x| x.unwrap())
+    /// Changes to labels along with the label as it ends up.
+    async fn label_subscription(
+        &self,
+        ctx: &Context<'_>,
+        label_ids: Option<Vec<Uuid>>,
+        operations: Option<Vec<ChangeOperation>>,
+    ) -> Result<impl Stream<Item = ResourceEvent<Label>>> {
+        resource_events(
+            ctx,
+            ChangeResourceType::Labels,
+            |loaders| &loaders.label_loader,
+            EventFilter::new(label_ids, operations),
+        )
+        .await
     }
 }
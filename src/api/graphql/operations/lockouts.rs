@@ -0,0 +1,45 @@
+use async_graphql::{Context, Object, Result};
+use uuid::Uuid;
+
+use crate::{
+    api::graphql::{
+        commons::extract_context,
+        guards::{AdminGuard, OrganizationWriteGuard},
+    },
+    auth::login_attempts::LoginLockout,
+};
+
+#[derive(Default)]
+pub struct LockoutsGraphQLQuery;
+
+#[Object]
+impl LockoutsGraphQLQuery {
+    /// Logins blocked after too many failed attempts. Only active lockouts by default.
+    #[graphql(guard = "AdminGuard")]
+    async fn login_lockouts(&self, ctx: &Context<'_>, #[graphql(default = true)] active_only: bool) -> Result<Vec<LoginLockout>> {
+        let (core, _member_id) = extract_context(ctx)?;
+
+        core.get_login_lockouts(active_only).await.map_err(|err| err.into())
+    }
+}
+
+#[derive(Default)]
+pub struct LockoutsGraphQLMutation;
+
+#[Object(guard = "OrganizationWriteGuard.and(AdminGuard)")]
+impl LockoutsGraphQLMutation {
+    async fn unlock_login_lockout(&self, ctx: &Context<'_>, id: Uuid) -> Result<LoginLockout> {
+        let (core, member_id) = extract_context(ctx)?;
+
+        core.unlock_login_lockout(id, member_id).await.map_err(|err| err.into())
+    }
+
+    /// Lifts the active lockouts on a member's account and resets their failed attempts.
+    async fn unlock_member_logins(&self, ctx: &Context<'_>, member_id: Uuid) -> Result<Vec<LoginLockout>> {
+        let (core, unlocked_by_id) = extract_context(ctx)?;
+
+        core.unlock_member_logins(member_id, unlocked_by_id)
+            .await
+            .map_err(|err| err.into())
+    }
+}
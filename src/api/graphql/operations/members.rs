@@ -1,11 +1,23 @@
-use crate::api::graphql::{commons::extract_context, resources::members::Member};
+use crate::{
+    api::graphql::{
+        commons::{create_change, extract_context},
+        guards::{AdminGuard, OrganizationWriteGuard},
+        limits::{connection_complexity, list_complexity},
+        pagination::{keyset_connection, KeysetConnection},
+        resources::{members::Member, tasks::Task},
+        subscriptions::{listen_events, resource_events, EventFilter, ResourceEvent},
+    },
+    core::app::Core,
+    errors::app::PlexoAppError,
+};
 use async_graphql::{Context, Object, Result, Subscription};
 
 use plexo_sdk::resources::{
-    changes::change::{ChangeResourceType, ListenEvent},
+    changes::change::{ChangeOperation, ChangeResourceType, ListenEvent},
     members::operations::{CreateMemberInput, GetMembersInput, MemberCrudOperations, UpdateMemberInput},
 };
-use tokio_stream::{Stream, StreamExt};
+use serde_json::json;
+use tokio_stream::Stream;
 use uuid::Uuid;
 
 #[derive(Default)]
@@ -13,14 +25,64 @@ pub struct MembersGraphQLQuery;
 
 #[Object]
 impl MembersGraphQLQuery {
-    async fn members(&self, ctx: &Context<'_>, input: Option<GetMembersInput>) -> Result<Vec<Member>> {
+    /// Deactivated members are left out unless `includeDeactivated` is set, so they don't show
+    /// up when picking assignees.
+    #[graphql(complexity = "list_complexity(child_complexity)")]
+    async fn members(
+        &self,
+        ctx: &Context<'_>,
+        input: Option<GetMembersInput>,
+        include_deactivated: Option<bool>,
+    ) -> Result<Vec<Member>> {
         let (core, _member_id) = extract_context(ctx)?;
 
-        core.engine
+        let members = core
+            .engine
             .get_members(input.unwrap_or_default())
             .await
-            .map_err(|err| async_graphql::Error::new(err.to_string()))
-            .map(|members| members.into_iter().map(|member| member.into()).collect())
+            .map_err(|err| async_graphql::Error::new(err.to_string()))?;
+
+        let deactivated_member_ids = match include_deactivated.unwrap_or(false) {
+            true => vec![],
+            false => core.get_deactivated_member_ids().await?,
+        };
+
+        Ok(members
+            .into_iter()
+            .filter(|member| !deactivated_member_ids.contains(&member.id))
+            .map(|member| member.into())
+            .collect())
+    }
+
+    /// Pages through `members`, oldest first.
+    #[graphql(complexity = "connection_complexity(first, last, child_complexity)")]
+    async fn members_connection(
+        &self,
+        ctx: &Context<'_>,
+        include_deactivated: Option<bool>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Member>> {
+        let (core, _member_id) = extract_context(ctx)?;
+        let include_deactivated = include_deactivated.unwrap_or(false);
+
+        keyset_connection(
+            ctx,
+            "members",
+            move |query| {
+                if !include_deactivated {
+                    query.push(" AND NOT EXISTS (SELECT 1 FROM member_deactivations d WHERE d.member_id = members.id)");
+                }
+            },
+            &core.loaders.member_loader,
+            after,
+            before,
+            first,
+            last,
+        )
+        .await
     }
 
     async fn member(&self, ctx: &Context<'_>, id: Uuid) -> Result<Member> {
@@ -37,7 +99,7 @@ impl MembersGraphQLQuery {
 #[derive(Default)]
 pub struct MembersGraphQLMutation;
 
-#[Object]
+#[Object(guard = "OrganizationWriteGuard.and(AdminGuard)")]
 impl MembersGraphQLMutation {
     async fn create_member(&self, ctx: &Context<'_>, input: CreateMemberInput) -> Result<Member> {
         let (core, _member_id) = extract_context(ctx)?;
@@ -59,14 +121,48 @@ impl MembersGraphQLMutation {
             .map(|member| member.into())
     }
 
+    /// Deactivates the member instead of deleting them, so nothing they own is left behind.
+    #[graphql(deprecation = "Use deactivateMember, which keeps the member's history attributed to them")]
     async fn delete_member(&self, ctx: &Context<'_>, id: Uuid) -> Result<Member> {
+        self.deactivate_member(ctx, id, None).await
+    }
+
+    /// Signs the member out everywhere and keeps them from logging in again, leaving their
+    /// tasks, projects and changes attributed to them. Their open tasks can be handed over
+    /// to `reassignTasksTo` in the same operation.
+    async fn deactivate_member(&self, ctx: &Context<'_>, id: Uuid, reassign_tasks_to: Option<Uuid>) -> Result<Member> {
+        let (core, member_id) = extract_context(ctx)?;
+
+        if id == member_id {
+            return Err(PlexoAppError::InvalidRequest("you can't deactivate yourself".to_string()).into());
+        }
+
+        let member = core.engine.get_member(id).await.map_err(|_| PlexoAppError::MemberNotFound)?;
+
+        if let Some(reassign_tasks_to) = reassign_tasks_to {
+            reassign_open_tasks(&core, member_id, id, reassign_tasks_to).await?;
+        }
+
+        core.deactivate_member(id, Some(member_id)).await?;
+
+        Ok(member.into())
+    }
+
+    async fn reactivate_member(&self, ctx: &Context<'_>, id: Uuid) -> Result<Member> {
         let (core, _member_id) = extract_context(ctx)?;
 
-        core.engine
-            .delete_member(id)
-            .await
-            .map_err(|err| async_graphql::Error::new(err.to_string()))
-            .map(|member| member.into())
+        let member = core.engine.get_member(id).await.map_err(|_| PlexoAppError::MemberNotFound)?;
+
+        core.reactivate_member(id).await?;
+
+        Ok(member.into())
+    }
+
+    /// Hands every open task the member leads or is assigned to over to another member.
+    async fn reassign_open_tasks(&self, ctx: &Context<'_>, id: Uuid, to_member_id: Uuid) -> Result<Vec<Task>> {
+        let (core, member_id) = extract_context(ctx)?;
+
+        reassign_open_tasks(&core, member_id, id, to_member_id).await
     }
 }
 
@@ -75,13 +171,50 @@ pub struct MembersGraphQLSubscription;
 
 #[Subscription]
 impl MembersGraphQLSubscription {
-    async fn members(&self, ctx: &Context<'_>) -> impl Stream<Item = ListenEvent> {
-        let (core, _member_id) = extract_context(ctx).unwrap();
+    async fn members(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = ListenEvent>> {
+        listen_events(ctx, ChangeResourceType::Members, EventFilter::default()).await
+    }
 
-        core.engine
-            .listen(ChangeResourceType::Members)
+    /// Changes to members along with the member as it ends up.
+    async fn member_subscription(
+        &self,
+        ctx: &Context<'_>,
+        member_ids: Option<Vec<Uuid>>,
+        operations: Option<Vec<ChangeOperation>>,
+    ) -> Result<impl Stream<Item = ResourceEvent<Member>>> {
+        resource_events(
+            ctx,
+            ChangeResourceType::Members,
+            |loaders| &loaders.member_loader,
+            EventFilter::new(member_ids, operations),
+        )
+        .await
+    }
+}
+
+async fn reassign_open_tasks(core: &Core, member_id: Uuid, from_member_id: Uuid, to_member_id: Uuid) -> Result<Vec<Task>> {
+    let tasks = core.reassign_open_tasks(from_member_id, to_member_id).await?;
+
+    for task in tasks.iter().cloned() {
+        let core = core.clone();
+
+        tokio::spawn(async move {
+            create_change(
+                &core,
+                member_id,
+                task.id,
+                ChangeOperation::Update,
+                ChangeResourceType::Tasks,
+                serde_json::to_string(&json!({
+                    "reassigned_from": from_member_id,
+                    "result": task,
+                }))
+                .unwrap(),
+            )
             .await
-            .unwrap()
-            .map(|x| x.unwrap())
+            .unwrap();
+        });
     }
+
+    Ok(tasks.into_iter().map(|task| task.into()).collect())
 }
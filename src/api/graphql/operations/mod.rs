@@ -1,9 +1,13 @@
 pub mod assets;
 pub mod auth;
 pub mod changes;
+pub mod invitations;
 pub mod labels;
+pub mod lockouts;
 pub mod members;
 pub mod profile;
 pub mod projects;
+pub mod service_accounts;
+pub mod settings;
 pub mod tasks;
 pub mod teams;
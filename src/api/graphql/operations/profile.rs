@@ -1,11 +1,23 @@
-use async_graphql::{Context, InputObject, Object, Result};
+use async_graphql::{Context, InputObject, Object, Result, SimpleObject};
 use plexo_sdk::resources::members::{
     extensions::MembersExtensionOperations,
     operations::{MemberCrudOperations, UpdateMemberInputBuilder},
 };
+use uuid::Uuid;
 
 use crate::{
-    api::graphql::{commons::extract_context, resources::members::Member},
+    api::graphql::{
+        commons::{extract_context, extract_principal},
+        guards::WriteScopeGuard,
+        resources::members::Member,
+    },
+    auth::{
+        api_keys::{ApiKey, ApiKeyScope},
+        commons::validate_email,
+        identities::MemberIdentity,
+        sessions::Session,
+        totp::TotpEnrollment,
+    },
     errors::app::PlexoAppError,
 };
 
@@ -23,6 +35,31 @@ impl ProfileGraphQLQuery {
             .map(|member| member.into())
             .map_err(|err| async_graphql::Error::new(err.to_string()))
     }
+
+    async fn api_keys(&self, ctx: &Context<'_>) -> Result<Vec<ApiKey>> {
+        let (core, member_id) = extract_context(ctx)?;
+
+        core.get_api_keys(member_id).await.map_err(|err| err.into())
+    }
+
+    async fn sessions(&self, ctx: &Context<'_>) -> Result<Vec<Session>> {
+        let (core, member_id) = extract_context(ctx)?;
+
+        core.get_sessions(member_id).await.map_err(|err| err.into())
+    }
+
+    async fn two_factor_enabled(&self, ctx: &Context<'_>) -> Result<bool> {
+        let (core, member_id) = extract_context(ctx)?;
+
+        core.is_totp_enabled(member_id).await.map_err(|err| err.into())
+    }
+
+    /// GitHub and OIDC accounts the member can sign in with.
+    async fn identities(&self, ctx: &Context<'_>) -> Result<Vec<MemberIdentity>> {
+        let (core, member_id) = extract_context(ctx)?;
+
+        core.get_identities(member_id).await.map_err(|err| err.into())
+    }
 }
 
 #[derive(Default)]
@@ -41,16 +78,45 @@ struct UpdatePasswordInput {
     new_password: String,
 }
 
-#[Object]
+#[derive(InputObject)]
+struct CreateApiKeyInput {
+    name: String,
+    scope: ApiKeyScope,
+    project_id: Option<Uuid>,
+}
+
+#[derive(InputObject)]
+struct UpdateApiKeyInput {
+    name: Option<String>,
+    scope: Option<ApiKeyScope>,
+}
+
+#[derive(SimpleObject)]
+pub struct CreateApiKeyResponse {
+    /// The plain API key, only returned once at creation time.
+    pub key: String,
+    pub api_key: ApiKey,
+}
+
+#[Object(guard = "WriteScopeGuard")]
 impl ProfileGraphQLMutation {
     async fn update_profile(&self, ctx: &Context<'_>, input: UpdateProfileInput) -> Result<Member> {
-        let (core, member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+
+        // The sign-in email and password can only be changed by the member themselves.
+        if principal.is_api_key() {
+            return Err(PlexoAppError::InsufficientScope.into());
+        }
+
+        let member_id = principal.member_id;
 
         let input_email = input.email;
         let input_name = input.name;
         let input_photo_url = input.photo_url;
 
         if let Some(email) = input_email.clone() {
+            validate_email(&email)?;
+
             if let Ok(Some(_member)) = core.engine.get_member_by_email(email).await {
                 return Err(async_graphql::Error::new("Email already in use"));
             }
@@ -58,7 +124,7 @@ impl ProfileGraphQLMutation {
 
         let mut update_member_input = UpdateMemberInputBuilder::default();
 
-        if let Some(email) = input_email {
+        if let Some(email) = input_email.clone() {
             update_member_input = update_member_input.email(email);
         }
 
@@ -70,18 +136,42 @@ impl ProfileGraphQLMutation {
             update_member_input = update_member_input.photo_url(photo_url);
         }
 
-        core.engine
+        let member = core
+            .engine
             .update_member(member_id, update_member_input.build()?)
             .await
-            .map(|member| member.into())
-            .map_err(|err| async_graphql::Error::new(err.to_string()))
+            .map_err(|err| async_graphql::Error::new(err.to_string()))?;
+
+        // A new email has to be verified again.
+        if input_email.is_some() {
+            core.start_email_verification(&member).await?;
+        }
+
+        Ok(member.into())
     }
 
-    async fn update_password(&self, ctx: &Context<'_>, input: UpdatePasswordInput) -> Result<Member> {
+    /// Sends a new verification link while the member's email is unverified.
+    async fn resend_email_verification(&self, ctx: &Context<'_>) -> Result<bool> {
         let (core, member_id) = extract_context(ctx)?;
 
         let member = core.engine.get_member(member_id).await?;
 
+        core.resend_email_verification(&member).await?;
+
+        Ok(true)
+    }
+
+    async fn update_password(&self, ctx: &Context<'_>, input: UpdatePasswordInput) -> Result<Member> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        if principal.is_api_key() {
+            return Err(PlexoAppError::InsufficientScope.into());
+        }
+
+        let member_id = principal.member_id;
+
+        let member = core.engine.get_member(member_id).await?;
+
         let current_password = input.current_password;
         let new_password = input.new_password;
 
@@ -97,13 +187,143 @@ impl ProfileGraphQLMutation {
 
         let new_password_hash = core.auth.hash_password(&new_password);
 
-        core.engine
+        let member = core
+            .engine
             .update_member(
                 member_id,
                 UpdateMemberInputBuilder::default().password_hash(new_password_hash).build()?,
             )
             .await
-            .map(|member| member.into())
-            .map_err(|err| async_graphql::Error::new(err.to_string()))
+            .map_err(|err| async_graphql::Error::new(err.to_string()))?;
+
+        core.revoke_all_sessions(member_id).await?;
+
+        Ok(member.into())
+    }
+
+    async fn revoke_session(&self, ctx: &Context<'_>, id: Uuid) -> Result<Session> {
+        let (core, member_id) = extract_context(ctx)?;
+
+        core.revoke_session(member_id, id).await.map_err(|err| err.into())
+    }
+
+    /// Signs the member out of every device, including the current one.
+    async fn revoke_all_sessions(&self, ctx: &Context<'_>) -> Result<u64> {
+        let (core, member_id) = extract_context(ctx)?;
+
+        core.revoke_all_sessions(member_id).await.map_err(|err| err.into())
+    }
+
+    async fn create_api_key(&self, ctx: &Context<'_>, input: CreateApiKeyInput) -> Result<CreateApiKeyResponse> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        // API keys can't be used to mint or manage other API keys.
+        if principal.is_api_key() {
+            return Err(PlexoAppError::InsufficientScope.into());
+        }
+
+        let (api_key, key) = core
+            .create_api_key(principal.member_id, input.name, input.scope, input.project_id)
+            .await?;
+
+        Ok(CreateApiKeyResponse { key, api_key })
+    }
+
+    async fn update_api_key(&self, ctx: &Context<'_>, id: Uuid, input: UpdateApiKeyInput) -> Result<ApiKey> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        if principal.is_api_key() {
+            return Err(PlexoAppError::InsufficientScope.into());
+        }
+
+        core.update_api_key(principal.member_id, id, input.name, input.scope)
+            .await
+            .map_err(|err| err.into())
+    }
+
+    async fn revoke_api_key(&self, ctx: &Context<'_>, id: Uuid) -> Result<ApiKey> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        if principal.is_api_key() {
+            return Err(PlexoAppError::InsufficientScope.into());
+        }
+
+        core.revoke_api_key(principal.member_id, id).await.map_err(|err| err.into())
+    }
+
+    /// Returns a new TOTP secret and its provisioning URI. Two-factor authentication stays
+    /// off until `confirmTwoFactor` receives a code from the authenticator app.
+    async fn enroll_two_factor(&self, ctx: &Context<'_>) -> Result<TotpEnrollment> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        if principal.is_api_key() {
+            return Err(PlexoAppError::InsufficientScope.into());
+        }
+
+        core.start_totp_enrollment(principal.member_id)
+            .await
+            .map_err(|err| err.into())
+    }
+
+    /// Enables two-factor authentication and returns the recovery codes, only shown once.
+    async fn confirm_two_factor(&self, ctx: &Context<'_>, code: String) -> Result<Vec<String>> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        if principal.is_api_key() {
+            return Err(PlexoAppError::InsufficientScope.into());
+        }
+
+        core.confirm_totp_enrollment(principal.member_id, code.as_str())
+            .await
+            .map_err(|err| err.into())
+    }
+
+    async fn disable_two_factor(&self, ctx: &Context<'_>, code: String) -> Result<bool> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        if principal.is_api_key() {
+            return Err(PlexoAppError::InsufficientScope.into());
+        }
+
+        core.disable_totp(principal.member_id, code.as_str()).await?;
+
+        Ok(true)
+    }
+
+    /// Invalidates the remaining recovery codes and returns a new set.
+    async fn regenerate_recovery_codes(&self, ctx: &Context<'_>, code: String) -> Result<Vec<String>> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        if principal.is_api_key() {
+            return Err(PlexoAppError::InsufficientScope.into());
+        }
+
+        core.regenerate_recovery_codes(principal.member_id, code.as_str())
+            .await
+            .map_err(|err| err.into())
+    }
+
+    /// Returns the URL to open in the browser to connect an account from `provider` (`github`
+    /// or the name of an OIDC provider). The browser comes back to `redirectTo` once linked.
+    async fn link_identity(&self, ctx: &Context<'_>, provider: String, redirect_to: Option<String>) -> Result<String> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        if principal.is_api_key() {
+            return Err(PlexoAppError::InsufficientScope.into());
+        }
+
+        core.identity_link_url(principal.member_id, provider.as_str(), redirect_to)
+            .map_err(|err| err.into())
+    }
+
+    /// Fails when it's the only way left to sign in to the account.
+    async fn unlink_identity(&self, ctx: &Context<'_>, id: Uuid) -> Result<MemberIdentity> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        if principal.is_api_key() {
+            return Err(PlexoAppError::InsufficientScope.into());
+        }
+
+        core.unlink_identity(principal.member_id, id).await.map_err(|err| err.into())
     }
 }
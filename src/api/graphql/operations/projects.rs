@@ -1,6 +1,13 @@
-use crate::api::graphql::{
-    commons::{create_change, extract_context},
-    resources::projects::Project,
+use crate::{
+    api::graphql::{
+        commons::{create_change, extract_principal},
+        guards::WriteGuard,
+        limits::{connection_complexity, list_complexity},
+        pagination::{keyset_connection, KeysetConnection},
+        resources::projects::Project,
+        subscriptions::{listen_events, resource_events, EventFilter, ResourceEvent},
+    },
+    auth::project_roles::{ProjectRole, ProjectRoleAssignment},
 };
 use async_graphql::{Context, Object, Result, Subscription};
 
@@ -11,7 +18,7 @@ use plexo_sdk::resources::{
 
 use serde_json::json;
 use tokio::task;
-use tokio_stream::{Stream, StreamExt};
+use tokio_stream::Stream;
 use uuid::Uuid;
 
 #[derive(Default)]
@@ -19,18 +26,54 @@ pub struct ProjectsGraphQLQuery;
 
 #[Object]
 impl ProjectsGraphQLQuery {
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn projects(&self, ctx: &Context<'_>, input: Option<GetProjectsInput>) -> Result<Vec<Project>> {
-        let (core, _member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+        let visible_projects = core.visible_projects(&principal).await?;
 
         core.engine
             .get_projects(input.unwrap_or_default())
             .await
             .map_err(|err| async_graphql::Error::new(err.to_string()))
-            .map(|projects| projects.into_iter().map(|project| project.into()).collect())
+            .map(|projects| {
+                projects
+                    .into_iter()
+                    .filter(|project| visible_projects.contains(Some(project.id)))
+                    .map(|project| project.into())
+                    .collect()
+            })
+    }
+
+    /// Pages through `projects`, oldest first.
+    #[graphql(complexity = "connection_complexity(first, last, child_complexity)")]
+    async fn projects_connection(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Project>> {
+        let (core, principal) = extract_principal(ctx)?;
+        let visible_projects = core.visible_projects(&principal).await?;
+
+        keyset_connection(
+            ctx,
+            "projects",
+            move |query| visible_projects.push_conditions(query, "projects.id"),
+            &core.loaders.project_loader,
+            after,
+            before,
+            first,
+            last,
+        )
+        .await
     }
 
     async fn project(&self, ctx: &Context<'_>, id: Uuid) -> Result<Project> {
-        let (core, _member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+
+        core.authorize_project_role(&principal, Some(id), ProjectRole::Viewer).await?;
 
         core.engine
             .get_project(id)
@@ -38,16 +81,28 @@ impl ProjectsGraphQLQuery {
             .map_err(|err| async_graphql::Error::new(err.to_string()))
             .map(|project| project.into())
     }
+
+    async fn project_roles(&self, ctx: &Context<'_>, project_id: Uuid) -> Result<Vec<ProjectRoleAssignment>> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        core.authorize_project_role(&principal, Some(project_id), ProjectRole::Viewer)
+            .await?;
+
+        core.get_project_roles(project_id).await.map_err(|err| err.into())
+    }
 }
 
 #[derive(Default)]
 pub struct ProjectsGraphQLMutation;
 
-#[Object]
+#[Object(guard = "WriteGuard")]
 impl ProjectsGraphQLMutation {
     // TODO: It's possible that this method may not work correctly, as the owner_id is being ignored by async_graphql
     async fn create_project(&self, ctx: &Context<'_>, input: CreateProjectInput) -> Result<Project> {
-        let (core, member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+        let member_id = principal.member_id;
+
+        principal.authorize_project(None)?;
 
         let mut input = input;
         input.owner_id = member_id;
@@ -91,7 +146,10 @@ impl ProjectsGraphQLMutation {
     }
 
     async fn update_project(&self, ctx: &Context<'_>, id: Uuid, input: UpdateProjectInput) -> Result<Project> {
-        let (core, member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+        let member_id = principal.member_id;
+
+        core.authorize_project_role(&principal, Some(id), ProjectRole::Lead).await?;
 
         let saved_input = input.clone();
 
@@ -121,7 +179,9 @@ impl ProjectsGraphQLMutation {
     }
 
     async fn delete_project(&self, ctx: &Context<'_>, id: Uuid) -> Result<Project> {
-        let (core, _member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+
+        core.authorize_project_role(&principal, Some(id), ProjectRole::Owner).await?;
 
         let project = core.engine.delete_project(id).await?;
         let saved_project = project.clone();
@@ -150,6 +210,35 @@ impl ProjectsGraphQLMutation {
         //     .map_err(|err| async_graphql::Error::new(err.to_string()))
         //     .map(|project| project.into())
     }
+
+    /// Grants a member an explicit role in the project, replacing any previous one.
+    async fn set_project_role(
+        &self,
+        ctx: &Context<'_>,
+        project_id: Uuid,
+        member_id: Uuid,
+        role: ProjectRole,
+    ) -> Result<ProjectRoleAssignment> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        core.authorize_project_role(&principal, Some(project_id), ProjectRole::Owner)
+            .await?;
+
+        core.set_project_role(project_id, member_id, role)
+            .await
+            .map_err(|err| err.into())
+    }
+
+    async fn remove_project_role(&self, ctx: &Context<'_>, project_id: Uuid, member_id: Uuid) -> Result<ProjectRoleAssignment> {
+        let (core, principal) = extract_principal(ctx)?;
+
+        core.authorize_project_role(&principal, Some(project_id), ProjectRole::Owner)
+            .await?;
+
+        core.remove_project_role(project_id, member_id)
+            .await
+            .map_err(|err| err.into())
+    }
 }
 
 #[derive(Default)]
@@ -157,13 +246,23 @@ pub struct ProjectsGraphQLSubscription;
 
 #[Subscription]
 impl ProjectsGraphQLSubscription {
-    async fn projects(&self, ctx: &Context<'_>) -> impl Stream<Item = ListenEvent> {
-        let (core, _member_id) = extract_context(ctx).unwrap();
+    async fn projects(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = ListenEvent>> {
+        listen_events(ctx, ChangeResourceType::Projects, EventFilter::default()).await
+    }
 
-        core.engine
-            .listen(ChangeResourceType::Projects)
-            .await
-            .unwrap()
-            .map(|x| x.unwrap())
+    /// Changes to projects along with the project as it ends up.
+    async fn project_subscription(
+        &self,
+        ctx: &Context<'_>,
+        project_ids: Option<Vec<Uuid>>,
+        operations: Option<Vec<ChangeOperation>>,
+    ) -> Result<impl Stream<Item = ResourceEvent<Project>>> {
+        resource_events(
+            ctx,
+            ChangeResourceType::Projects,
+            |loaders| &loaders.project_loader,
+            EventFilter::new(project_ids, operations),
+        )
+        .await
     }
 }
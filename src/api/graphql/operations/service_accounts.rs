@@ -0,0 +1,127 @@
+use async_graphql::{Context, InputObject, Object, Result};
+use plexo_sdk::resources::members::member::MemberRole;
+use uuid::Uuid;
+
+use crate::{
+    api::graphql::{
+        commons::extract_context,
+        guards::{AdminGuard, OrganizationWriteGuard},
+    },
+    auth::{
+        api_keys::{ApiKey, ApiKeyScope},
+        service_accounts::ServiceAccount,
+    },
+    errors::app::PlexoAppError,
+};
+
+use super::profile::CreateApiKeyResponse;
+
+#[derive(Default)]
+pub struct ServiceAccountsGraphQLQuery;
+
+#[Object]
+impl ServiceAccountsGraphQLQuery {
+    #[graphql(guard = "AdminGuard")]
+    async fn service_accounts(&self, ctx: &Context<'_>) -> Result<Vec<ServiceAccount>> {
+        let (core, _member_id) = extract_context(ctx)?;
+
+        core.get_service_accounts().await.map_err(|err| err.into())
+    }
+
+    #[graphql(guard = "AdminGuard")]
+    async fn service_account_api_keys(&self, ctx: &Context<'_>, id: Uuid) -> Result<Vec<ApiKey>> {
+        let (core, _member_id) = extract_context(ctx)?;
+
+        if core.get_service_account(id).await?.is_none() {
+            return Err(PlexoAppError::ServiceAccountNotFound.into());
+        }
+
+        core.get_api_keys(id).await.map_err(|err| err.into())
+    }
+}
+
+#[derive(Default)]
+pub struct ServiceAccountsGraphQLMutation;
+
+#[derive(InputObject)]
+struct CreateServiceAccountInput {
+    name: String,
+    description: Option<String>,
+    #[graphql(default)]
+    role: MemberRole,
+    #[graphql(default_with = "ApiKeyScope::ReadOnly")]
+    scope: ApiKeyScope,
+    /// Leave empty to allow every project.
+    #[graphql(default)]
+    project_ids: Vec<Uuid>,
+}
+
+#[derive(InputObject)]
+struct UpdateServiceAccountInput {
+    description: Option<String>,
+    scope: Option<ApiKeyScope>,
+    project_ids: Option<Vec<Uuid>>,
+}
+
+#[derive(InputObject)]
+struct CreateServiceAccountApiKeyInput {
+    name: String,
+    scope: ApiKeyScope,
+    project_id: Option<Uuid>,
+}
+
+#[Object(guard = "OrganizationWriteGuard.and(AdminGuard)")]
+impl ServiceAccountsGraphQLMutation {
+    async fn create_service_account(&self, ctx: &Context<'_>, input: CreateServiceAccountInput) -> Result<ServiceAccount> {
+        let (core, member_id) = extract_context(ctx)?;
+
+        core.create_service_account(
+            member_id,
+            input.name,
+            input.description,
+            input.role,
+            input.scope,
+            input.project_ids,
+        )
+        .await
+        .map_err(|err| err.into())
+    }
+
+    async fn update_service_account(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        input: UpdateServiceAccountInput,
+    ) -> Result<ServiceAccount> {
+        let (core, _member_id) = extract_context(ctx)?;
+
+        core.update_service_account(id, input.description, input.scope, input.project_ids)
+            .await
+            .map_err(|err| err.into())
+    }
+
+    async fn create_service_account_api_key(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        input: CreateServiceAccountApiKeyInput,
+    ) -> Result<CreateApiKeyResponse> {
+        let (core, _member_id) = extract_context(ctx)?;
+
+        let (api_key, key) = core
+            .create_service_account_api_key(id, input.name, input.scope, input.project_id)
+            .await?;
+
+        Ok(CreateApiKeyResponse { key, api_key })
+    }
+
+    async fn revoke_service_account_api_key(&self, ctx: &Context<'_>, id: Uuid, api_key_id: Uuid) -> Result<ApiKey> {
+        let (core, _member_id) = extract_context(ctx)?;
+
+        if core.get_service_account(id).await?.is_none() {
+            return Err(PlexoAppError::ServiceAccountNotFound.into());
+        }
+
+        core.revoke_api_key(id, api_key_id).await.map_err(|err| err.into())
+    }
+}
@@ -0,0 +1,47 @@
+use async_graphql::{Context, InputObject, Object, Result};
+
+use crate::{
+    api::graphql::{
+        commons::extract_context,
+        guards::{AdminGuard, OrganizationWriteGuard},
+    },
+    auth::email_verifications::SelfRegistrationSettings,
+};
+
+#[derive(Default)]
+pub struct SettingsGraphQLQuery;
+
+#[Object]
+impl SettingsGraphQLQuery {
+    #[graphql(guard = "AdminGuard")]
+    async fn self_registration_settings(&self, ctx: &Context<'_>) -> Result<SelfRegistrationSettings> {
+        let (core, _member_id) = extract_context(ctx)?;
+
+        core.get_self_registration_settings().await.map_err(|err| err.into())
+    }
+}
+
+#[derive(Default)]
+pub struct SettingsGraphQLMutation;
+
+#[derive(InputObject)]
+struct UpdateSelfRegistrationSettingsInput {
+    require_verified_email: Option<bool>,
+    /// Replaces the whole list. An empty list lets any email register.
+    allowed_email_domains: Option<Vec<String>>,
+}
+
+#[Object(guard = "OrganizationWriteGuard.and(AdminGuard)")]
+impl SettingsGraphQLMutation {
+    async fn update_self_registration_settings(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdateSelfRegistrationSettingsInput,
+    ) -> Result<SelfRegistrationSettings> {
+        let (core, member_id) = extract_context(ctx)?;
+
+        core.update_self_registration_settings(input.require_verified_email, input.allowed_email_domains, member_id)
+            .await
+            .map_err(|err| err.into())
+    }
+}
@@ -1,6 +1,13 @@
-use crate::api::graphql::{
-    commons::{create_change, extract_context},
-    resources::tasks::Task,
+use crate::{
+    api::graphql::{
+        commons::{create_change, extract_principal},
+        guards::WriteGuard,
+        limits::{connection_complexity, list_complexity},
+        pagination::{keyset_connection, KeysetConnection},
+        resources::tasks::Task,
+        subscriptions::{listen_events, resource_events, EventFilter, ResourceEvent},
+    },
+    auth::project_roles::ProjectRole,
 };
 use async_graphql::{Context, Object, Result, Subscription};
 
@@ -13,7 +20,7 @@ use plexo_sdk::resources::{
 };
 use serde_json::json;
 use tokio::task;
-use tokio_stream::{Stream, StreamExt};
+use tokio_stream::Stream;
 use uuid::Uuid;
 
 #[derive(Default)]
@@ -21,35 +28,78 @@ pub struct TasksGraphQLQuery;
 
 #[Object]
 impl TasksGraphQLQuery {
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn tasks(&self, ctx: &Context<'_>, input: Option<GetTasksInput>) -> Result<Vec<Task>> {
-        let (core, _member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+        let visible_projects = core.visible_projects(&principal).await?;
 
         core.engine
             .get_tasks(input)
             .await
             .map_err(|err| async_graphql::Error::new(err.to_string()))
-            .map(|tasks| tasks.into_iter().map(|task| task.into()).collect())
+            .map(|tasks| {
+                tasks
+                    .into_iter()
+                    .filter(|task| visible_projects.contains(task.project_id))
+                    .map(|task| task.into())
+                    .collect()
+            })
+    }
+
+    /// Pages through `tasks`, oldest first.
+    #[graphql(complexity = "connection_complexity(first, last, child_complexity)")]
+    async fn tasks_connection(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Task>> {
+        let (core, principal) = extract_principal(ctx)?;
+        let visible_projects = core.visible_projects(&principal).await?;
+
+        keyset_connection(
+            ctx,
+            "tasks",
+            move |query| visible_projects.push_conditions(query, "tasks.project_id"),
+            &core.loaders.task_loader,
+            after,
+            before,
+            first,
+            last,
+        )
+        .await
     }
 
     async fn task(&self, ctx: &Context<'_>, id: Uuid) -> Result<Task> {
-        let (core, _member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
 
-        core.engine
+        let task = core
+            .engine
             .get_task(id)
             .await
-            .map_err(|err| async_graphql::Error::new(err.to_string()))
-            .map(|task| task.into())
+            .map_err(|err| async_graphql::Error::new(err.to_string()))?;
+
+        core.authorize_project_role(&principal, task.project_id, ProjectRole::Viewer)
+            .await?;
+
+        Ok(task.into())
     }
 }
 
 #[derive(Default)]
 pub struct TasksGraphQLMutation;
 
-#[Object]
+#[Object(guard = "WriteGuard")]
 impl TasksGraphQLMutation {
     // TODO: It's possible that this method may not work correctly, as the owner_id is being ignored by async_graphql
     async fn create_task(&self, ctx: &Context<'_>, input: CreateTaskInput) -> Result<Task> {
-        let (core, member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+        let member_id = principal.member_id;
+
+        core.authorize_project_role(&principal, input.project_id, ProjectRole::Member)
+            .await?;
 
         let mut input = input;
         input.owner_id = member_id;
@@ -82,7 +132,13 @@ impl TasksGraphQLMutation {
     }
 
     async fn create_tasks(&self, ctx: &Context<'_>, input: CreateTasksInput) -> Result<Vec<Task>> {
-        let (core, member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+        let member_id = principal.member_id;
+
+        for task in input.tasks.iter() {
+            core.authorize_project_role(&principal, task.project_id, ProjectRole::Member)
+                .await?;
+        }
 
         let mut input = input;
         input.tasks.iter_mut().for_each(|task| task.owner_id = member_id);
@@ -119,7 +175,17 @@ impl TasksGraphQLMutation {
     }
 
     async fn update_task(&self, ctx: &Context<'_>, id: Uuid, input: UpdateTaskInput) -> Result<Task> {
-        let (core, member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+        let member_id = principal.member_id;
+
+        let task = core.engine.get_task(id).await?;
+        core.authorize_project_role(&principal, task.project_id, ProjectRole::Member)
+            .await?;
+
+        if input.project_id.is_some() {
+            core.authorize_project_role(&principal, input.project_id, ProjectRole::Member)
+                .await?;
+        }
 
         let saved_input = input.clone();
 
@@ -149,7 +215,11 @@ impl TasksGraphQLMutation {
     }
 
     async fn delete_task(&self, ctx: &Context<'_>, id: Uuid) -> Result<Task> {
-        let (core, _member_id) = extract_context(ctx)?;
+        let (core, principal) = extract_principal(ctx)?;
+
+        let task = core.engine.get_task(id).await?;
+        core.authorize_project_role(&principal, task.project_id, ProjectRole::Member)
+            .await?;
 
         let task = core.engine.delete_task(id).await?;
         let saved_task = task.clone();
@@ -179,13 +249,25 @@ pub struct TasksGraphQLSubscription;
 
 #[Subscription]
 impl TasksGraphQLSubscription {
-    async fn tasks(&self, ctx: &Context<'_>) -> impl Stream<Item = ListenEvent> {
-        let (core, _member_id) = extract_context(ctx).unwrap();
+    async fn tasks(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = ListenEvent>> {
+        listen_events(ctx, ChangeResourceType::Tasks, EventFilter::default()).await
+    }
 
-        core.engine
-            .listen(ChangeResourceType::Tasks)
-            .await
-            .unwrap()
-            .map(|x| x.unwrap())
+    /// Changes to tasks along with the task as it ends up, narrowed down to a project, some
+    /// tasks or some operations.
+    async fn task_subscription(
+        &self,
+        ctx: &Context<'_>,
+        project_id: Option<Uuid>,
+        task_ids: Option<Vec<Uuid>>,
+        operations: Option<Vec<ChangeOperation>>,
+    ) -> Result<impl Stream<Item = ResourceEvent<Task>>> {
+        resource_events(
+            ctx,
+            ChangeResourceType::Tasks,
+            |loaders| &loaders.task_loader,
+            EventFilter::new(task_ids, operations).in_project(project_id),
+        )
+        .await
     }
 }
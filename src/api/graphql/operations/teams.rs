@@ -1,6 +1,10 @@
 use crate::api::graphql::{
     commons::{create_change, extract_context},
+    guards::OrganizationWriteGuard,
+    limits::{connection_complexity, list_complexity},
+    pagination::{keyset_connection, KeysetConnection},
     resources::teams::Team,
+    subscriptions::{listen_events, resource_events, EventFilter, ResourceEvent},
 };
 use async_graphql::{Context, Object, Result, Subscription};
 
@@ -11,7 +15,7 @@ use plexo_sdk::resources::{
 
 use serde_json::json;
 use tokio::task;
-use tokio_stream::{Stream, StreamExt};
+use tokio_stream::Stream;
 use uuid::Uuid;
 
 #[derive(Default)]
@@ -19,6 +23,7 @@ pub struct TeamsGraphQLQuery;
 
 #[Object]
 impl TeamsGraphQLQuery {
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn teams(&self, ctx: &Context<'_>, input: Option<GetTeamsInput>) -> Result<Vec<Team>> {
         let (core, _member_id) = extract_context(ctx)?;
 
@@ -29,6 +34,31 @@ impl TeamsGraphQLQuery {
             .map(|teams| teams.into_iter().map(|team| team.into()).collect())
     }
 
+    /// Pages through `teams`, oldest first.
+    #[graphql(complexity = "connection_complexity(first, last, child_complexity)")]
+    async fn teams_connection(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Team>> {
+        let (core, _member_id) = extract_context(ctx)?;
+
+        keyset_connection(
+            ctx,
+            "teams",
+            |_query| {},
+            &core.loaders.team_loader,
+            after,
+            before,
+            first,
+            last,
+        )
+        .await
+    }
+
     async fn team(&self, ctx: &Context<'_>, id: Uuid) -> Result<Team> {
         let (core, _member_id) = extract_context(ctx)?;
 
@@ -43,7 +73,7 @@ impl TeamsGraphQLQuery {
 #[derive(Default)]
 pub struct TeamsGraphQLMutation;
 
-#[Object]
+#[Object(guard = "OrganizationWriteGuard")]
 impl TeamsGraphQLMutation {
     // TODO: It's possible that this method may not work correctly, as the owner_id is being ignored by async_graphql
     async fn create_team(&self, ctx: &Context<'_>, input: CreateTeamInput) -> Result<Team> {
@@ -163,13 +193,23 @@ pub struct TeamsGraphQLSubscription;
 
 #[Subscription]
 impl TeamsGraphQLSubscription {
-    async fn teams(&self, ctx: &Context<'_>) -> impl Stream<Item = ListenEvent> {
-        let (core, _member_id) = extract_context(ctx).unwrap();
+    async fn teams(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = ListenEvent>> {
+        listen_events(ctx, ChangeResourceType::Teams, EventFilter::default()).await
+    }
 
-        core.engine
-            .listen(ChangeResourceType::Teams)
-            .await
-            .unwrap()
-            .map(|x| x.unwrap())
+    /// Changes to teams along with the team as it ends up.
+    async fn team_subscription(
+        &self,
+        ctx: &Context<'_>,
+        team_ids: Option<Vec<Uuid>>,
+        operations: Option<Vec<ChangeOperation>>,
+    ) -> Result<impl Stream<Item = ResourceEvent<Team>>> {
+        resource_events(
+            ctx,
+            ChangeResourceType::Teams,
+            |loaders| &loaders.team_loader,
+            EventFilter::new(team_ids, operations),
+        )
+        .await
     }
 }
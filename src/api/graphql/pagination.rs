@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+
+use async_graphql::{
+    connection::{query, Connection, Edge, OpaqueCursor},
+    dataloader::{DataLoader, Loader},
+    Context, OutputType, Result, SimpleObject,
+};
+use chrono::{DateTime, Utc};
+use plexo_sdk::resources::changes::change::Change as SDKChange;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::{auth::principal::PlexoPrincipal, core::app::Core, errors::app::PlexoAppError};
+
+use super::resources::changes::Change;
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 200;
+
+/// Rows scanned at once when changes have to be filtered one by one to count them.
+const CHANGES_COUNT_BATCH_SIZE: usize = 1000;
+
+/// Position of a row in a list ordered by creation. The id breaks ties between rows created
+/// at the same instant, so rows inserted while paginating never shift the following pages.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, FromRow)]
+pub struct KeysetPosition {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+pub type KeysetCursor = OpaqueCursor<KeysetPosition>;
+
+pub type KeysetConnection<Node> = Connection<KeysetCursor, Node, ConnectionTotal>;
+
+#[derive(SimpleObject)]
+pub struct ConnectionTotal {
+    /// Every item in the list, not only the ones in this page.
+    pub total_count: i64,
+}
+
+/// Extra `AND` conditions narrowing the rows of a table, pushed after a `WHERE true`.
+pub trait KeysetConditions: Fn(&mut QueryBuilder<'_, Postgres>) + Send + Sync {}
+
+impl<F> KeysetConditions for F where F: Fn(&mut QueryBuilder<'_, Postgres>) + Send + Sync {}
+
+struct KeysetPage {
+    positions: Vec<KeysetPosition>,
+    has_previous_page: bool,
+    has_next_page: bool,
+}
+
+struct KeysetRequest {
+    after: Option<KeysetPosition>,
+    before: Option<KeysetPosition>,
+    limit: usize,
+    backward: bool,
+}
+
+impl KeysetRequest {
+    fn new(
+        after: Option<KeysetCursor>,
+        before: Option<KeysetCursor>,
+        first: Option<usize>,
+        last: Option<usize>,
+    ) -> Result<Self, PlexoAppError> {
+        let limit = last.or(first).unwrap_or(DEFAULT_PAGE_SIZE);
+
+        if limit > MAX_PAGE_SIZE {
+            return Err(PlexoAppError::InvalidRequest(format!(
+                "pages can't have more than {MAX_PAGE_SIZE} items"
+            )));
+        }
+
+        Ok(KeysetRequest {
+            after: after.map(|cursor| cursor.0),
+            before: before.map(|cursor| cursor.0),
+            limit,
+            backward: last.is_some(),
+        })
+    }
+
+    /// Wraps up a page from the rows fetched after and before the cursors, in fetch order.
+    fn page(&self, mut positions: Vec<KeysetPosition>) -> KeysetPage {
+        let has_more = positions.len() > self.limit;
+
+        positions.truncate(self.limit);
+
+        if self.backward {
+            positions.reverse();
+        }
+
+        KeysetPage {
+            positions,
+            has_previous_page: match self.backward {
+                true => has_more,
+                false => self.after.is_some(),
+            },
+            has_next_page: match self.backward {
+                true => self.before.is_some(),
+                false => has_more,
+            },
+        }
+    }
+}
+
+async fn fetch_positions(
+    core: &Core,
+    table: &str,
+    conditions: &impl KeysetConditions,
+    after: Option<KeysetPosition>,
+    before: Option<KeysetPosition>,
+    limit: usize,
+    backward: bool,
+) -> Result<Vec<KeysetPosition>, PlexoAppError> {
+    let mut query = QueryBuilder::<Postgres>::new(format!("SELECT created_at, id FROM {table} WHERE true"));
+
+    conditions(&mut query);
+
+    if let Some(after) = after {
+        query
+            .push(format!(" AND ({table}.created_at, {table}.id) > ("))
+            .push_bind(after.created_at)
+            .push(", ")
+            .push_bind(after.id)
+            .push(")");
+    }
+
+    if let Some(before) = before {
+        query
+            .push(format!(" AND ({table}.created_at, {table}.id) < ("))
+            .push_bind(before.created_at)
+            .push(", ")
+            .push_bind(before.id)
+            .push(")");
+    }
+
+    let direction = match backward {
+        true => "DESC",
+        false => "ASC",
+    };
+
+    query
+        .push(format!(
+            " ORDER BY {table}.created_at {direction}, {table}.id {direction} LIMIT "
+        ))
+        .push_bind(limit as i64);
+
+    let positions = query
+        .build_query_as::<KeysetPosition>()
+        .fetch_all(core.engine.db_pool.as_ref())
+        .await?;
+
+    Ok(positions)
+}
+
+async fn count_rows(core: &Core, table: &str, conditions: &impl KeysetConditions) -> Result<i64, PlexoAppError> {
+    let mut query = QueryBuilder::<Postgres>::new(format!("SELECT COUNT(*) FROM {table} WHERE true"));
+
+    conditions(&mut query);
+
+    let total_count = query
+        .build_query_scalar::<i64>()
+        .fetch_one(core.engine.db_pool.as_ref())
+        .await?;
+
+    Ok(total_count)
+}
+
+async fn load_nodes<L, Node>(loader: &DataLoader<L>, positions: &[KeysetPosition]) -> Result<Vec<(KeysetPosition, Node)>>
+where
+    L: Loader<Uuid>,
+    L::Error: std::fmt::Display,
+    Node: From<L::Value>,
+{
+    let mut values: HashMap<Uuid, L::Value> = loader
+        .load_many(positions.iter().map(|position| position.id))
+        .await
+        .map_err(|err| async_graphql::Error::new(err.to_string()))?;
+
+    // Rows deleted between both queries are left out.
+    Ok(positions
+        .iter()
+        .filter_map(|position| values.remove(&position.id).map(|value| (*position, value.into())))
+        .collect())
+}
+
+fn build_connection<Node: OutputType>(
+    nodes: Vec<(KeysetPosition, Node)>,
+    has_previous_page: bool,
+    has_next_page: bool,
+    total_count: i64,
+) -> KeysetConnection<Node> {
+    let mut connection = Connection::with_additional_fields(has_previous_page, has_next_page, ConnectionTotal { total_count });
+
+    connection.edges.extend(
+        nodes
+            .into_iter()
+            .map(|(position, node)| Edge::new(OpaqueCursor(position), node)),
+    );
+
+    connection
+}
+
+/// Counting every row is skipped unless the query asks for `totalCount`.
+fn wants_total_count(ctx: &Context<'_>) -> bool {
+    ctx.look_ahead().field("totalCount").exists()
+}
+
+/// Pages through the rows of `table` matching `conditions`, oldest first, and loads them with
+/// `loader`. Pages are found by comparing with the cursor's position instead of skipping
+/// rows, so they stay cheap and stable however deep they go.
+#[allow(clippy::too_many_arguments)]
+pub async fn keyset_connection<L, Node>(
+    ctx: &Context<'_>,
+    table: &'static str,
+    conditions: impl KeysetConditions,
+    loader: &DataLoader<L>,
+    after: Option<String>,
+    before: Option<String>,
+    first: Option<i32>,
+    last: Option<i32>,
+) -> Result<KeysetConnection<Node>>
+where
+    L: Loader<Uuid>,
+    L::Error: std::fmt::Display,
+    Node: From<L::Value> + OutputType,
+{
+    let core = ctx.data::<Core>()?;
+    let wants_total_count = wants_total_count(ctx);
+
+    query(after, before, first, last, |after, before, first, last| async move {
+        let request = KeysetRequest::new(after, before, first, last)?;
+
+        let positions = fetch_positions(
+            core,
+            table,
+            &conditions,
+            request.after,
+            request.before,
+            request.limit + 1,
+            request.backward,
+        )
+        .await?;
+
+        let page = request.page(positions);
+
+        let total_count = match wants_total_count {
+            true => count_rows(core, table, &conditions).await?,
+            false => 0,
+        };
+
+        let nodes = load_nodes(loader, &page.positions).await?;
+
+        Ok::<_, async_graphql::Error>(build_connection(
+            nodes,
+            page.has_previous_page,
+            page.has_next_page,
+            total_count,
+        ))
+    })
+    .await
+}
+
+/// Like [`keyset_connection`] for changes, whose visibility depends on the project of the
+/// resource they're about and can't be checked in the query. Rows are fetched in batches
+/// until the page is full of changes the caller can see.
+#[allow(clippy::too_many_arguments)]
+pub async fn changes_connection(
+    ctx: &Context<'_>,
+    principal: &PlexoPrincipal,
+    conditions: impl KeysetConditions,
+    after: Option<String>,
+    before: Option<String>,
+    first: Option<i32>,
+    last: Option<i32>,
+) -> Result<KeysetConnection<Change>> {
+    let core = ctx.data::<Core>()?;
+    let wants_total_count = wants_total_count(ctx);
+
+    query(after, before, first, last, |after, before, first, last| async move {
+        let request = KeysetRequest::new(after, before, first, last)?;
+
+        let mut visible: Vec<(KeysetPosition, SDKChange)> = Vec::new();
+        let (mut after, mut before) = (request.after, request.before);
+
+        loop {
+            let positions = fetch_positions(
+                core,
+                "changes",
+                &conditions,
+                after,
+                before,
+                request.limit + 1,
+                request.backward,
+            )
+            .await?;
+            let exhausted = positions.len() <= request.limit;
+
+            match (request.backward, positions.last()) {
+                (true, Some(last)) => before = Some(*last),
+                (false, Some(last)) => after = Some(*last),
+                (_, None) => {}
+            }
+
+            visible.extend(visible_changes(core, principal, &positions).await?);
+
+            if exhausted || visible.len() > request.limit {
+                break;
+            }
+        }
+
+        let page = request.page(visible.iter().map(|(position, _change)| *position).collect());
+
+        let mut changes: HashMap<Uuid, SDKChange> = visible.into_iter().map(|(position, change)| (position.id, change)).collect();
+
+        let nodes = page
+            .positions
+            .iter()
+            .filter_map(|position| changes.remove(&position.id).map(|change| (*position, change.into())))
+            .collect();
+
+        let total_count = match wants_total_count {
+            true => count_visible_changes(core, principal, &conditions).await?,
+            false => 0,
+        };
+
+        Ok::<_, async_graphql::Error>(build_connection(
+            nodes,
+            page.has_previous_page,
+            page.has_next_page,
+            total_count,
+        ))
+    })
+    .await
+}
+
+async fn visible_changes(
+    core: &Core,
+    principal: &PlexoPrincipal,
+    positions: &[KeysetPosition],
+) -> Result<Vec<(KeysetPosition, SDKChange)>> {
+    let changes: Vec<(KeysetPosition, SDKChange)> = load_nodes(&core.loaders.change_loader, positions).await?;
+
+    let visible_ids: Vec<Uuid> = core
+        .filter_visible_changes(principal, changes.iter().map(|(_position, change)| change.clone()).collect())
+        .await?
+        .into_iter()
+        .map(|change| change.id)
+        .collect();
+
+    Ok(changes
+        .into_iter()
+        .filter(|(position, _change)| visible_ids.contains(&position.id))
+        .collect())
+}
+
+async fn count_visible_changes(core: &Core, principal: &PlexoPrincipal, conditions: &impl KeysetConditions) -> Result<i64> {
+    let visible_projects = core.visible_projects(principal).await?;
+
+    if visible_projects.is_unrestricted() {
+        return Ok(count_rows(core, "changes", conditions).await?);
+    }
+
+    let mut total_count = 0;
+    let mut after = None;
+
+    loop {
+        let positions = fetch_positions(core, "changes", conditions, after, None, CHANGES_COUNT_BATCH_SIZE, false).await?;
+
+        total_count += visible_changes(core, principal, &positions).await?.len() as i64;
+
+        if positions.len() < CHANGES_COUNT_BATCH_SIZE {
+            return Ok(total_count);
+        }
+
+        after = positions.last().copied();
+    }
+}
@@ -0,0 +1,129 @@
+use std::{collections::HashMap, fs, sync::Arc};
+
+use async_graphql::{
+    async_trait::async_trait,
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest},
+    from_value, ErrorExtensions, Request, ServerResult,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::{
+    core::config::{DEVELOPMENT_MODE, GRAPHQL_ALLOWED_QUERIES_ONLY, GRAPHQL_ALLOWED_QUERIES_PATH},
+    errors::app::PlexoAppError,
+};
+
+use super::commons::server_error;
+
+/// The `apollo-persisted-query-manifest` format, of which only the operation bodies are read.
+#[derive(Deserialize)]
+struct PersistedQueryManifest {
+    operations: Vec<PersistedQueryOperation>,
+}
+
+#[derive(Deserialize)]
+struct PersistedQueryOperation {
+    body: String,
+}
+
+/// The `persistedQuery` extension Apollo clients send along with, or instead of, the query.
+#[derive(Deserialize)]
+struct PersistedQuery {
+    #[serde(rename = "sha256Hash")]
+    sha256_hash: String,
+}
+
+fn query_hash(query: &str) -> String {
+    hex::encode(Sha256::digest(query.as_bytes()))
+}
+
+/// Queries registered when the clients were built, by the sha256 hash of their text, which
+/// is the hash automatic persisted queries use.
+#[derive(Default)]
+pub struct AllowedQueries {
+    queries: HashMap<String, String>,
+}
+
+impl AllowedQueries {
+    /// Reads the manifest at `GRAPHQL_ALLOWED_QUERIES_PATH`, if any.
+    pub fn from_env() -> Result<Option<Self>, PlexoAppError> {
+        let Some(path) = GRAPHQL_ALLOWED_QUERIES_PATH.as_ref() else {
+            if *GRAPHQL_ALLOWED_QUERIES_ONLY {
+                return Err(PlexoAppError::InvalidConfiguration(
+                    "GRAPHQL_ALLOWED_QUERIES_ONLY needs a manifest in GRAPHQL_ALLOWED_QUERIES_PATH".to_string(),
+                ));
+            }
+
+            return Ok(None);
+        };
+
+        let manifest = fs::read_to_string(path)
+            .map_err(|err| PlexoAppError::InvalidConfiguration(format!("allowed queries can't be read from {path}: {err}")))?;
+
+        let manifest: PersistedQueryManifest = serde_json::from_str(&manifest)
+            .map_err(|err| PlexoAppError::InvalidConfiguration(format!("allowed queries manifest isn't valid: {err}")))?;
+
+        let allowed_queries = AllowedQueries {
+            queries: manifest
+                .operations
+                .into_iter()
+                .map(|operation| (query_hash(&operation.body), operation.body))
+                .collect(),
+        };
+
+        info!("{} allowed graphql queries loaded", allowed_queries.queries.len());
+
+        Ok(Some(allowed_queries))
+    }
+}
+
+/// Fills in the text of registered queries sent only by hash, so clients don't have to
+/// register them with a second request. With `GRAPHQL_ALLOWED_QUERIES_ONLY`, any other query
+/// is rejected, except in development mode where it's only logged so GraphiQL keeps working.
+///
+/// It has to run before `ApolloPersistedQueries`, which then caches the registered queries.
+pub struct AllowList(Arc<AllowedQueries>);
+
+impl AllowList {
+    pub fn new(allowed_queries: AllowedQueries) -> Self {
+        AllowList(Arc::new(allowed_queries))
+    }
+}
+
+impl ExtensionFactory for AllowList {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(AllowListExtension(self.0.clone()))
+    }
+}
+
+struct AllowListExtension(Arc<AllowedQueries>);
+
+#[async_trait]
+impl Extension for AllowListExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        let hash = match request.query.is_empty() {
+            true => request
+                .extensions
+                .get("persistedQuery")
+                .and_then(|value| from_value::<PersistedQuery>(value.clone()).ok())
+                .map(|persisted_query| persisted_query.sha256_hash.to_lowercase()),
+            false => Some(query_hash(&request.query)),
+        };
+
+        match hash.and_then(|hash| self.0.queries.get(&hash)) {
+            Some(query) if request.query.is_empty() => request.query = query.clone(),
+            Some(_) => {}
+            None if !*GRAPHQL_ALLOWED_QUERIES_ONLY => {}
+            None if *DEVELOPMENT_MODE => warn!("graphql query isn't on the allow-list, only accepted in development mode"),
+            None => return Err(server_error(PlexoAppError::QueryNotAllowed.extend())),
+        }
+
+        next.run(ctx, request).await
+    }
+}
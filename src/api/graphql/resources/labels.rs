@@ -1,7 +1,11 @@
 use async_graphql::{ComplexObject, Context, Result, SimpleObject};
 use plexo_sdk::resources::labels::{label::Label as SDKLabel, relations::LabelRelations};
 
-use crate::api::graphql::commons::extract_context;
+use crate::api::graphql::{
+    commons::{extract_context, extract_principal},
+    limits::{connection_complexity, list_complexity},
+    pagination::{keyset_connection, KeysetConnection},
+};
 
 use super::{members::Member, tasks::Task};
 
@@ -30,13 +34,54 @@ impl Label {
             .map(|member| member.into())
     }
 
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn tasks(&self, ctx: &Context<'_>) -> Result<Vec<Task>> {
-        let (plexo_engine, _label_id) = extract_context(ctx)?;
+        let (plexo_engine, principal) = extract_principal(ctx)?;
+        let visible_projects = plexo_engine.visible_projects(&principal).await?;
 
         self.label
             .tasks(&plexo_engine.loaders)
             .await
             .map_err(|e| e.into())
-            .map(|tasks| tasks.into_iter().map(|task| task.into()).collect())
+            .map(|tasks| {
+                tasks
+                    .into_iter()
+                    .filter(|task| visible_projects.contains(task.project_id))
+                    .map(|task| task.into())
+                    .collect()
+            })
+    }
+
+    /// Pages through `tasks`, oldest first.
+    #[graphql(complexity = "connection_complexity(first, last, child_complexity)")]
+    async fn tasks_connection(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Task>> {
+        let (plexo_engine, principal) = extract_principal(ctx)?;
+        let visible_projects = plexo_engine.visible_projects(&principal).await?;
+        let label_id = self.label.id;
+
+        keyset_connection(
+            ctx,
+            "tasks",
+            move |query| {
+                query
+                    .push(" AND EXISTS (SELECT 1 FROM labels_by_tasks l WHERE l.task_id = tasks.id AND l.label_id = ")
+                    .push_bind(label_id)
+                    .push(")");
+                visible_projects.push_conditions(query, "tasks.project_id");
+            },
+            &plexo_engine.loaders.task_loader,
+            after,
+            before,
+            first,
+            last,
+        )
+        .await
     }
 }
@@ -1,7 +1,15 @@
 use async_graphql::{ComplexObject, Context, Result, SimpleObject};
+use chrono::{DateTime, Utc};
 use plexo_sdk::resources::members::{member::Member as SDKMember, relations::MemberRelations};
 
-use crate::api::graphql::commons::extract_context;
+use crate::{
+    api::graphql::{
+        commons::{extract_context, extract_principal},
+        limits::{connection_complexity, list_complexity},
+        pagination::{keyset_connection, KeysetConnection},
+    },
+    auth::service_accounts::MemberKind,
+};
 
 use super::{projects::Project, tasks::Task, teams::Team};
 
@@ -20,26 +28,101 @@ impl From<SDKMember> for Member {
 
 #[ComplexObject]
 impl Member {
-    async fn projects(&self, ctx: &Context<'_>) -> Result<Vec<Project>> {
+    /// Whether the member is a person or a service account used by automation.
+    async fn kind(&self, ctx: &Context<'_>) -> Result<MemberKind> {
+        let (plexo_engine, _member_id) = extract_context(ctx)?;
+
+        plexo_engine.get_member_kind(self.member.id).await.map_err(|err| err.into())
+    }
+
+    /// When the member was deactivated, if they were. Deactivated members can't log in.
+    async fn deactivated_at(&self, ctx: &Context<'_>) -> Result<Option<DateTime<Utc>>> {
+        let (plexo_engine, _member_id) = extract_context(ctx)?;
+
+        plexo_engine
+            .get_member_deactivated_at(self.member.id)
+            .await
+            .map_err(|err| err.into())
+    }
+
+    /// Whether the member confirmed their email. Only self-registered members and members
+    /// who changed their email can be unverified.
+    async fn email_verified(&self, ctx: &Context<'_>) -> Result<bool> {
         let (plexo_engine, _member_id) = extract_context(ctx)?;
 
+        plexo_engine.is_email_verified(self.member.id).await.map_err(|err| err.into())
+    }
+
+    #[graphql(complexity = "list_complexity(child_complexity)")]
+    async fn projects(&self, ctx: &Context<'_>) -> Result<Vec<Project>> {
+        let (plexo_engine, principal) = extract_principal(ctx)?;
+        let visible_projects = plexo_engine.visible_projects(&principal).await?;
+
         self.member
             .projects(&plexo_engine.loaders)
             .await
             .map_err(|e| e.into())
-            .map(|projects| projects.into_iter().map(|project| project.into()).collect())
+            .map(|projects| {
+                projects
+                    .into_iter()
+                    .filter(|project| visible_projects.contains(Some(project.id)))
+                    .map(|project| project.into())
+                    .collect()
+            })
     }
 
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn tasks(&self, ctx: &Context<'_>) -> Result<Vec<Task>> {
-        let (plexo_engine, _member_id) = extract_context(ctx)?;
+        let (plexo_engine, principal) = extract_principal(ctx)?;
+        let visible_projects = plexo_engine.visible_projects(&principal).await?;
 
         self.member
             .tasks(&plexo_engine.loaders)
             .await
             .map_err(|e| e.into())
-            .map(|tasks| tasks.into_iter().map(|task| task.into()).collect())
+            .map(|tasks| {
+                tasks
+                    .into_iter()
+                    .filter(|task| visible_projects.contains(task.project_id))
+                    .map(|task| task.into())
+                    .collect()
+            })
+    }
+
+    /// Pages through the tasks assigned to the member, oldest first.
+    #[graphql(complexity = "connection_complexity(first, last, child_complexity)")]
+    async fn tasks_connection(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Task>> {
+        let (plexo_engine, principal) = extract_principal(ctx)?;
+        let visible_projects = plexo_engine.visible_projects(&principal).await?;
+        let member_id = self.member.id;
+
+        keyset_connection(
+            ctx,
+            "tasks",
+            move |query| {
+                query
+                    .push(" AND EXISTS (SELECT 1 FROM tasks_by_assignees a WHERE a.task_id = tasks.id AND a.assignee_id = ")
+                    .push_bind(member_id)
+                    .push(")");
+                visible_projects.push_conditions(query, "tasks.project_id");
+            },
+            &plexo_engine.loaders.task_loader,
+            after,
+            before,
+            first,
+            last,
+        )
+        .await
     }
 
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn teams(&self, ctx: &Context<'_>) -> Result<Vec<Team>> {
         let (plexo_engine, _member_id) = extract_context(ctx)?;
 
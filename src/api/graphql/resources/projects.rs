@@ -1,7 +1,11 @@
 use async_graphql::{ComplexObject, Context, Result, SimpleObject};
 use plexo_sdk::resources::projects::{project::Project as SDKProject, relations::ProjectRelations};
 
-use crate::api::graphql::commons::extract_context;
+use crate::api::graphql::{
+    commons::{extract_context, extract_principal},
+    limits::{connection_complexity, list_complexity},
+    pagination::{keyset_connection, KeysetConnection},
+};
 
 use super::{assets::Asset, changes::Change, members::Member, tasks::Task, teams::Team};
 
@@ -40,6 +44,7 @@ impl Project {
             .map(|member| member.map(|member| member.into()))
     }
 
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn tasks(&self, ctx: &Context<'_>) -> Result<Vec<Task>> {
         let (plexo_engine, _member_id) = extract_context(ctx)?;
 
@@ -50,6 +55,37 @@ impl Project {
             .map(|tasks| tasks.into_iter().map(|task| task.into()).collect())
     }
 
+    /// Pages through `tasks`, oldest first.
+    #[graphql(complexity = "connection_complexity(first, last, child_complexity)")]
+    async fn tasks_connection(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Task>> {
+        let (plexo_engine, principal) = extract_principal(ctx)?;
+        let visible_projects = plexo_engine.visible_projects(&principal).await?;
+        let project_id = self.project.id;
+
+        keyset_connection(
+            ctx,
+            "tasks",
+            move |query| {
+                query.push(" AND tasks.project_id = ").push_bind(project_id);
+                visible_projects.push_conditions(query, "tasks.project_id");
+            },
+            &plexo_engine.loaders.task_loader,
+            after,
+            before,
+            first,
+            last,
+        )
+        .await
+    }
+
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn members(&self, ctx: &Context<'_>) -> Result<Vec<Member>> {
         let (plexo_engine, _member_id) = extract_context(ctx)?;
 
@@ -60,6 +96,7 @@ impl Project {
             .map(|members| members.into_iter().map(|member| member.into()).collect())
     }
 
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn assets(&self, ctx: &Context<'_>) -> Result<Vec<Asset>> {
         let (plexo_engine, _member_id) = extract_context(ctx)?;
 
@@ -70,6 +107,37 @@ impl Project {
             .map(|assets| assets.into_iter().map(|asset| asset.into()).collect())
     }
 
+    /// Pages through `assets`, oldest first.
+    #[graphql(complexity = "connection_complexity(first, last, child_complexity)")]
+    async fn assets_connection(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Asset>> {
+        let (plexo_engine, principal) = extract_principal(ctx)?;
+        let visible_projects = plexo_engine.visible_projects(&principal).await?;
+        let project_id = self.project.id;
+
+        keyset_connection(
+            ctx,
+            "assets",
+            move |query| {
+                query.push(" AND assets.project_id = ").push_bind(project_id);
+                visible_projects.push_conditions(query, "assets.project_id");
+            },
+            &plexo_engine.loaders.asset_loader,
+            after,
+            before,
+            first,
+            last,
+        )
+        .await
+    }
+
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn teams(&self, ctx: &Context<'_>) -> Result<Vec<Team>> {
         let (plexo_engine, _member_id) = extract_context(ctx)?;
 
@@ -80,6 +148,7 @@ impl Project {
             .map(|teams| teams.into_iter().map(|team| team.into()).collect())
     }
 
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn changes(&self, ctx: &Context<'_>) -> Result<Vec<Change>> {
         let (plexo_engine, _member_id) = extract_context(ctx)?;
 
@@ -1,7 +1,11 @@
 use async_graphql::{ComplexObject, Context, Result, SimpleObject};
 use plexo_sdk::resources::tasks::{relations::TaskRelations, task::Task as SDKTask};
 
-use crate::api::graphql::commons::extract_context;
+use crate::api::graphql::{
+    commons::{extract_context, extract_principal},
+    limits::{connection_complexity, list_complexity},
+    pagination::{keyset_connection, KeysetConnection},
+};
 
 use super::{changes::Change, labels::Label, members::Member, projects::Project};
 
@@ -60,6 +64,7 @@ impl Task {
             .map(|task| task.map(|t| t.into()))
     }
 
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn assignees(&self, ctx: &Context<'_>) -> Result<Vec<Member>> {
         let (plexo_engine, _member_id) = extract_context(ctx)?;
 
@@ -70,6 +75,7 @@ impl Task {
             .map(|members| members.into_iter().map(|member| member.into()).collect())
     }
 
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn labels(&self, ctx: &Context<'_>) -> Result<Vec<Label>> {
         let (plexo_engine, _member_id) = extract_context(ctx)?;
 
@@ -80,16 +86,55 @@ impl Task {
             .map(|labels| labels.into_iter().map(|label| label.into()).collect())
     }
 
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn subtasks(&self, ctx: &Context<'_>) -> Result<Vec<Task>> {
-        let (plexo_engine, _member_id) = extract_context(ctx)?;
+        let (plexo_engine, principal) = extract_principal(ctx)?;
+        let visible_projects = plexo_engine.visible_projects(&principal).await?;
 
         self.task
             .subtasks(&plexo_engine.loaders)
             .await
             .map_err(|e| e.into())
-            .map(|tasks| tasks.into_iter().map(|task| task.into()).collect())
+            .map(|tasks| {
+                tasks
+                    .into_iter()
+                    .filter(|task| visible_projects.contains(task.project_id))
+                    .map(|task| task.into())
+                    .collect()
+            })
+    }
+
+    /// Pages through `subtasks`, oldest first.
+    #[graphql(complexity = "connection_complexity(first, last, child_complexity)")]
+    async fn subtasks_connection(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Task>> {
+        let (plexo_engine, principal) = extract_principal(ctx)?;
+        let visible_projects = plexo_engine.visible_projects(&principal).await?;
+        let task_id = self.task.id;
+
+        keyset_connection(
+            ctx,
+            "tasks",
+            move |query| {
+                query.push(" AND tasks.parent_id = ").push_bind(task_id);
+                visible_projects.push_conditions(query, "tasks.project_id");
+            },
+            &plexo_engine.loaders.task_loader,
+            after,
+            before,
+            first,
+            last,
+        )
+        .await
     }
 
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn changes(&self, ctx: &Context<'_>) -> Result<Vec<Change>> {
         let (plexo_engine, _member_id) = extract_context(ctx)?;
 
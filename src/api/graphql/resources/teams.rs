@@ -1,7 +1,10 @@
 use async_graphql::{ComplexObject, Context, Result, SimpleObject};
 use plexo_sdk::resources::teams::{relations::TeamRelations, team::Team as SDKTeam};
 
-use crate::api::graphql::commons::extract_context;
+use crate::api::graphql::{
+    commons::{extract_context, extract_principal},
+    limits::list_complexity,
+};
 
 use super::members::Member;
 use super::projects::Project;
@@ -31,16 +34,25 @@ impl Team {
             .map(|member| member.into())
     }
 
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn projects(&self, ctx: &Context<'_>) -> Result<Vec<Project>> {
-        let (plexo_engine, _member_id) = extract_context(ctx)?;
+        let (plexo_engine, principal) = extract_principal(ctx)?;
+        let visible_projects = plexo_engine.visible_projects(&principal).await?;
 
         self.team
             .projects(&plexo_engine.loaders)
             .await
             .map_err(|e| e.into())
-            .map(|projects| projects.into_iter().map(|project| project.into()).collect())
+            .map(|projects| {
+                projects
+                    .into_iter()
+                    .filter(|project| visible_projects.contains(Some(project.id)))
+                    .map(|project| project.into())
+                    .collect()
+            })
     }
 
+    #[graphql(complexity = "list_complexity(child_complexity)")]
     async fn members(&self, ctx: &Context<'_>) -> Result<Vec<Member>> {
         let (plexo_engine, _member_id) = extract_context(ctx)?;
 
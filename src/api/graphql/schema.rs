@@ -1,24 +1,36 @@
 use async_graphql::{
-    extensions::{Analyzer, Tracing}, // extensions::OpenTelemetry,
+    extensions::{
+        apollo_persisted_queries::{ApolloPersistedQueries, LruCacheStorage},
+        Analyzer, Tracing,
+    }, // extensions::OpenTelemetry,
     MergedObject,
     MergedSubscription,
     Schema,
 };
 
-use crate::core::app::Core;
+use crate::{
+    core::{app::Core, config::GRAPHQL_APQ_CACHE_SIZE},
+    errors::app::PlexoAppError,
+};
 
 use super::{
+    limits::QueryLimits,
     operations::{
         assets::{AssetsGraphQLMutation, AssetsGraphQLQuery, AssetsGraphQLSubscription},
         auth::AuthMutation,
-        changes::ChangesGraphQLQuery,
+        changes::{ChangesGraphQLMutation, ChangesGraphQLQuery, ChangesGraphQLSubscription},
+        invitations::{InvitationsGraphQLMutation, InvitationsGraphQLQuery},
         labels::{LabelsGraphQLMutation, LabelsGraphQLQuery, LabelsGraphQLSubscription},
+        lockouts::{LockoutsGraphQLMutation, LockoutsGraphQLQuery},
         members::{MembersGraphQLMutation, MembersGraphQLQuery, MembersGraphQLSubscription},
         profile::{ProfileGraphQLMutation, ProfileGraphQLQuery},
         projects::{ProjectsGraphQLMutation, ProjectsGraphQLQuery, ProjectsGraphQLSubscription},
+        service_accounts::{ServiceAccountsGraphQLMutation, ServiceAccountsGraphQLQuery},
+        settings::{SettingsGraphQLMutation, SettingsGraphQLQuery},
         tasks::{TasksGraphQLMutation, TasksGraphQLQuery, TasksGraphQLSubscription},
         teams::{TeamsGraphQLMutation, TeamsGraphQLQuery, TeamsGraphQLSubscription},
     },
+    persisted_queries::{AllowList, AllowedQueries},
     processors::ai::AIProcessorGraphQLQuery,
 };
 
@@ -33,6 +45,10 @@ pub struct QueryRoot(
     ChangesGraphQLQuery,
     AIProcessorGraphQLQuery,
     ProfileGraphQLQuery,
+    InvitationsGraphQLQuery,
+    LockoutsGraphQLQuery,
+    ServiceAccountsGraphQLQuery,
+    SettingsGraphQLQuery,
 );
 
 #[derive(MergedObject, Default)]
@@ -45,7 +61,11 @@ pub struct MutationRoot(
     TeamsGraphQLMutation,
     MembersGraphQLMutation,
     ProfileGraphQLMutation,
-    // ChangesGraphQLMutation,
+    InvitationsGraphQLMutation,
+    LockoutsGraphQLMutation,
+    ServiceAccountsGraphQLMutation,
+    SettingsGraphQLMutation,
+    ChangesGraphQLMutation,
 );
 
 #[derive(MergedSubscription, Default)]
@@ -56,19 +76,29 @@ pub struct SubscriptionRoot(
     LabelsGraphQLSubscription,
     MembersGraphQLSubscription,
     TeamsGraphQLSubscription,
+    ChangesGraphQLSubscription,
 );
 
 pub trait GraphQLSchema {
-    fn graphql_api_schema(&self) -> Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+    fn graphql_api_schema(&self) -> Result<Schema<QueryRoot, MutationRoot, SubscriptionRoot>, PlexoAppError>;
 }
 
 impl GraphQLSchema for Core {
-    fn graphql_api_schema(&self) -> Schema<QueryRoot, MutationRoot, SubscriptionRoot> {
-        Schema::build(QueryRoot::default(), MutationRoot::default(), SubscriptionRoot::default())
+    fn graphql_api_schema(&self) -> Result<Schema<QueryRoot, MutationRoot, SubscriptionRoot>, PlexoAppError> {
+        let mut schema = Schema::build(QueryRoot::default(), MutationRoot::default(), SubscriptionRoot::default())
             .data(self.clone()) // TODO: Optimize this
             .extension(Tracing)
             .extension(Analyzer)
-            // .extension(open_telemetry)
-            .finish()
+            .extension(QueryLimits);
+        // .extension(open_telemetry)
+
+        // The allow-list resolves registered hashes before the persisted queries cache is checked
+        if let Some(allowed_queries) = AllowedQueries::from_env()? {
+            schema = schema.extension(AllowList::new(allowed_queries));
+        }
+
+        Ok(schema
+            .extension(ApolloPersistedQueries::new(LruCacheStorage::new(*GRAPHQL_APQ_CACHE_SIZE)))
+            .finish())
     }
 }
@@ -0,0 +1,269 @@
+use std::{
+    fmt::Debug,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    futures_util::{future::ready, StreamExt},
+    Context, ErrorExtensions, OutputType, Result, SimpleObject,
+};
+use plexo_sdk::{
+    backend::loaders::SDKLoaders,
+    resources::changes::change::{ChangeOperation, ChangeResourceType, ListenEvent},
+};
+use tokio::sync::Mutex;
+use tokio_stream::Stream;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{core::app::Core, errors::app::PlexoAppError};
+
+use super::{
+    commons::extract_principal,
+    resources::{assets::Asset, changes::Change, labels::Label, members::Member, projects::Project, tasks::Task, teams::Team},
+};
+
+/// A resource that was inserted, updated or deleted.
+#[derive(SimpleObject)]
+#[graphql(
+    concrete(name = "TaskEvent", params(Task)),
+    concrete(name = "ProjectEvent", params(Project)),
+    concrete(name = "AssetEvent", params(Asset)),
+    concrete(name = "MemberEvent", params(Member)),
+    concrete(name = "TeamEvent", params(Team)),
+    concrete(name = "LabelEvent", params(Label))
+)]
+pub struct ResourceEvent<Node: OutputType> {
+    pub operation: ChangeOperation,
+    pub id: Uuid,
+    /// The resource as it is after the change, missing once it's deleted.
+    pub node: Option<Node>,
+}
+
+/// Narrows down the events of a subscription. Everything passes what's left unset.
+#[derive(Default)]
+pub struct EventFilter {
+    pub ids: Option<Vec<Uuid>>,
+    pub operations: Option<Vec<ChangeOperation>>,
+    pub project_id: Option<Uuid>,
+}
+
+impl EventFilter {
+    pub fn new(ids: Option<Vec<Uuid>>, operations: Option<Vec<ChangeOperation>>) -> Self {
+        EventFilter {
+            ids,
+            operations,
+            project_id: None,
+        }
+    }
+
+    /// Only keeps events about rows in the project, including deletions of rows that were in it.
+    pub fn in_project(self, project_id: Option<Uuid>) -> Self {
+        EventFilter { project_id, ..self }
+    }
+
+    fn matches(&self, event: &ListenEvent) -> bool {
+        let id_matches = self.ids.as_ref().is_none_or(|ids| ids.contains(&event.row_id));
+        let operation_matches = self
+            .operations
+            .as_ref()
+            .is_none_or(|operations| operations.contains(&event.operation));
+
+        id_matches && operation_matches
+    }
+}
+
+/// Listens for changes to a kind of resource, keeping the events that pass `filter` and the
+/// caller can see. The stream ends instead of failing on the first database error, so the
+/// client sees the subscription complete and can subscribe again. It also ends once the
+/// caller's session or API key is revoked or their member is deactivated, checked before
+/// each event is sent.
+pub async fn listen_events(
+    ctx: &Context<'_>,
+    resource: ChangeResourceType,
+    filter: EventFilter,
+) -> Result<impl Stream<Item = ListenEvent>> {
+    let (core, principal) = extract_principal(ctx)?;
+
+    let events = core
+        .engine
+        .listen(resource)
+        .await
+        .map_err(|err| PlexoAppError::from(err).extend())?;
+
+    let filter = Arc::new(filter);
+
+    Ok(events
+        .take_while(move |event| {
+            if let Err(err) = event {
+                warn!("{} subscription ended: {:?}", resource, err);
+            }
+
+            ready(event.is_ok())
+        })
+        .then(move |event| {
+            let core = core.clone();
+            let principal = principal.clone();
+            let filter = filter.clone();
+            let event = event.ok().filter(|event| filter.matches(event));
+
+            async move {
+                let Some(event) = event else {
+                    return Ok(None);
+                };
+
+                match core.is_principal_active(&principal).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        info!("{} subscription ended, its credentials are no longer valid", resource);
+                        return Err(());
+                    }
+                    Err(err) => {
+                        warn!("{} subscription ended: {:?}", resource, err);
+                        return Err(());
+                    }
+                }
+
+                // Rows that can't be traced back to a project are dropped
+                let Ok(project_id) = core.get_event_project_id(&event).await else {
+                    return Ok(None);
+                };
+
+                if filter
+                    .project_id
+                    .is_some_and(|filter_project_id| project_id != Some(filter_project_id))
+                {
+                    return Ok(None);
+                }
+
+                Ok(core
+                    .can_view_project(&principal, project_id)
+                    .await
+                    .unwrap_or(false)
+                    .then_some(event))
+            }
+        })
+        .take_while(|event| ready(event.is_ok()))
+        .filter_map(|event| ready(event.ok().flatten())))
+}
+
+/// Like [`listen_events`], with each event carrying the resource as it is after the change,
+/// loaded with `loader`.
+pub async fn resource_events<L, Node>(
+    ctx: &Context<'_>,
+    resource: ChangeResourceType,
+    loader: fn(&SDKLoaders) -> &DataLoader<L>,
+    filter: EventFilter,
+) -> Result<impl Stream<Item = ResourceEvent<Node>>>
+where
+    L: Loader<Uuid>,
+    L::Error: Debug,
+    Node: From<L::Value> + OutputType,
+{
+    let core = ctx.data::<Core>()?.clone();
+
+    let events = listen_events(ctx, resource, filter).await?.then(move |event| {
+        let core = core.clone();
+
+        async move {
+            let node = match event.operation {
+                ChangeOperation::Delete => None,
+                _ => match loader(&core.loaders).load_one(event.row_id).await {
+                    Ok(Some(node)) => Some(node),
+                    // Deleted before it could be loaded, its deletion comes next
+                    Ok(None) => return Ok(None),
+                    Err(err) => {
+                        warn!("{} subscription ended: {:?}", resource, err);
+                        return Err(());
+                    }
+                },
+            };
+
+            Ok(Some(ResourceEvent {
+                operation: event.operation,
+                id: event.row_id,
+                node: node.map(|node| node.into()),
+            }))
+        }
+    });
+
+    Ok(events
+        .take_while(|event| ready(event.is_ok()))
+        .filter_map(|event| ready(event.ok().flatten())))
+}
+
+/// How long a change log subscription reuses the projects the caller can see before resolving
+/// them again.
+const VISIBLE_PROJECTS_TTL: Duration = Duration::from_secs(30);
+
+/// New entries of the change log the caller can see, narrowed down to a kind of resource, the
+/// member who made them or a project. Like [`listen_events`], the stream ends on the first
+/// database error.
+pub async fn change_events(
+    ctx: &Context<'_>,
+    resource_type: Option<ChangeResourceType>,
+    owner_id: Option<Uuid>,
+    project_id: Option<Uuid>,
+) -> Result<impl Stream<Item = Change>> {
+    let (core, principal) = extract_principal(ctx)?;
+
+    let visible_projects = core.visible_projects(&principal).await?;
+    let visible_projects = Arc::new(Mutex::new((Instant::now(), visible_projects)));
+
+    let filter = EventFilter::new(None, Some(vec![ChangeOperation::Insert]));
+
+    let events = listen_events(ctx, ChangeResourceType::Changes, filter)
+        .await?
+        .then(move |event| {
+            let core = core.clone();
+            let principal = principal.clone();
+            let visible_projects = visible_projects.clone();
+
+            async move {
+                let change = match core.loaders.change_loader.load_one(event.row_id).await {
+                    Ok(Some(change)) => change,
+                    Ok(None) => return Ok(None),
+                    Err(err) => {
+                        warn!("changes subscription ended: {:?}", err);
+                        return Err(());
+                    }
+                };
+
+                if resource_type.is_some_and(|resource_type| resource_type != change.resource_type)
+                    || owner_id.is_some_and(|owner_id| owner_id != change.owner_id)
+                {
+                    return Ok(None);
+                }
+
+                let visible = async {
+                    let change_project_id = core.get_change_project_id(&change).await?;
+
+                    if project_id.is_some_and(|project_id| change_project_id != Some(project_id)) {
+                        return Ok(false);
+                    }
+
+                    let mut visible_projects = visible_projects.lock().await;
+
+                    if visible_projects.0.elapsed() > VISIBLE_PROJECTS_TTL {
+                        *visible_projects = (Instant::now(), core.visible_projects(&principal).await?);
+                    }
+
+                    Ok::<_, PlexoAppError>(visible_projects.1.contains_change(&change, change_project_id))
+                };
+
+                match visible.await {
+                    Ok(visible) => Ok(visible.then(|| change.into())),
+                    Err(err) => {
+                        warn!("changes subscription ended: {:?}", err);
+                        Err(())
+                    }
+                }
+            }
+        });
+
+    Ok(events
+        .take_while(|event| ready(event.is_ok()))
+        .filter_map(|event| ready(event.ok().flatten())))
+}
@@ -1,2 +1,3 @@
 pub mod graphql;
 pub mod openapi;
+pub mod scim;
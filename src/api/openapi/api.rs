@@ -17,6 +17,7 @@ use poem_openapi::payload::Json;
 use poem_openapi::{ApiResponse, OpenApi};
 use uuid::Uuid;
 
+use crate::auth::project_roles::ProjectRole;
 use crate::core::app::Core;
 use crate::errors::app::PlexoAppError;
 
@@ -37,8 +38,16 @@ impl PlexoOpenAPI {
     #[oai(path = "/tasks", method = "post", tag = "PlexoAPITags::Task", operation_id = "create_task")]
     /// Creates a new task leveraging Plexo's AI-powered autonomous task generation.
     /// This function streamlines the planning process by intelligently considering project requirements and team capabilities.
-    async fn create_task(&self, input: Json<CreateTaskInput>, _auth: PlexoAPIKeyAuthorization) -> Result<CreateTaskResponse> {
-        let task = self.core.engine.create_task(input.0).await.map_err(PlexoAppError::SDKError)?;
+    async fn create_task(&self, input: Json<CreateTaskInput>, auth: PlexoAPIKeyAuthorization) -> Result<CreateTaskResponse> {
+        auth.authorize_write()?;
+        self.core
+            .authorize_project_role(&auth.0, input.project_id, ProjectRole::Member)
+            .await?;
+
+        let mut input = input.0;
+        input.owner_id = auth.member_id();
+
+        let task = self.core.engine.create_task(input).await.map_err(PlexoAppError::SDKError)?;
 
         Ok(CreateTaskResponse::Ok(Json(task)))
     }
@@ -46,18 +55,29 @@ impl PlexoOpenAPI {
     #[oai(path = "/tasks/:id", method = "get", tag = "PlexoAPITags::Task", operation_id = "get_task")]
     /// Retrieves a specific task, utilizing Plexo's real-time task tracking feature.
     /// This function aids in monitoring the progress of individual tasks within a project.
-    async fn get_task(&self, id: Path<Uuid>, _auth: PlexoAPIKeyAuthorization) -> Result<GetTaskResponse> {
+    async fn get_task(&self, id: Path<Uuid>, auth: PlexoAPIKeyAuthorization) -> Result<GetTaskResponse> {
         let task = self.core.engine.get_task(id.0).await.map_err(PlexoAppError::SDKError)?;
 
+        self.core
+            .authorize_project_role(&auth.0, task.project_id, ProjectRole::Viewer)
+            .await?;
+
         Ok(GetTaskResponse::Ok(Json(task)))
     }
 
     #[oai(path = "/tasks", method = "get", tag = "PlexoAPITags::Task", operation_id = "get_tasks")]
     /// Retrieves a list of tasks using Plexo's real-time task tracking feature filtered by the input provided.
     /// This function helps in monitoring the progress of a specific group of tasks.
-    async fn get_tasks(&self, _auth: PlexoAPIKeyAuthorization) -> Result<GetTasksResponse> {
+    async fn get_tasks(&self, auth: PlexoAPIKeyAuthorization) -> Result<GetTasksResponse> {
+        let visible_projects = self.core.visible_projects(&auth.0).await?;
+
         let tasks = self.core.engine.get_tasks(None).await.map_err(PlexoAppError::SDKError)?;
 
+        let tasks = tasks
+            .into_iter()
+            .filter(|task| visible_projects.contains(task.project_id))
+            .collect();
+
         Ok(GetTasksResponse::Ok(Json(tasks)))
     }
 
@@ -73,8 +93,22 @@ impl PlexoOpenAPI {
         &self,
         id: Path<Uuid>,
         input: Json<UpdateTaskInput>,
-        _auth: PlexoAPIKeyAuthorization,
+        auth: PlexoAPIKeyAuthorization,
     ) -> Result<UpdateTaskResponse> {
+        auth.authorize_write()?;
+
+        let task = self.core.engine.get_task(id.0).await.map_err(PlexoAppError::SDKError)?;
+
+        self.core
+            .authorize_project_role(&auth.0, task.project_id, ProjectRole::Member)
+            .await?;
+
+        if input.project_id.is_some() {
+            self.core
+                .authorize_project_role(&auth.0, input.project_id, ProjectRole::Member)
+                .await?;
+        }
+
         let task = self
             .core
             .engine
@@ -93,7 +127,15 @@ impl PlexoOpenAPI {
     )]
     /// Deletes an existing task in a certain project.
     /// This function helps in removing a task that is no longer needed within a project.
-    async fn delete_task(&self, id: Path<Uuid>, _auth: PlexoAPIKeyAuthorization) -> Result<DeleteTaskResponse> {
+    async fn delete_task(&self, id: Path<Uuid>, auth: PlexoAPIKeyAuthorization) -> Result<DeleteTaskResponse> {
+        auth.authorize_write()?;
+
+        let task = self.core.engine.get_task(id.0).await.map_err(PlexoAppError::SDKError)?;
+
+        self.core
+            .authorize_project_role(&auth.0, task.project_id, ProjectRole::Member)
+            .await?;
+
         let task = self.core.engine.delete_task(id.0).await.map_err(PlexoAppError::SDKError)?;
 
         Ok(DeleteTaskResponse::Ok(Json(task)))
@@ -110,12 +152,18 @@ impl PlexoOpenAPI {
     async fn create_project(
         &self,
         input: Json<CreateProjectInput>,
-        _auth: PlexoAPIKeyAuthorization,
+        auth: PlexoAPIKeyAuthorization,
     ) -> Result<CreateProjectResponse> {
+        auth.authorize_write()?;
+        auth.authorize_project(None)?;
+
+        let mut input = input.0;
+        input.owner_id = auth.member_id();
+
         let project = self
             .core
             .engine
-            .create_project(input.0)
+            .create_project(input)
             .await
             .map_err(PlexoAppError::SDKError)?;
 
@@ -130,7 +178,11 @@ impl PlexoOpenAPI {
     )]
     /// Retrieves detailed information about a specific project, employing Plexo's real-time tracking and AI analytics.
     /// This function facilitates in-depth insight into project progress and dynamics.
-    async fn get_project(&self, id: Path<Uuid>, _auth: PlexoAPIKeyAuthorization) -> Result<GetProjectResponse> {
+    async fn get_project(&self, id: Path<Uuid>, auth: PlexoAPIKeyAuthorization) -> Result<GetProjectResponse> {
+        self.core
+            .authorize_project_role(&auth.0, Some(id.0), ProjectRole::Viewer)
+            .await?;
+
         let project = self.core.engine.get_project(id.0).await.map_err(PlexoAppError::SDKError)?;
 
         Ok(GetProjectResponse::Ok(Json(project)))
@@ -144,16 +196,22 @@ impl PlexoOpenAPI {
     )]
     /// Gathers a list of all projects, leveraging Plexo's comprehensive data management and AI insights.
     /// This function aids in overseeing multiple projects, enhancing strategic decision-making.
-    async fn get_projects(&self, _auth: PlexoAPIKeyAuthorization) -> GetProjectsResponse {
+    async fn get_projects(&self, auth: PlexoAPIKeyAuthorization) -> Result<GetProjectsResponse> {
+        let visible_projects = self.core.visible_projects(&auth.0).await?;
+
         let projects = self
             .core
             .engine
             .get_projects(GetProjectsInput::default())
             .await
-            .map_err(PlexoAppError::SDKError)
-            .unwrap();
+            .map_err(PlexoAppError::SDKError)?;
+
+        let projects = projects
+            .into_iter()
+            .filter(|project| visible_projects.contains(Some(project.id)))
+            .collect();
 
-        GetProjectsResponse::Ok(Json(projects))
+        Ok(GetProjectsResponse::Ok(Json(projects)))
     }
 
     #[oai(
@@ -168,8 +226,13 @@ impl PlexoOpenAPI {
         &self,
         id: Path<Uuid>,
         input: Json<UpdateProjectInput>,
-        _auth: PlexoAPIKeyAuthorization,
+        auth: PlexoAPIKeyAuthorization,
     ) -> Result<UpdateProjectResponse> {
+        auth.authorize_write()?;
+        self.core
+            .authorize_project_role(&auth.0, Some(id.0), ProjectRole::Lead)
+            .await?;
+
         let project = self
             .core
             .engine
@@ -188,7 +251,12 @@ impl PlexoOpenAPI {
     )]
     /// Removes a project from Plexo's system, maintaining the platform's focus on current and active projects.
     /// This function is crucial for project lifecycle management and resource allocation optimization.
-    async fn delete_project(&self, id: Path<Uuid>, _auth: PlexoAPIKeyAuthorization) -> Result<DeleteProjectResponse> {
+    async fn delete_project(&self, id: Path<Uuid>, auth: PlexoAPIKeyAuthorization) -> Result<DeleteProjectResponse> {
+        auth.authorize_write()?;
+        self.core
+            .authorize_project_role(&auth.0, Some(id.0), ProjectRole::Owner)
+            .await?;
+
         let project = self.core.engine.delete_project(id.0).await.map_err(PlexoAppError::SDKError)?;
 
         Ok(DeleteProjectResponse::Ok(Json(project)))
@@ -202,7 +270,11 @@ impl PlexoOpenAPI {
     )]
     /// Registers a new member in the Plexo system, harnessing the AI capabilities for optimal team integration.
     /// This function is essential for expanding the team and managing member roles effectively.
-    async fn create_member(&self, input: Json<CreateMemberInput>, _auth: PlexoAPIKeyAuthorization) -> Result<CreateMemberResponse> {
+    async fn create_member(&self, input: Json<CreateMemberInput>, auth: PlexoAPIKeyAuthorization) -> Result<CreateMemberResponse> {
+        auth.authorize_write()?;
+        auth.authorize_project(None)?;
+        auth.authorize_admin()?;
+
         let member = self
             .core
             .engine
@@ -258,8 +330,12 @@ impl PlexoOpenAPI {
         &self,
         id: Path<Uuid>,
         input: Json<UpdateMemberInput>,
-        _auth: PlexoAPIKeyAuthorization,
+        auth: PlexoAPIKeyAuthorization,
     ) -> Result<UpdateMemberResponse> {
+        auth.authorize_write()?;
+        auth.authorize_project(None)?;
+        auth.authorize_admin()?;
+
         let member = self
             .core
             .engine
@@ -276,10 +352,25 @@ impl PlexoOpenAPI {
         tag = "PlexoAPITags::Member",
         operation_id = "delete_member"
     )]
-    /// Deletes a member's profile from Plexo, maintaining the accuracy of team composition and project alignment.
-    /// This function is key for managing team dynamics and project resources.
-    async fn delete_member(&self, id: Path<Uuid>, _auth: PlexoAPIKeyAuthorization) -> Result<DeleteMemberResponse> {
-        let member = self.core.engine.delete_member(id.0).await.map_err(PlexoAppError::SDKError)?;
+    /// Deactivates a member instead of deleting them, so their tasks, projects and changes stay attributed to them.
+    /// They're signed out everywhere and can't log in again until reactivated.
+    async fn delete_member(&self, id: Path<Uuid>, auth: PlexoAPIKeyAuthorization) -> Result<DeleteMemberResponse> {
+        auth.authorize_write()?;
+        auth.authorize_project(None)?;
+        auth.authorize_admin()?;
+
+        if id.0 == auth.member_id() {
+            return Err(PlexoAppError::InvalidRequest("you can't deactivate yourself".to_string()).into());
+        }
+
+        let member = self
+            .core
+            .engine
+            .get_member(id.0)
+            .await
+            .map_err(|_| PlexoAppError::MemberNotFound)?;
+
+        self.core.deactivate_member(id.0, Some(auth.member_id())).await?;
 
         Ok(DeleteMemberResponse::Ok(Json(member)))
     }
@@ -287,8 +378,14 @@ impl PlexoOpenAPI {
     #[oai(path = "/teams", method = "post", tag = "PlexoAPITags::Team", operation_id = "create_team")]
     /// Creates a new team within Plexo, employing AI-driven insights for optimal team formation and project alignment.
     /// This function is crucial for structuring teams based on project needs and member skills.
-    async fn create_team(&self, input: Json<CreateTeamInput>, _auth: PlexoAPIKeyAuthorization) -> Result<CreateTeamResponse> {
-        let team = self.core.engine.create_team(input.0).await.map_err(PlexoAppError::SDKError)?;
+    async fn create_team(&self, input: Json<CreateTeamInput>, auth: PlexoAPIKeyAuthorization) -> Result<CreateTeamResponse> {
+        auth.authorize_write()?;
+        auth.authorize_project(None)?;
+
+        let mut input = input.0;
+        input.owner_id = auth.member_id();
+
+        let team = self.core.engine.create_team(input).await.map_err(PlexoAppError::SDKError)?;
 
         Ok(CreateTeamResponse::Ok(Json(team)))
     }
@@ -328,8 +425,11 @@ impl PlexoOpenAPI {
         &self,
         id: Path<Uuid>,
         input: Json<UpdateTeamInput>,
-        _auth: PlexoAPIKeyAuthorization,
+        auth: PlexoAPIKeyAuthorization,
     ) -> Result<UpdateTeamResponse> {
+        auth.authorize_write()?;
+        auth.authorize_project(None)?;
+
         let team = self
             .core
             .engine
@@ -348,7 +448,10 @@ impl PlexoOpenAPI {
     )]
     /// Removes a team from the Plexo system, ensuring that the platform's focus remains on active and relevant teams.
     /// This function is critical for effective project management and resource allocation.
-    async fn delete_team(&self, id: Path<Uuid>, _auth: PlexoAPIKeyAuthorization) -> Result<DeleteTeamResponse> {
+    async fn delete_team(&self, id: Path<Uuid>, auth: PlexoAPIKeyAuthorization) -> Result<DeleteTeamResponse> {
+        auth.authorize_write()?;
+        auth.authorize_project(None)?;
+
         let team = self.core.engine.delete_team(id.0).await.map_err(PlexoAppError::SDKError)?;
 
         Ok(DeleteTeamResponse::Ok(Json(team)))
@@ -362,13 +465,14 @@ impl PlexoOpenAPI {
     )]
     /// Introduces a new label to Plexo, enhancing project categorization and task prioritization.
     /// This function is essential for maintaining organized and efficient project workflows.
-    async fn create_label(&self, input: Json<CreateLabelInput>, _auth: PlexoAPIKeyAuthorization) -> Result<CreateLabelResponse> {
-        let label = self
-            .core
-            .engine
-            .create_label(input.0)
-            .await
-            .map_err(PlexoAppError::SDKError)?;
+    async fn create_label(&self, input: Json<CreateLabelInput>, auth: PlexoAPIKeyAuthorization) -> Result<CreateLabelResponse> {
+        auth.authorize_write()?;
+        auth.authorize_project(None)?;
+
+        let mut input = input.0;
+        input.owner_id = auth.member_id();
+
+        let label = self.core.engine.create_label(input).await.map_err(PlexoAppError::SDKError)?;
 
         Ok(CreateLabelResponse::Ok(Json(label)))
     }
@@ -413,8 +517,11 @@ impl PlexoOpenAPI {
         &self,
         id: Path<Uuid>,
         input: Json<UpdateLabelInput>,
-        _auth: PlexoAPIKeyAuthorization,
+        auth: PlexoAPIKeyAuthorization,
     ) -> Result<UpdateLabelResponse> {
+        auth.authorize_write()?;
+        auth.authorize_project(None)?;
+
         let label = self
             .core
             .engine
@@ -433,7 +540,10 @@ impl PlexoOpenAPI {
     )]
     /// Deletes a label from Plexo, streamlining the categorization system to focus on current and active labels.
     /// This function is important for maintaining clarity and efficiency in project organization.
-    async fn delete_label(&self, id: Path<Uuid>, _auth: PlexoAPIKeyAuthorization) -> Result<DeleteLabelResponse> {
+    async fn delete_label(&self, id: Path<Uuid>, auth: PlexoAPIKeyAuthorization) -> Result<DeleteLabelResponse> {
+        auth.authorize_write()?;
+        auth.authorize_project(None)?;
+
         let label = self.core.engine.delete_label(id.0).await.map_err(PlexoAppError::SDKError)?;
 
         Ok(DeleteLabelResponse::Ok(Json(label)))
@@ -447,13 +557,16 @@ impl PlexoOpenAPI {
     )]
     /// Introduces a new asset to Plexo, enhancing project categorization and task prioritization.
     /// This function is essential for maintaining organized and efficient project workflows.
-    async fn create_asset(&self, input: Json<CreateAssetInput>, _auth: PlexoAPIKeyAuthorization) -> Result<CreateAssetResponse> {
-        let asset = self
-            .core
-            .engine
-            .create_asset(input.0)
-            .await
-            .map_err(PlexoAppError::SDKError)?;
+    async fn create_asset(&self, input: Json<CreateAssetInput>, auth: PlexoAPIKeyAuthorization) -> Result<CreateAssetResponse> {
+        auth.authorize_write()?;
+        self.core
+            .authorize_project_role(&auth.0, input.project_id, ProjectRole::Member)
+            .await?;
+
+        let mut input = input.0;
+        input.owner_id = auth.member_id();
+
+        let asset = self.core.engine.create_asset(input).await.map_err(PlexoAppError::SDKError)?;
 
         Ok(CreateAssetResponse::Ok(Json(asset)))
     }
@@ -466,16 +579,22 @@ impl PlexoOpenAPI {
     )]
     /// Retrieves specific details about an asset, utilizing Plexo's structured approach to task and project organization.
     /// This function aids in understanding the role and impact of assets within project management.
-    async fn get_asset(&self, id: Path<Uuid>, _auth: PlexoAPIKeyAuthorization) -> Result<GetAssetResponse> {
+    async fn get_asset(&self, id: Path<Uuid>, auth: PlexoAPIKeyAuthorization) -> Result<GetAssetResponse> {
         let asset = self.core.engine.get_asset(id.0).await.map_err(PlexoAppError::SDKError)?;
 
+        self.core
+            .authorize_project_role(&auth.0, asset.project_id, ProjectRole::Viewer)
+            .await?;
+
         Ok(GetAssetResponse::Ok(Json(asset)))
     }
 
     #[oai(path = "/assets", method = "get", tag = "PlexoAPITags::Asset", operation_id = "get_assets")]
     /// Compiles a list of all assets in Plexo, showcasing the platform's comprehensive categorization capabilities.
     /// This function is vital for overseeing task organization and project prioritization.
-    async fn get_assets(&self, _auth: PlexoAPIKeyAuthorization) -> Result<GetAssetsResponse> {
+    async fn get_assets(&self, auth: PlexoAPIKeyAuthorization) -> Result<GetAssetsResponse> {
+        let visible_projects = self.core.visible_projects(&auth.0).await?;
+
         let assets = self
             .core
             .engine
@@ -483,6 +602,11 @@ impl PlexoOpenAPI {
             .await
             .map_err(PlexoAppError::SDKError)?;
 
+        let assets = assets
+            .into_iter()
+            .filter(|asset| visible_projects.contains(asset.project_id))
+            .collect();
+
         Ok(GetAssetsResponse::Ok(Json(assets)))
     }
 
@@ -498,8 +622,22 @@ impl PlexoOpenAPI {
         &self,
         id: Path<Uuid>,
         input: Json<UpdateAssetInput>,
-        _auth: PlexoAPIKeyAuthorization,
+        auth: PlexoAPIKeyAuthorization,
     ) -> Result<UpdateAssetResponse> {
+        auth.authorize_write()?;
+
+        let asset = self.core.engine.get_asset(id.0).await.map_err(PlexoAppError::SDKError)?;
+
+        self.core
+            .authorize_project_role(&auth.0, asset.project_id, ProjectRole::Member)
+            .await?;
+
+        if input.project_id.is_some() {
+            self.core
+                .authorize_project_role(&auth.0, input.project_id, ProjectRole::Member)
+                .await?;
+        }
+
         let asset = self
             .core
             .engine
@@ -518,7 +656,15 @@ impl PlexoOpenAPI {
     )]
     /// Deletes an asset from Plexo, streamlining the categorization system to focus on current and active assets.
     /// This function is important for maintaining clarity and efficiency in project organization.
-    async fn delete_asset(&self, id: Path<Uuid>, _auth: PlexoAPIKeyAuthorization) -> Result<DeleteAssetResponse> {
+    async fn delete_asset(&self, id: Path<Uuid>, auth: PlexoAPIKeyAuthorization) -> Result<DeleteAssetResponse> {
+        auth.authorize_write()?;
+
+        let asset = self.core.engine.get_asset(id.0).await.map_err(PlexoAppError::SDKError)?;
+
+        self.core
+            .authorize_project_role(&auth.0, asset.project_id, ProjectRole::Member)
+            .await?;
+
         let asset = self.core.engine.delete_asset(id.0).await.map_err(PlexoAppError::SDKError)?;
 
         Ok(DeleteAssetResponse::Ok(Json(asset)))
@@ -1,69 +1,44 @@
-// use crate::auth::resources::PlexoAuthToken;
-// use crate::{auth::jwt::PlexoAuthTokenClaims, core::app::Core};
-// use poem::Request;
+use poem::{error::ResponseError, http::StatusCode, Request};
 use poem_openapi::auth::ApiKey;
 use poem_openapi::SecurityScheme;
-// pub struct PlexoAPIKeyAuthorization(pub PlexoAuthTokenClaims);
+use uuid::Uuid;
 
-// #[poem_openapi::__private::poem::async_trait]
-// impl<'a> poem_openapi::ApiExtractor<'a> for PlexoAPIKeyAuthorization {
-//     const TYPES: &'static [poem_openapi::ApiExtractorType] = &[poem_openapi::ApiExtractorType::SecurityScheme];
-
-//     const PARAM_IS_REQUIRED: bool = true;
-
-//     type ParamType = ();
-//     type ParamRawType = ();
-
-//     fn register(registry: &mut poem_openapi::registry::Registry) {
-//         registry.create_security_scheme(
-//             "PlexoAPIKeyAuthorization",
-//             poem_openapi::registry::MetaSecurityScheme {
-//                 ty: "apiKey",
-//                 description: ::std::option::Option::Some("Plexo API Key Authorization"),
-//                 name: ::std::option::Option::Some("Authorization"),
-//                 key_in: ::std::option::Option::Some("header"),
-//                 scheme: ::std::option::Option::None,
-//                 bearer_format: ::std::option::Option::None,
-//                 flows: ::std::option::Option::None,
-//                 openid_connect_url: ::std::option::Option::None,
-//             },
-//         );
-//     }
-
-//     fn security_schemes() -> ::std::vec::Vec<&'static str> {
-//         ::std::vec!["MyApiKeyAuthorization"]
-//     }
-
-//     async fn from_request(
-//         req: &'a poem_openapi::__private::poem::Request,
-//         _body: &mut poem_openapi::__private::poem::RequestBody,
-//         _param_opts: poem_openapi::ExtractParamOptions<Self::ParamType>,
-//     ) -> poem_openapi::__private::poem::Result<Self> {
-//         let query = req.extensions().get::<poem_openapi::__private::UrlQuery>().unwrap();
-//         let output = poem_openapi::__private::CheckerReturn::from(
-//             api_checker(
-//                 req,
-//                 <poem_openapi::auth::ApiKey as poem_openapi::auth::ApiKeyAuthorization>::from_request(
-//                     req,
-//                     query,
-//                     "Authorization",
-//                     poem_openapi::registry::MetaParamIn::Header,
-//                 )?,
-//             )
-//             .await,
-//         )
-//         .into_result()?;
-//         ::std::result::Result::Ok(Self(output))
-//     }
-// }
-
-// async fn api_checker(req: &Request, api_key: ApiKey) -> Option<PlexoAuthTokenClaims> {
-//     let core = req.data::<Core>().unwrap();
-//     let auth_token = &PlexoAuthToken(api_key.key);
-
-//     core.auth.extract_claims(auth_token).ok()
-// }
+use crate::auth::{commons::parse_authorization_header, principal::PlexoPrincipal};
+use crate::core::app::Core;
+use crate::errors::app::PlexoAppError;
 
+/// Accepts either a session token or a member API key in the `Authorization` header.
 #[derive(SecurityScheme)]
-#[oai(ty = "api_key", key_name = "Authorization", key_in = "header")]
-pub struct PlexoAPIKeyAuthorization(ApiKey);
+#[oai(ty = "api_key", key_name = "Authorization", key_in = "header", checker = "api_checker")]
+pub struct PlexoAPIKeyAuthorization(pub PlexoPrincipal);
+
+impl PlexoAPIKeyAuthorization {
+    pub fn member_id(&self) -> Uuid {
+        self.0.member_id
+    }
+
+    pub fn authorize_write(&self) -> Result<(), PlexoAppError> {
+        self.0.authorize_write()
+    }
+
+    pub fn authorize_project(&self, project_id: Option<Uuid>) -> Result<(), PlexoAppError> {
+        self.0.authorize_project(project_id)
+    }
+
+    pub fn authorize_admin(&self) -> Result<(), PlexoAppError> {
+        self.0.authorize_admin()
+    }
+}
+
+async fn api_checker(req: &Request, api_key: ApiKey) -> poem::Result<PlexoPrincipal> {
+    let Some(core) = req.data::<Core>() else {
+        return Err(PlexoAppError::MissingAuthorizationToken.into());
+    };
+
+    let auth_token = &parse_authorization_header(api_key.key.as_str());
+
+    core.authenticate(auth_token).await.map_err(|err| match err {
+        err if err.status() == StatusCode::UNAUTHORIZED => err.into(),
+        _ => PlexoAppError::InvalidAuthorizationToken.into(),
+    })
+}
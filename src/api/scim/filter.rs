@@ -0,0 +1,205 @@
+use crate::errors::app::PlexoAppError;
+
+/// One `attribute eq value` comparison of a SCIM filter. Attribute names are lowercased and
+/// stripped of their schema URN, since SCIM compares them case-insensitively.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScimFilterClause {
+    pub attribute: String,
+    pub value: ScimFilterValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScimFilterValue {
+    String(String),
+    Boolean(bool),
+}
+
+impl ScimFilterValue {
+    pub fn as_str(&self) -> Result<&str, PlexoAppError> {
+        match self {
+            ScimFilterValue::String(value) => Ok(value),
+            ScimFilterValue::Boolean(_) => Err(PlexoAppError::InvalidFilter("expected a string value".to_string())),
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, PlexoAppError> {
+        match self {
+            ScimFilterValue::Boolean(value) => Ok(*value),
+            ScimFilterValue::String(_) => Err(PlexoAppError::InvalidFilter("expected a boolean value".to_string())),
+        }
+    }
+}
+
+/// Parses the filters identity providers send to match existing resources, equality
+/// comparisons joined with `and`, like `userName eq "ana@plexo.app" and active eq true`.
+/// Anything else is rejected rather than silently ignored.
+pub fn parse_scim_filter(filter: &str) -> Result<Vec<ScimFilterClause>, PlexoAppError> {
+    let tokens = tokenize(filter)?;
+
+    let mut clauses = Vec::new();
+    let mut tokens = tokens.into_iter();
+
+    loop {
+        let (Some(Token::Word(attribute)), Some(Token::Word(operator)), Some(value)) =
+            (tokens.next(), tokens.next(), tokens.next())
+        else {
+            return Err(PlexoAppError::InvalidFilter(filter.to_string()));
+        };
+
+        if !operator.eq_ignore_ascii_case("eq") {
+            return Err(PlexoAppError::InvalidFilter(format!("unsupported operator {operator}")));
+        }
+
+        let value = match value {
+            Token::String(value) => ScimFilterValue::String(value),
+            Token::Word(word) if word.eq_ignore_ascii_case("true") => ScimFilterValue::Boolean(true),
+            Token::Word(word) if word.eq_ignore_ascii_case("false") => ScimFilterValue::Boolean(false),
+            Token::Word(word) => return Err(PlexoAppError::InvalidFilter(format!("unsupported value {word}"))),
+        };
+
+        clauses.push(ScimFilterClause {
+            attribute: normalize_attribute(&attribute),
+            value,
+        });
+
+        match tokens.next() {
+            None => return Ok(clauses),
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("and") => continue,
+            Some(_) => return Err(PlexoAppError::InvalidFilter(filter.to_string())),
+        }
+    }
+}
+
+/// `urn:ietf:params:scim:schemas:core:2.0:User:userName` is the same attribute as `userName`.
+pub fn normalize_attribute(attribute: &str) -> String {
+    let attribute = match attribute.starts_with("urn:") {
+        true => attribute.rsplit(':').next().unwrap_or(attribute),
+        false => attribute,
+    };
+
+    attribute.to_lowercase()
+}
+
+enum Token {
+    Word(String),
+    String(String),
+}
+
+fn tokenize(filter: &str) -> Result<Vec<Token>, PlexoAppError> {
+    let mut tokens = Vec::new();
+    let mut chars = filter.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '"' => {
+                chars.next();
+
+                let mut value = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => value.push(escaped),
+                            None => return Err(PlexoAppError::InvalidFilter(filter.to_string())),
+                        },
+                        Some(c) => value.push(c),
+                        None => return Err(PlexoAppError::InvalidFilter(filter.to_string())),
+                    }
+                }
+
+                tokens.push(Token::String(value));
+            }
+            c if c.is_alphanumeric() || matches!(c, '.' | ':' | '_' | '-' | '$') => {
+                let mut word = String::new();
+
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || matches!(c, '.' | ':' | '_' | '-' | '$')) {
+                        break;
+                    }
+
+                    word.push(c);
+                    chars.next();
+                }
+
+                tokens.push(Token::Word(word));
+            }
+            c => return Err(PlexoAppError::InvalidFilter(format!("unsupported character {c}"))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clause(attribute: &str, value: ScimFilterValue) -> ScimFilterClause {
+        ScimFilterClause {
+            attribute: attribute.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn parses_and_chains() {
+        let clauses = parse_scim_filter(r#"userName eq "ana@plexo.app" AND active eq true and externalId eq "42""#).unwrap();
+
+        assert_eq!(
+            clauses,
+            vec![
+                clause("username", ScimFilterValue::String("ana@plexo.app".to_string())),
+                clause("active", ScimFilterValue::Boolean(true)),
+                clause("externalid", ScimFilterValue::String("42".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn strips_schema_urns_from_attributes() {
+        let clauses = parse_scim_filter(r#"urn:ietf:params:scim:schemas:core:2.0:User:userName eq "ana@plexo.app""#).unwrap();
+
+        assert_eq!(clauses[0].attribute, "username");
+    }
+
+    #[test]
+    fn unescapes_quoted_strings() {
+        let clauses = parse_scim_filter(r#"displayName eq "Ana \"the admin\" \\ Ops""#).unwrap();
+
+        assert_eq!(
+            clauses[0].value,
+            ScimFilterValue::String(r#"Ana "the admin" \ Ops"#.to_string())
+        );
+    }
+
+    #[test]
+    fn parses_booleans_in_any_case() {
+        let clauses = parse_scim_filter("active eq FALSE").unwrap();
+
+        assert!(!clauses[0].value.as_bool().unwrap());
+        assert!(clauses[0].value.as_str().is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_filters() {
+        for filter in [
+            r#"userName co "ana""#,
+            r#"userName eq "ana" or active eq true"#,
+            r#"userName eq "ana"#,
+            r#"userName eq "ana\"#,
+            "userName eq ana",
+            "userName eq",
+            r#"(userName eq "ana")"#,
+            "",
+        ] {
+            assert!(
+                matches!(parse_scim_filter(filter), Err(PlexoAppError::InvalidFilter(_))),
+                "{filter:?} was accepted"
+            );
+        }
+    }
+}
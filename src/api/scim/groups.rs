@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use plexo_sdk::{
+    common::commons::UpdateListInput,
+    organization::operations::OrganizationCrudOperations,
+    resources::teams::{
+        operations::{CreateTeamInputBuilder, TeamCrudOperations, UpdateTeamInputBuilder},
+        team::TeamVisibility,
+    },
+};
+use serde_json::Value;
+use sqlx::{FromRow, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::{core::app::Core, core::config::DOMAIN, errors::app::PlexoAppError};
+
+use super::{
+    filter::{normalize_attribute, parse_scim_filter, ScimFilterClause},
+    resources::{ScimGroup, ScimGroupMember, ScimMeta, ScimPatchOp, ScimPatchOperation, GROUP_SCHEMA},
+    users::{external_id_error, value_as_string},
+};
+
+#[derive(FromRow)]
+struct ScimGroupRow {
+    id: Uuid,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    name: String,
+    external_id: Option<String>,
+}
+
+#[derive(FromRow)]
+struct ScimGroupMemberRow {
+    team_id: Uuid,
+    member_id: Uuid,
+    name: String,
+}
+
+impl ScimGroupRow {
+    fn into_group(self, members: Vec<ScimGroupMember>) -> ScimGroup {
+        ScimGroup {
+            schemas: vec![GROUP_SCHEMA.to_string()],
+            id: Some(self.id.to_string()),
+            external_id: self.external_id,
+            display_name: self.name,
+            members,
+            meta: Some(ScimMeta {
+                resource_type: "Group".to_string(),
+                created: self.created_at,
+                last_modified: self.updated_at,
+                location: format!("{}/scim/v2/Groups/{}", *DOMAIN, self.id),
+            }),
+        }
+    }
+}
+
+const SELECT_SCIM_GROUPS: &str = r#"
+    SELECT teams.id, teams.created_at, teams.updated_at, teams.name, scim_groups.external_id
+    FROM teams
+    LEFT JOIN scim_groups ON scim_groups.team_id = teams.id
+    WHERE true
+"#;
+
+fn push_group_filter(query: &mut QueryBuilder<'_, Postgres>, clause: &ScimFilterClause) -> Result<(), PlexoAppError> {
+    match clause.attribute.as_str() {
+        "id" => {
+            query
+                .push(" AND teams.id = ")
+                .push_bind(Uuid::parse_str(clause.value.as_str()?).ok());
+        }
+        "displayname" => {
+            query.push(" AND teams.name = ").push_bind(clause.value.as_str()?.to_string());
+        }
+        "externalid" => {
+            query
+                .push(" AND scim_groups.external_id = ")
+                .push_bind(clause.value.as_str()?.to_string());
+        }
+        "members" | "members.value" => {
+            query
+                .push(" AND EXISTS (SELECT 1 FROM members_by_teams WHERE team_id = teams.id AND member_id = ")
+                .push_bind(Uuid::parse_str(clause.value.as_str()?).ok())
+                .push(")");
+        }
+        attribute => return Err(PlexoAppError::InvalidFilter(format!("unsupported attribute {attribute}"))),
+    }
+
+    Ok(())
+}
+
+fn parse_member_ids(members: &[ScimGroupMember]) -> Result<Vec<Uuid>, PlexoAppError> {
+    members
+        .iter()
+        .map(|member| {
+            Uuid::parse_str(&member.value).map_err(|_| PlexoAppError::InvalidRequest(format!("{} isn't a member id", member.value)))
+        })
+        .collect()
+}
+
+fn members_from_value(value: Value) -> Result<Vec<Uuid>, PlexoAppError> {
+    let members: Vec<ScimGroupMember> = match value {
+        Value::Array(_) => serde_json::from_value(value),
+        value => serde_json::from_value(value).map(|member| vec![member]),
+    }
+    .map_err(|err| PlexoAppError::InvalidRequest(format!("members: {err}")))?;
+
+    parse_member_ids(&members)
+}
+
+/// What a replace or patch request changes on a group. `None` leaves the attribute as it is.
+#[derive(Default)]
+struct ScimGroupChanges {
+    name: Option<String>,
+    external_id: Option<Option<String>>,
+    member_ids: Option<Vec<Uuid>>,
+}
+
+impl ScimGroupChanges {
+    fn apply(&mut self, op: ScimPatchOp, path: &str, value: Option<Value>, current: &[Uuid]) -> Result<(), PlexoAppError> {
+        let path = normalize_attribute(path);
+
+        match (op, path.as_str(), value) {
+            (ScimPatchOp::Remove, "externalid", _) => self.external_id = Some(None),
+            (ScimPatchOp::Remove, "members", None) => self.member_ids = Some(Vec::new()),
+            (ScimPatchOp::Remove, "members", Some(value)) => {
+                let removed = members_from_value(value)?;
+
+                let member_ids = self.member_ids.get_or_insert_with(|| current.to_vec());
+                member_ids.retain(|member_id| !removed.contains(member_id));
+            }
+            // `members[value eq "<id>"]`, the way most identity providers remove one member.
+            (ScimPatchOp::Remove, path, _) if path.starts_with("members[") && path.ends_with(']') => {
+                let clauses = parse_scim_filter(&path["members[".len()..path.len() - 1])?;
+
+                let member_ids = self.member_ids.get_or_insert_with(|| current.to_vec());
+
+                for clause in clauses.iter().filter(|clause| clause.attribute == "value") {
+                    if let Ok(removed) = Uuid::parse_str(clause.value.as_str()?) {
+                        member_ids.retain(|member_id| *member_id != removed);
+                    }
+                }
+            }
+            (ScimPatchOp::Remove, path, _) => return Err(PlexoAppError::InvalidRequest(format!("can't remove {path}"))),
+            (_, _, None) => return Err(PlexoAppError::InvalidRequest("patch operation without a value".to_string())),
+            (op, "members", Some(value)) => {
+                let members = members_from_value(value)?;
+
+                let member_ids = self.member_ids.get_or_insert_with(|| current.to_vec());
+
+                if op == ScimPatchOp::Replace {
+                    member_ids.clear();
+                }
+
+                for member_id in members {
+                    if !member_ids.contains(&member_id) {
+                        member_ids.push(member_id);
+                    }
+                }
+            }
+            (_, "displayname", Some(value)) => self.name = Some(value_as_string(value)?),
+            (_, "externalid", Some(Value::Null)) => self.external_id = Some(None),
+            (_, "externalid", Some(value)) => self.external_id = Some(Some(value_as_string(value)?)),
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl Core {
+    /// Lists groups matching the filter, along with how many match in total.
+    pub async fn get_scim_groups(
+        &self,
+        filter: Option<&str>,
+        start_index: i64,
+        count: i64,
+    ) -> Result<(Vec<ScimGroup>, i64), PlexoAppError> {
+        let clauses = filter.map(parse_scim_filter).transpose()?.unwrap_or_default();
+
+        let mut total_query = QueryBuilder::new(format!("SELECT COUNT(*) FROM ({SELECT_SCIM_GROUPS}"));
+        let mut query = QueryBuilder::new(SELECT_SCIM_GROUPS);
+
+        for clause in &clauses {
+            push_group_filter(&mut total_query, clause)?;
+            push_group_filter(&mut query, clause)?;
+        }
+
+        total_query.push(") AS matching");
+
+        query
+            .push(" ORDER BY teams.created_at LIMIT ")
+            .push_bind(count)
+            .push(" OFFSET ")
+            .push_bind(start_index - 1);
+
+        let total_results: i64 = total_query
+            .build_query_scalar()
+            .fetch_one(self.engine.db_pool.as_ref())
+            .await?;
+
+        let rows = query
+            .build_query_as::<ScimGroupRow>()
+            .fetch_all(self.engine.db_pool.as_ref())
+            .await?;
+
+        let mut members = self
+            .get_scim_group_members(&rows.iter().map(|row| row.id).collect::<Vec<_>>())
+            .await?;
+
+        let groups = rows
+            .into_iter()
+            .map(|row| {
+                let group_members = members.remove(&row.id).unwrap_or_default();
+                row.into_group(group_members)
+            })
+            .collect();
+
+        Ok((groups, total_results))
+    }
+
+    pub async fn get_scim_group(&self, id: Uuid) -> Result<ScimGroup, PlexoAppError> {
+        let Some(row) = sqlx::query_as::<_, ScimGroupRow>(&format!("{SELECT_SCIM_GROUPS} AND teams.id = $1"))
+            .bind(id)
+            .fetch_optional(self.engine.db_pool.as_ref())
+            .await?
+        else {
+            return Err(PlexoAppError::TeamNotFound);
+        };
+
+        let members = self.get_scim_group_members(&[id]).await?.remove(&id).unwrap_or_default();
+
+        Ok(row.into_group(members))
+    }
+
+    /// Teams need an owner, which SCIM has no notion of, so provisioned teams belong to the
+    /// organization owner.
+    pub async fn create_scim_group(&self, group: ScimGroup) -> Result<ScimGroup, PlexoAppError> {
+        let name = group.display_name.trim().to_string();
+
+        if name.is_empty() {
+            return Err(PlexoAppError::InvalidRequest("displayName is required".to_string()));
+        }
+
+        let member_ids = parse_member_ids(&group.members)?;
+        self.ensure_members_exist(&member_ids).await?;
+
+        let Some(organization) = self.engine.get_organization().await? else {
+            return Err(PlexoAppError::InvalidConfiguration(
+                "organization isn't initialized".to_string(),
+            ));
+        };
+
+        if let Some(external_id) = group.external_id.as_ref() {
+            let in_use: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM scim_groups WHERE external_id = $1)")
+                .bind(external_id)
+                .fetch_one(self.engine.db_pool.as_ref())
+                .await?;
+
+            if in_use {
+                return Err(PlexoAppError::ExternalIdAlreadyInUse);
+            }
+        }
+
+        let team = self
+            .engine
+            .create_team(
+                CreateTeamInputBuilder::default()
+                    .name(name)
+                    .owner_id(organization.owner_id)
+                    .visibility(TeamVisibility::default())
+                    .members(member_ids)
+                    .build()
+                    .unwrap(),
+            )
+            .await?;
+
+        self.apply_scim_group_changes(
+            team.id,
+            ScimGroupChanges {
+                external_id: Some(group.external_id),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    pub async fn replace_scim_group(&self, id: Uuid, group: ScimGroup) -> Result<ScimGroup, PlexoAppError> {
+        self.apply_scim_group_changes(
+            id,
+            ScimGroupChanges {
+                name: Some(group.display_name.trim().to_string()).filter(|name| !name.is_empty()),
+                external_id: Some(group.external_id),
+                member_ids: Some(parse_member_ids(&group.members)?),
+            },
+        )
+        .await
+    }
+
+    /// Joiners and leavers usually arrive as patches adding or removing `members`.
+    pub async fn patch_scim_group(&self, id: Uuid, operations: Vec<ScimPatchOperation>) -> Result<ScimGroup, PlexoAppError> {
+        let current = self.get_scim_group(id).await?;
+        let current_member_ids = parse_member_ids(&current.members)?;
+
+        let mut changes = ScimGroupChanges::default();
+
+        for operation in operations {
+            let op = operation.kind()?;
+
+            match (operation.path, operation.value) {
+                (Some(path), value) => changes.apply(op, &path, value, &current_member_ids)?,
+                (None, Some(Value::Object(attributes))) if op != ScimPatchOp::Remove => {
+                    for (path, value) in attributes {
+                        changes.apply(op, &path, Some(value), &current_member_ids)?;
+                    }
+                }
+                _ => return Err(PlexoAppError::InvalidRequest("patch operation without a path".to_string())),
+            }
+        }
+
+        self.apply_scim_group_changes(id, changes).await
+    }
+
+    pub async fn delete_scim_group(&self, id: Uuid) -> Result<(), PlexoAppError> {
+        self.get_scim_group(id).await?;
+
+        self.engine.delete_team(id).await?;
+
+        Ok(())
+    }
+
+    async fn apply_scim_group_changes(&self, id: Uuid, changes: ScimGroupChanges) -> Result<ScimGroup, PlexoAppError> {
+        let current = self.get_scim_group(id).await?;
+        let current_member_ids = parse_member_ids(&current.members)?;
+
+        let mut input = UpdateTeamInputBuilder::default();
+        let mut changed = false;
+
+        if let Some(name) = changes.name.filter(|name| *name != current.display_name) {
+            input = input.name(name);
+            changed = true;
+        }
+
+        if let Some(member_ids) = changes.member_ids {
+            self.ensure_members_exist(&member_ids).await?;
+
+            let members = UpdateListInput {
+                add: member_ids
+                    .iter()
+                    .filter(|member_id| !current_member_ids.contains(member_id))
+                    .copied()
+                    .collect(),
+                remove: current_member_ids
+                    .iter()
+                    .filter(|member_id| !member_ids.contains(member_id))
+                    .copied()
+                    .collect(),
+            };
+
+            if !members.add.is_empty() || !members.remove.is_empty() {
+                input = input.members(members);
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.engine.update_team(id, input.build().unwrap()).await?;
+        }
+
+        if let Some(external_id) = changes.external_id {
+            sqlx::query(
+                r#"
+                INSERT INTO scim_groups (team_id, external_id)
+                VALUES ($1, $2)
+                ON CONFLICT (team_id) DO UPDATE SET external_id = EXCLUDED.external_id
+                "#,
+            )
+            .bind(id)
+            .bind(external_id)
+            .execute(self.engine.db_pool.as_ref())
+            .await
+            .map_err(external_id_error)?;
+        }
+
+        self.get_scim_group(id).await
+    }
+
+    async fn get_scim_group_members(&self, team_ids: &[Uuid]) -> Result<HashMap<Uuid, Vec<ScimGroupMember>>, PlexoAppError> {
+        let rows = sqlx::query_as::<_, ScimGroupMemberRow>(
+            r#"
+            SELECT members_by_teams.team_id, members.id AS member_id, members.name
+            FROM members_by_teams
+            JOIN members ON members.id = members_by_teams.member_id
+            WHERE members_by_teams.team_id = ANY($1)
+            ORDER BY members.name
+            "#,
+        )
+        .bind(team_ids)
+        .fetch_all(self.engine.db_pool.as_ref())
+        .await?;
+
+        let mut members: HashMap<Uuid, Vec<ScimGroupMember>> = HashMap::new();
+
+        for row in rows {
+            members.entry(row.team_id).or_default().push(ScimGroupMember {
+                value: row.member_id.to_string(),
+                display: Some(row.name),
+            });
+        }
+
+        Ok(members)
+    }
+
+    async fn ensure_members_exist(&self, member_ids: &[Uuid]) -> Result<(), PlexoAppError> {
+        let existing: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM members WHERE id = ANY($1)")
+            .bind(member_ids)
+            .fetch_all(self.engine.db_pool.as_ref())
+            .await?;
+
+        if let Some(missing) = member_ids.iter().find(|member_id| !existing.contains(member_id)) {
+            return Err(PlexoAppError::InvalidRequest(format!("member {missing} not found")));
+        }
+
+        Ok(())
+    }
+}
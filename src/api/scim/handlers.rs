@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use poem::http::{header::AUTHORIZATION, StatusCode};
+use poem::web::{Data, Json, Path, Query};
+use poem::{handler, Endpoint, IntoResponse, Request, Response, Result};
+use ring::constant_time::verify_slices_are_equal;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::auth::commons::parse_authorization_header;
+use crate::core::app::Core;
+use crate::core::config::SCIM_BEARER_TOKEN;
+use crate::errors::app::PlexoAppError;
+
+use super::{
+    resources::{
+        scim_response, ScimError, ScimGroup, ScimListParams, ScimListResponse, ScimPatchRequest, ScimUser,
+        SERVICE_PROVIDER_CONFIG_SCHEMA,
+    },
+    users::{page_bounds, SCIM_MAX_RESULTS},
+};
+
+/// Guards every SCIM route with `SCIM_BEARER_TOKEN`, which is separate from member tokens
+/// and API keys so the identity provider holds no member's credentials.
+pub async fn scim_auth<E: Endpoint>(next: Arc<E>, req: Request) -> Result<Response> {
+    let provided = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(parse_authorization_header);
+
+    let authorized = match (provided, SCIM_BEARER_TOKEN.as_ref()) {
+        (Some(provided), Some(expected)) => {
+            verify_slices_are_equal(&Sha256::digest(provided.0.as_bytes()), &Sha256::digest(expected.as_bytes())).is_ok()
+        }
+        _ => false,
+    };
+
+    if !authorized {
+        return Err(ScimError(PlexoAppError::InvalidAuthorizationToken).into());
+    }
+
+    Ok(next.call(req).await?.into_response())
+}
+
+#[handler]
+pub async fn scim_service_provider_config_handler() -> Response {
+    scim_response(
+        StatusCode::OK,
+        &json!({
+            "schemas": [SERVICE_PROVIDER_CONFIG_SCHEMA],
+            "patch": { "supported": true },
+            "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+            "filter": { "supported": true, "maxResults": SCIM_MAX_RESULTS },
+            "changePassword": { "supported": false },
+            "sort": { "supported": false },
+            "etag": { "supported": false },
+            "authenticationSchemes": [{
+                "type": "oauthbearertoken",
+                "name": "Bearer token",
+                "description": "The token configured in SCIM_BEARER_TOKEN",
+                "primary": true,
+            }],
+        }),
+    )
+}
+
+#[handler]
+pub async fn scim_get_users_handler(plexo_engine: Data<&Core>, params: Query<ScimListParams>) -> Result<Response> {
+    let (start_index, count) = page_bounds(params.start_index, params.count);
+
+    let (users, total_results) = plexo_engine
+        .get_scim_users(params.filter.as_deref(), start_index, count)
+        .await
+        .map_err(ScimError)?;
+
+    Ok(scim_response(
+        StatusCode::OK,
+        &ScimListResponse::new(users, total_results, start_index),
+    ))
+}
+
+#[handler]
+pub async fn scim_create_user_handler(plexo_engine: Data<&Core>, user: Json<ScimUser>) -> Result<Response> {
+    let user = plexo_engine.create_scim_user(user.0).await.map_err(ScimError)?;
+
+    Ok(scim_response(StatusCode::CREATED, &user))
+}
+
+#[handler]
+pub async fn scim_get_user_handler(plexo_engine: Data<&Core>, id: Path<Uuid>) -> Result<Response> {
+    let user = plexo_engine.get_scim_user(id.0).await.map_err(ScimError)?;
+
+    Ok(scim_response(StatusCode::OK, &user))
+}
+
+#[handler]
+pub async fn scim_replace_user_handler(plexo_engine: Data<&Core>, id: Path<Uuid>, user: Json<ScimUser>) -> Result<Response> {
+    let user = plexo_engine.replace_scim_user(id.0, user.0).await.map_err(ScimError)?;
+
+    Ok(scim_response(StatusCode::OK, &user))
+}
+
+#[handler]
+pub async fn scim_patch_user_handler(plexo_engine: Data<&Core>, id: Path<Uuid>, patch: Json<ScimPatchRequest>) -> Result<Response> {
+    let user = plexo_engine
+        .patch_scim_user(id.0, patch.0.operations)
+        .await
+        .map_err(ScimError)?;
+
+    Ok(scim_response(StatusCode::OK, &user))
+}
+
+#[handler]
+pub async fn scim_delete_user_handler(plexo_engine: Data<&Core>, id: Path<Uuid>) -> Result<Response> {
+    plexo_engine.delete_scim_user(id.0).await.map_err(ScimError)?;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[handler]
+pub async fn scim_get_groups_handler(plexo_engine: Data<&Core>, params: Query<ScimListParams>) -> Result<Response> {
+    let (start_index, count) = page_bounds(params.start_index, params.count);
+
+    let (groups, total_results) = plexo_engine
+        .get_scim_groups(params.filter.as_deref(), start_index, count)
+        .await
+        .map_err(ScimError)?;
+
+    Ok(scim_response(
+        StatusCode::OK,
+        &ScimListResponse::new(groups, total_results, start_index),
+    ))
+}
+
+#[handler]
+pub async fn scim_create_group_handler(plexo_engine: Data<&Core>, group: Json<ScimGroup>) -> Result<Response> {
+    let group = plexo_engine.create_scim_group(group.0).await.map_err(ScimError)?;
+
+    Ok(scim_response(StatusCode::CREATED, &group))
+}
+
+#[handler]
+pub async fn scim_get_group_handler(plexo_engine: Data<&Core>, id: Path<Uuid>) -> Result<Response> {
+    let group = plexo_engine.get_scim_group(id.0).await.map_err(ScimError)?;
+
+    Ok(scim_response(StatusCode::OK, &group))
+}
+
+#[handler]
+pub async fn scim_replace_group_handler(plexo_engine: Data<&Core>, id: Path<Uuid>, group: Json<ScimGroup>) -> Result<Response> {
+    let group = plexo_engine.replace_scim_group(id.0, group.0).await.map_err(ScimError)?;
+
+    Ok(scim_response(StatusCode::OK, &group))
+}
+
+#[handler]
+pub async fn scim_patch_group_handler(
+    plexo_engine: Data<&Core>,
+    id: Path<Uuid>,
+    patch: Json<ScimPatchRequest>,
+) -> Result<Response> {
+    let group = plexo_engine
+        .patch_scim_group(id.0, patch.0.operations)
+        .await
+        .map_err(ScimError)?;
+
+    Ok(scim_response(StatusCode::OK, &group))
+}
+
+#[handler]
+pub async fn scim_delete_group_handler(plexo_engine: Data<&Core>, id: Path<Uuid>) -> Result<Response> {
+    plexo_engine.delete_scim_group(id.0).await.map_err(ScimError)?;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
@@ -0,0 +1,5 @@
+pub mod filter;
+pub mod groups;
+pub mod handlers;
+pub mod resources;
+pub mod users;
@@ -0,0 +1,252 @@
+use chrono::{DateTime, Utc};
+use poem::{error::ResponseError, http::StatusCode, Body, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::errors::app::PlexoAppError;
+
+pub const USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const GROUP_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+pub const LIST_RESPONSE_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+pub const PATCH_OP_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+pub const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+pub const SERVICE_PROVIDER_CONFIG_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig";
+
+pub const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimMeta {
+    pub resource_type: String,
+    pub created: DateTime<Utc>,
+    pub last_modified: DateTime<Utc>,
+    pub location: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimName {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub given_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub family_name: Option<String>,
+}
+
+impl ScimName {
+    /// The formatted name, or the given and family names put together.
+    pub fn full_name(&self) -> Option<String> {
+        if let Some(formatted) = self.formatted.as_ref().filter(|formatted| !formatted.trim().is_empty()) {
+            return Some(formatted.trim().to_string());
+        }
+
+        let parts = [&self.given_name, &self.family_name]
+            .into_iter()
+            .flatten()
+            .map(|part| part.trim())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>();
+
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScimMultiValue {
+    pub value: String,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+}
+
+/// A Plexo member as a SCIM user. `userName` and the primary email are both the member's email.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimUser {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    pub user_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<ScimName>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub emails: Vec<ScimMultiValue>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub photos: Vec<ScimMultiValue>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl ScimUser {
+    pub fn email(&self) -> String {
+        primary_value(&self.emails).unwrap_or(&self.user_name).trim().to_string()
+    }
+
+    pub fn full_name(&self) -> Option<String> {
+        self.display_name
+            .as_ref()
+            .map(|display_name| display_name.trim().to_string())
+            .filter(|display_name| !display_name.is_empty())
+            .or_else(|| self.name.as_ref().and_then(|name| name.full_name()))
+    }
+
+    pub fn photo_url(&self) -> Option<String> {
+        primary_value(&self.photos).map(|photo_url| photo_url.to_string())
+    }
+}
+
+fn primary_value(values: &[ScimMultiValue]) -> Option<&String> {
+    values
+        .iter()
+        .find(|value| value.primary == Some(true))
+        .or(values.first())
+        .map(|value| &value.value)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScimGroupMember {
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
+
+/// A Plexo team as a SCIM group.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimGroup {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    pub display_name: String,
+    #[serde(default)]
+    pub members: Vec<ScimGroupMember>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListResponse<T> {
+    pub schemas: Vec<String>,
+    pub total_results: i64,
+    pub start_index: i64,
+    pub items_per_page: i64,
+    #[serde(rename = "Resources")]
+    pub resources: Vec<T>,
+}
+
+impl<T> ScimListResponse<T> {
+    pub fn new(resources: Vec<T>, total_results: i64, start_index: i64) -> Self {
+        Self {
+            schemas: vec![LIST_RESPONSE_SCHEMA.to_string()],
+            total_results,
+            start_index,
+            items_per_page: resources.len() as i64,
+            resources,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListParams {
+    pub filter: Option<String>,
+    pub start_index: Option<i64>,
+    pub count: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScimPatchRequest {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimPatchOperation>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScimPatchOperation {
+    pub op: String,
+    pub path: Option<String>,
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ScimPatchOp {
+    Add,
+    Replace,
+    Remove,
+}
+
+impl ScimPatchOperation {
+    /// Some identity providers capitalize the operation, so it's matched case-insensitively.
+    pub fn kind(&self) -> Result<ScimPatchOp, PlexoAppError> {
+        match self.op.to_lowercase().as_str() {
+            "add" => Ok(ScimPatchOp::Add),
+            "replace" => Ok(ScimPatchOp::Replace),
+            "remove" => Ok(ScimPatchOp::Remove),
+            op => Err(PlexoAppError::InvalidRequest(format!("unknown patch operation {op}"))),
+        }
+    }
+}
+
+/// A `PlexoAppError` in the SCIM error format.
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct ScimError(#[from] pub PlexoAppError);
+
+impl ScimError {
+    fn scim_type(&self) -> Option<&'static str> {
+        match self.0 {
+            PlexoAppError::EmailAlreadyInUse | PlexoAppError::ExternalIdAlreadyInUse => Some("uniqueness"),
+            PlexoAppError::InvalidFilter(_) => Some("invalidFilter"),
+            PlexoAppError::InvalidRequest(_) => Some("invalidValue"),
+            _ => None,
+        }
+    }
+}
+
+impl ResponseError for ScimError {
+    fn status(&self) -> StatusCode {
+        match self.0 {
+            PlexoAppError::EmailAlreadyInUse => StatusCode::CONFLICT,
+            _ => self.0.status(),
+        }
+    }
+
+    fn as_response(&self) -> Response {
+        let mut body = json!({
+            "schemas": [ERROR_SCHEMA],
+            "status": self.status().as_u16().to_string(),
+            "detail": self.0.to_string(),
+        });
+
+        if let Some(scim_type) = self.scim_type() {
+            body["scimType"] = json!(scim_type);
+        }
+
+        scim_response(self.status(), &body)
+    }
+}
+
+pub fn scim_response<T: Serialize>(status: StatusCode, body: &T) -> Response {
+    Response::builder()
+        .status(status)
+        .content_type(SCIM_CONTENT_TYPE)
+        .body(Body::from_json(body).unwrap_or_else(|_| Body::empty()))
+}
@@ -0,0 +1,437 @@
+use chrono::{DateTime, Utc};
+use plexo_sdk::resources::members::{
+    extensions::MembersExtensionOperations,
+    member::MemberRole,
+    operations::{CreateMemberInputBuilder, MemberCrudOperations, UpdateMemberInputBuilder},
+};
+use serde_json::Value;
+use sqlx::{FromRow, Postgres, QueryBuilder};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{core::app::Core, core::config::DOMAIN, errors::app::PlexoAppError};
+
+use super::{
+    filter::{normalize_attribute, parse_scim_filter, ScimFilterClause},
+    resources::{ScimMeta, ScimMultiValue, ScimName, ScimPatchOp, ScimPatchOperation, ScimUser, USER_SCHEMA},
+};
+
+/// The most resources a single list request returns, whatever `count` asks for.
+pub const SCIM_MAX_RESULTS: i64 = 200;
+
+#[derive(FromRow)]
+struct ScimUserRow {
+    id: Uuid,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    name: String,
+    email: String,
+    photo_url: Option<String>,
+    external_id: Option<String>,
+    active: bool,
+}
+
+impl From<ScimUserRow> for ScimUser {
+    fn from(row: ScimUserRow) -> Self {
+        ScimUser {
+            schemas: vec![USER_SCHEMA.to_string()],
+            id: Some(row.id.to_string()),
+            external_id: row.external_id,
+            user_name: row.email.clone(),
+            name: Some(ScimName {
+                formatted: Some(row.name.clone()),
+                ..Default::default()
+            }),
+            display_name: Some(row.name),
+            emails: vec![ScimMultiValue {
+                value: row.email,
+                kind: Some("work".to_string()),
+                primary: Some(true),
+            }],
+            photos: row
+                .photo_url
+                .map(|photo_url| ScimMultiValue {
+                    value: photo_url,
+                    kind: Some("photo".to_string()),
+                    primary: Some(true),
+                })
+                .into_iter()
+                .collect(),
+            active: row.active,
+            meta: Some(ScimMeta {
+                resource_type: "User".to_string(),
+                created: row.created_at,
+                last_modified: row.updated_at,
+                location: format!("{}/scim/v2/Users/{}", *DOMAIN, row.id),
+            }),
+        }
+    }
+}
+
+/// Service accounts aren't people, so they're left out of SCIM.
+const SELECT_SCIM_USERS: &str = r#"
+    SELECT
+        members.id, members.created_at, members.updated_at, members.name, members.email, members.photo_url,
+        scim_users.external_id, member_deactivations.member_id IS NULL AS active
+    FROM members
+    LEFT JOIN scim_users ON scim_users.member_id = members.id
+    LEFT JOIN member_deactivations ON member_deactivations.member_id = members.id
+    WHERE NOT EXISTS (SELECT 1 FROM service_accounts WHERE service_accounts.member_id = members.id)
+"#;
+
+fn push_user_filter(query: &mut QueryBuilder<'_, Postgres>, clause: &ScimFilterClause) -> Result<(), PlexoAppError> {
+    match clause.attribute.as_str() {
+        "id" => {
+            query
+                .push(" AND members.id = ")
+                .push_bind(Uuid::parse_str(clause.value.as_str()?).ok());
+        }
+        "username" | "emails" | "emails.value" => {
+            query
+                .push(" AND lower(members.email) = lower(")
+                .push_bind(clause.value.as_str()?.to_string())
+                .push(")");
+        }
+        "externalid" => {
+            query
+                .push(" AND scim_users.external_id = ")
+                .push_bind(clause.value.as_str()?.to_string());
+        }
+        "displayname" | "name.formatted" => {
+            query
+                .push(" AND members.name = ")
+                .push_bind(clause.value.as_str()?.to_string());
+        }
+        "active" => {
+            query
+                .push(" AND (member_deactivations.member_id IS NULL) = ")
+                .push_bind(clause.value.as_bool()?);
+        }
+        attribute => return Err(PlexoAppError::InvalidFilter(format!("unsupported attribute {attribute}"))),
+    }
+
+    Ok(())
+}
+
+/// What a replace or patch request changes on a user. `None` leaves the attribute as it is.
+#[derive(Default)]
+struct ScimUserChanges {
+    name: Option<String>,
+    given_name: Option<String>,
+    family_name: Option<String>,
+    email: Option<String>,
+    photo_url: Option<String>,
+    external_id: Option<Option<String>>,
+    active: Option<bool>,
+}
+
+impl ScimUserChanges {
+    fn apply(&mut self, path: &str, value: Value) -> Result<(), PlexoAppError> {
+        let path = normalize_attribute(path);
+
+        match path.as_str() {
+            "active" => self.active = Some(value_as_bool(&value)?),
+            "username" => self.email = Some(value_as_string(value)?),
+            "displayname" | "name.formatted" => self.name = Some(value_as_string(value)?),
+            "name.givenname" => self.given_name = Some(value_as_string(value)?),
+            "name.familyname" => self.family_name = Some(value_as_string(value)?),
+            "name" => {
+                let name: ScimName =
+                    serde_json::from_value(value).map_err(|err| PlexoAppError::InvalidRequest(format!("name: {err}")))?;
+
+                self.name = name.full_name().or(self.name.take());
+            }
+            "externalid" => {
+                self.external_id = Some(match value {
+                    Value::Null => None,
+                    value => Some(value_as_string(value)?),
+                })
+            }
+            "emails" => {
+                let emails: Vec<ScimMultiValue> =
+                    serde_json::from_value(value).map_err(|err| PlexoAppError::InvalidRequest(format!("emails: {err}")))?;
+
+                let primary = emails.iter().find(|email| email.primary == Some(true)).or(emails.first());
+
+                if let Some(email) = primary {
+                    self.email = Some(email.value.clone());
+                }
+            }
+            path if path.starts_with("emails[") && path.ends_with(".value") => self.email = Some(value_as_string(value)?),
+            "photos" => {
+                let photos: Vec<ScimMultiValue> =
+                    serde_json::from_value(value).map_err(|err| PlexoAppError::InvalidRequest(format!("photos: {err}")))?;
+
+                if let Some(photo) = photos.iter().find(|photo| photo.primary == Some(true)).or(photos.first()) {
+                    self.photo_url = Some(photo.value.clone());
+                }
+            }
+            // Attributes Plexo doesn't keep, like enterprise extension fields, are ignored so
+            // identity providers can keep sending their full profile.
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn full_name(&self) -> Option<String> {
+        self.name.clone().or_else(|| {
+            ScimName {
+                formatted: None,
+                given_name: self.given_name.clone(),
+                family_name: self.family_name.clone(),
+            }
+            .full_name()
+        })
+    }
+}
+
+pub(super) fn value_as_string(value: Value) -> Result<String, PlexoAppError> {
+    match value {
+        Value::String(value) => Ok(value.trim().to_string()),
+        value => Err(PlexoAppError::InvalidRequest(format!("expected a string, found {value}"))),
+    }
+}
+
+/// Some identity providers send booleans as `"True"` or `"False"`.
+fn value_as_bool(value: &Value) -> Result<bool, PlexoAppError> {
+    match value {
+        Value::Bool(value) => Ok(*value),
+        Value::String(value) if value.eq_ignore_ascii_case("true") => Ok(true),
+        Value::String(value) if value.eq_ignore_ascii_case("false") => Ok(false),
+        value => Err(PlexoAppError::InvalidRequest(format!("expected a boolean, found {value}"))),
+    }
+}
+
+pub(super) fn external_id_error(err: sqlx::Error) -> PlexoAppError {
+    match err {
+        sqlx::Error::Database(err) if err.is_unique_violation() => PlexoAppError::ExternalIdAlreadyInUse,
+        err => err.into(),
+    }
+}
+
+/// SCIM's `startIndex` is 1-based.
+pub(super) fn page_bounds(start_index: Option<i64>, count: Option<i64>) -> (i64, i64) {
+    let start_index = start_index.unwrap_or(1).max(1);
+    let count = count.unwrap_or(SCIM_MAX_RESULTS).clamp(0, SCIM_MAX_RESULTS);
+
+    (start_index, count)
+}
+
+impl Core {
+    /// Lists users matching the filter, along with how many match in total.
+    pub async fn get_scim_users(
+        &self,
+        filter: Option<&str>,
+        start_index: i64,
+        count: i64,
+    ) -> Result<(Vec<ScimUser>, i64), PlexoAppError> {
+        let clauses = filter.map(parse_scim_filter).transpose()?.unwrap_or_default();
+
+        let mut total_query = QueryBuilder::new(format!("SELECT COUNT(*) FROM ({SELECT_SCIM_USERS}"));
+        let mut query = QueryBuilder::new(SELECT_SCIM_USERS);
+
+        for clause in &clauses {
+            push_user_filter(&mut total_query, clause)?;
+            push_user_filter(&mut query, clause)?;
+        }
+
+        total_query.push(") AS matching");
+
+        query
+            .push(" ORDER BY members.created_at LIMIT ")
+            .push_bind(count)
+            .push(" OFFSET ")
+            .push_bind(start_index - 1);
+
+        let total_results: i64 = total_query
+            .build_query_scalar()
+            .fetch_one(self.engine.db_pool.as_ref())
+            .await?;
+
+        let users = query
+            .build_query_as::<ScimUserRow>()
+            .fetch_all(self.engine.db_pool.as_ref())
+            .await?
+            .into_iter()
+            .map(ScimUser::from)
+            .collect();
+
+        Ok((users, total_results))
+    }
+
+    pub async fn get_scim_user(&self, id: Uuid) -> Result<ScimUser, PlexoAppError> {
+        sqlx::query_as::<_, ScimUserRow>(&format!("{SELECT_SCIM_USERS} AND members.id = $1"))
+            .bind(id)
+            .fetch_optional(self.engine.db_pool.as_ref())
+            .await?
+            .map(ScimUser::from)
+            .ok_or(PlexoAppError::MemberNotFound)
+    }
+
+    /// Provisions a member without a password, who signs in through the identity provider.
+    pub async fn create_scim_user(&self, user: ScimUser) -> Result<ScimUser, PlexoAppError> {
+        let email = user.email();
+
+        if email.is_empty() {
+            return Err(PlexoAppError::InvalidRequest("userName is required".to_string()));
+        }
+
+        if self.engine.get_member_by_email(email.clone()).await?.is_some() {
+            return Err(PlexoAppError::EmailAlreadyInUse);
+        }
+
+        if let Some(external_id) = user.external_id.as_ref() {
+            self.ensure_scim_external_id_available(external_id).await?;
+        }
+
+        let mut input = CreateMemberInputBuilder::default()
+            .name(user.full_name().unwrap_or(email.clone()))
+            .email(email)
+            .role(MemberRole::Member);
+
+        if let Some(photo_url) = user.photo_url() {
+            input = input.photo_url(photo_url);
+        }
+
+        let member = self.engine.create_member(input.build().unwrap()).await?;
+
+        let user = self
+            .apply_scim_user_changes(
+                member.id,
+                ScimUserChanges {
+                    external_id: Some(user.external_id),
+                    active: Some(user.active),
+                    ..Default::default()
+                },
+            )
+            .await;
+
+        // The SDK can't create the member in a transaction, so it's deleted again when the rest
+        // fails, like when another request took the external id in the meantime
+        if user.is_err() {
+            if let Err(err) = self.engine.delete_member(member.id).await {
+                warn!("scim member {} left behind after a failed create: {:?}", member.id, err);
+            }
+        }
+
+        user
+    }
+
+    pub async fn replace_scim_user(&self, id: Uuid, user: ScimUser) -> Result<ScimUser, PlexoAppError> {
+        self.apply_scim_user_changes(
+            id,
+            ScimUserChanges {
+                name: user.full_name(),
+                email: Some(user.email()).filter(|email| !email.is_empty()),
+                photo_url: user.photo_url(),
+                external_id: Some(user.external_id),
+                active: Some(user.active),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Leavers usually arrive as a patch replacing `active` with `false`.
+    pub async fn patch_scim_user(&self, id: Uuid, operations: Vec<ScimPatchOperation>) -> Result<ScimUser, PlexoAppError> {
+        let mut changes = ScimUserChanges::default();
+
+        for operation in operations {
+            match (operation.kind()?, operation.path, operation.value) {
+                (ScimPatchOp::Remove, Some(path), _) if normalize_attribute(&path) == "externalid" => {
+                    changes.external_id = Some(None);
+                }
+                (ScimPatchOp::Remove, path, _) => {
+                    return Err(PlexoAppError::InvalidRequest(format!(
+                        "can't remove {}",
+                        path.unwrap_or_default()
+                    )));
+                }
+                (_, Some(path), Some(value)) => changes.apply(&path, value)?,
+                (_, None, Some(Value::Object(attributes))) => {
+                    for (path, value) in attributes {
+                        changes.apply(&path, value)?;
+                    }
+                }
+                _ => return Err(PlexoAppError::InvalidRequest("patch operation without a value".to_string())),
+            }
+        }
+
+        self.apply_scim_user_changes(id, changes).await
+    }
+
+    /// Deleting a user through SCIM deactivates the member, so their history stays theirs.
+    pub async fn delete_scim_user(&self, id: Uuid) -> Result<(), PlexoAppError> {
+        self.get_scim_user(id).await?;
+
+        self.deactivate_member(id, None).await
+    }
+
+    async fn apply_scim_user_changes(&self, id: Uuid, changes: ScimUserChanges) -> Result<ScimUser, PlexoAppError> {
+        let current = self.get_scim_user(id).await?;
+
+        let name = changes.full_name();
+        let email = changes.email.filter(|email| *email != current.user_name);
+
+        if let Some(email) = email.as_ref() {
+            if self.engine.get_member_by_email(email.clone()).await?.is_some() {
+                return Err(PlexoAppError::EmailAlreadyInUse);
+            }
+        }
+
+        if name.is_some() || email.is_some() || changes.photo_url.is_some() {
+            let mut input = UpdateMemberInputBuilder::default();
+
+            if let Some(name) = name {
+                input = input.name(name);
+            }
+
+            if let Some(email) = email {
+                input = input.email(email);
+            }
+
+            if let Some(photo_url) = changes.photo_url {
+                input = input.photo_url(photo_url);
+            }
+
+            self.engine.update_member(id, input.build().unwrap()).await?;
+        }
+
+        if let Some(external_id) = changes.external_id {
+            sqlx::query(
+                r#"
+                INSERT INTO scim_users (member_id, external_id)
+                VALUES ($1, $2)
+                ON CONFLICT (member_id) DO UPDATE SET external_id = EXCLUDED.external_id
+                "#,
+            )
+            .bind(id)
+            .bind(external_id)
+            .execute(self.engine.db_pool.as_ref())
+            .await
+            .map_err(external_id_error)?;
+        }
+
+        match changes.active {
+            Some(false) if current.active => self.deactivate_member(id, None).await?,
+            Some(true) if !current.active => self.reactivate_member(id).await?,
+            _ => {}
+        }
+
+        self.get_scim_user(id).await
+    }
+
+    async fn ensure_scim_external_id_available(&self, external_id: &str) -> Result<(), PlexoAppError> {
+        let in_use: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM scim_users WHERE external_id = $1)")
+            .bind(external_id)
+            .fetch_one(self.engine.db_pool.as_ref())
+            .await?;
+
+        if in_use {
+            return Err(PlexoAppError::ExternalIdAlreadyInUse);
+        }
+
+        Ok(())
+    }
+}
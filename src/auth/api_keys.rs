@@ -0,0 +1,230 @@
+use std::str::FromStr;
+
+use async_graphql::{Enum, SimpleObject};
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{core::app::Core, errors::app::PlexoAppError};
+
+pub const API_KEY_PREFIX: &str = "plexo_";
+const API_KEY_SECRET_LENGTH: usize = 40;
+const API_KEY_VISIBLE_LENGTH: usize = 8;
+
+#[derive(Debug, Enum, Copy, Clone, Eq, PartialEq)]
+pub enum ApiKeyScope {
+    ReadOnly,
+    ReadWrite,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::ReadOnly => "ReadOnly",
+            ApiKeyScope::ReadWrite => "ReadWrite",
+        }
+    }
+}
+
+impl FromStr for ApiKeyScope {
+    type Err = PlexoAppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ReadOnly" => Ok(ApiKeyScope::ReadOnly),
+            "ReadWrite" => Ok(ApiKeyScope::ReadWrite),
+            _ => Err(PlexoAppError::InvalidAuthorizationToken),
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject, Clone)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+
+    pub member_id: Uuid,
+    pub name: String,
+    /// The first characters of the key, kept so members can tell their keys apart.
+    pub prefix: String,
+    pub scope: ApiKeyScope,
+    pub project_id: Option<Uuid>,
+
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(FromRow)]
+struct ApiKeyRow {
+    id: Uuid,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    member_id: Uuid,
+    name: String,
+    prefix: String,
+    scope: String,
+    project_id: Option<Uuid>,
+    last_used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<ApiKeyRow> for ApiKey {
+    type Error = PlexoAppError;
+
+    fn try_from(row: ApiKeyRow) -> Result<Self, Self::Error> {
+        Ok(ApiKey {
+            id: row.id,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            member_id: row.member_id,
+            name: row.name,
+            prefix: row.prefix,
+            scope: ApiKeyScope::from_str(&row.scope)?,
+            project_id: row.project_id,
+            last_used_at: row.last_used_at,
+            revoked_at: row.revoked_at,
+        })
+    }
+}
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(API_KEY_PREFIX)
+}
+
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+fn generate_api_key() -> String {
+    let secret: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(API_KEY_SECRET_LENGTH)
+        .map(char::from)
+        .collect();
+
+    format!("{}{}", API_KEY_PREFIX, secret)
+}
+
+impl Core {
+    /// Creates a new API key for the member and returns it along with the plain key,
+    /// which is never stored and can't be recovered afterwards.
+    pub async fn create_api_key(
+        &self,
+        member_id: Uuid,
+        name: String,
+        scope: ApiKeyScope,
+        project_id: Option<Uuid>,
+    ) -> Result<(ApiKey, String), PlexoAppError> {
+        let key = generate_api_key();
+        let prefix = key[..API_KEY_PREFIX.len() + API_KEY_VISIBLE_LENGTH].to_string();
+
+        let row = sqlx::query_as::<_, ApiKeyRow>(
+            r#"
+            INSERT INTO api_keys (member_id, name, prefix, key_hash, scope, project_id)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+        )
+        .bind(member_id)
+        .bind(name)
+        .bind(prefix)
+        .bind(hash_api_key(&key))
+        .bind(scope.as_str())
+        .bind(project_id)
+        .fetch_one(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok((row.try_into()?, key))
+    }
+
+    pub async fn get_api_keys(&self, member_id: Uuid) -> Result<Vec<ApiKey>, PlexoAppError> {
+        sqlx::query_as::<_, ApiKeyRow>(
+            r#"
+            SELECT * FROM api_keys
+            WHERE member_id = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(member_id)
+        .fetch_all(self.engine.db_pool.as_ref())
+        .await?
+        .into_iter()
+        .map(|row| row.try_into())
+        .collect()
+    }
+
+    pub async fn update_api_key(
+        &self,
+        member_id: Uuid,
+        id: Uuid,
+        name: Option<String>,
+        scope: Option<ApiKeyScope>,
+    ) -> Result<ApiKey, PlexoAppError> {
+        sqlx::query_as::<_, ApiKeyRow>(
+            r#"
+            UPDATE api_keys
+            SET
+                name = COALESCE($1, name),
+                scope = COALESCE($2, scope)
+            WHERE id = $3 AND member_id = $4 AND revoked_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(name)
+        .bind(scope.map(|scope| scope.as_str()))
+        .bind(id)
+        .bind(member_id)
+        .fetch_one(self.engine.db_pool.as_ref())
+        .await?
+        .try_into()
+    }
+
+    pub async fn revoke_api_key(&self, member_id: Uuid, id: Uuid) -> Result<ApiKey, PlexoAppError> {
+        sqlx::query_as::<_, ApiKeyRow>(
+            r#"
+            UPDATE api_keys
+            SET revoked_at = COALESCE(revoked_at, now())
+            WHERE id = $1 AND member_id = $2
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(member_id)
+        .fetch_one(self.engine.db_pool.as_ref())
+        .await?
+        .try_into()
+    }
+
+    pub async fn is_api_key_active(&self, id: Uuid) -> Result<bool, PlexoAppError> {
+        let active: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM api_keys WHERE id = $1 AND revoked_at IS NULL)")
+            .bind(id)
+            .fetch_one(self.engine.db_pool.as_ref())
+            .await?;
+
+        Ok(active)
+    }
+
+    /// Resolves a plain API key to its stored record, touching its last-used timestamp.
+    pub async fn authenticate_api_key(&self, key: &str) -> Result<ApiKey, PlexoAppError> {
+        let row = sqlx::query_as::<_, ApiKeyRow>(
+            r#"
+            UPDATE api_keys
+            SET last_used_at = now()
+            WHERE key_hash = $1 AND revoked_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(hash_api_key(key))
+        .fetch_optional(self.engine.db_pool.as_ref())
+        .await?;
+
+        let Some(row) = row else {
+            return Err(PlexoAppError::InvalidAuthorizationToken);
+        };
+
+        row.try_into()
+    }
+}
@@ -1,15 +1,56 @@
-use crate::core::config::COOKIE_SESSION_NAME;
+use crate::{
+    core::config::{
+        AUTH_REDIRECT_ALLOWED_ORIGINS, CLIENT_IP_HEADER, CLIENT_IP_PROXY_HOPS, COOKIE_OAUTH_STATE_NAME, COOKIE_REFRESH_NAME,
+        COOKIE_SESSION_NAME, DOMAIN,
+    },
+    errors::app::PlexoAppError,
+};
 
-use super::resources::PlexoAuthToken;
+use super::resources::{ClientIp, PlexoAuthToken};
 use cookie::Cookie;
-use poem::http::HeaderMap;
+use poem::{http::HeaderMap, web::RemoteAddr};
+use rand::{distributions::Alphanumeric, Rng};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
 
-pub const GITHUB_USER_API: &str = "https://api.github.com/user";
+/// Random token for emailed links; only its hash is ever stored.
+pub fn generate_link_token(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+pub fn hash_link_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Rejects addresses that can't be emailed, before anything is saved for them.
+pub fn validate_email(email: &str) -> Result<(), PlexoAppError> {
+    email
+        .parse::<lettre::Address>()
+        .map(|_| ())
+        .map_err(|_| PlexoAppError::InvalidEmail)
+}
 
 pub fn get_token_from_headers(headers: &HeaderMap) -> Option<PlexoAuthToken> {
     headers
         .get("Authorization")
-        .and_then(|value| value.to_str().map(|s| PlexoAuthToken(s.to_string())).ok())
+        .and_then(|value| value.to_str().ok())
+        .map(parse_authorization_header)
+}
+
+/// Accepts the token alone or with the `Bearer` scheme.
+pub fn parse_authorization_header(value: &str) -> PlexoAuthToken {
+    let value = value.trim();
+
+    let token = match value.split_once(' ') {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => token.trim(),
+        _ => value,
+    };
+
+    PlexoAuthToken(token.to_string())
 }
 
 pub fn get_token_from_cookie(headers: &HeaderMap) -> Option<PlexoAuthToken> {
@@ -19,16 +60,138 @@ pub fn get_token_from_cookie(headers: &HeaderMap) -> Option<PlexoAuthToken> {
 }
 
 pub fn get_token_from_raw_cookie(raw_cookie: &str) -> Option<PlexoAuthToken> {
+    get_cookie_value(raw_cookie, COOKIE_SESSION_NAME.as_str()).map(PlexoAuthToken)
+}
+
+pub fn get_refresh_token_from_cookie(headers: &HeaderMap) -> Option<String> {
+    let raw_cookie = headers.get("Cookie").and_then(|c| c.to_str().ok())?;
+
+    get_cookie_value(raw_cookie, COOKIE_REFRESH_NAME.as_str())
+}
+
+pub fn get_oauth_state_from_cookie(headers: &HeaderMap) -> Option<String> {
+    let raw_cookie = headers.get("Cookie").and_then(|c| c.to_str().ok())?;
+
+    get_cookie_value(raw_cookie, COOKIE_OAUTH_STATE_NAME.as_str())
+}
+
+/// Where to send the browser after a sign-in. Paths on this site are always accepted, full
+/// URLs only when their origin is `DOMAIN` or in `AUTH_REDIRECT_ALLOWED_ORIGINS`, so the
+/// sign-in can't be used as an open redirect. Paths are resolved against `DOMAIN` the way a
+/// browser would, so tricks like `/%09/evil.com` are caught by the origin check.
+pub fn validate_redirect_to(redirect_to: &str) -> Result<String, PlexoAppError> {
+    // Browsers drop tabs and newlines from URLs, which can turn a path into another origin
+    if redirect_to.chars().any(|c| c.is_ascii_control() || c.is_whitespace()) {
+        return Err(PlexoAppError::InvalidRedirect);
+    }
+
+    let domain = Url::parse(DOMAIN.as_str()).map_err(|_| PlexoAppError::InvalidRedirect)?;
+    let domain_origin = domain.origin().ascii_serialization();
+
+    let is_path = redirect_to.starts_with('/');
+
+    let url = if is_path {
+        domain.join(redirect_to)
+    } else {
+        Url::parse(redirect_to)
+    }
+    .map_err(|_| PlexoAppError::InvalidRedirect)?;
+
+    let origin = url.origin().ascii_serialization();
+
+    if is_path {
+        if origin != domain_origin || redirect_to.starts_with("//") || redirect_to.contains('\\') {
+            return Err(PlexoAppError::InvalidRedirect);
+        }
+
+        return Ok(redirect_to.to_string());
+    }
+
+    if origin == domain_origin || AUTH_REDIRECT_ALLOWED_ORIGINS.contains(&origin) {
+        return Ok(url.to_string());
+    }
+
+    Err(PlexoAppError::InvalidRedirect)
+}
+
+/// Prefers the header set by a trusted proxy, when configured, over the socket address. Only
+/// the entries appended by the trusted proxies are read, since the client controls the rest.
+pub fn get_client_ip(headers: &HeaderMap, remote_addr: &RemoteAddr) -> ClientIp {
+    let forwarded_ip = CLIENT_IP_HEADER.as_ref().and_then(|header| {
+        headers
+            .get(header.as_str())
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').nth(*CLIENT_IP_PROXY_HOPS - 1))
+            .map(|ip| ip.trim().to_string())
+            .filter(|ip| !ip.is_empty())
+    });
+
+    ClientIp(forwarded_ip.or_else(|| remote_addr.as_socket_addr().map(|addr| addr.ip().to_string())))
+}
+
+fn get_cookie_value(raw_cookie: &str, name: &str) -> Option<String> {
     for cookie in Cookie::split_parse(raw_cookie) {
         let Ok(cookie) = cookie else {
             println!("Error parsing cookie");
             continue;
         };
 
-        if cookie.name() == COOKIE_SESSION_NAME.to_string() {
-            return Some(PlexoAuthToken(cookie.value().to_string()));
+        if cookie.name() == name {
+            return Some(cookie.value().to_string());
         }
     }
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authorization_header_accepts_bearer_scheme_in_any_case() {
+        assert_eq!(parse_authorization_header("Bearer token").0, "token");
+        assert_eq!(parse_authorization_header("bearer token").0, "token");
+        assert_eq!(parse_authorization_header("BEARER  token ").0, "token");
+    }
+
+    #[test]
+    fn authorization_header_accepts_bare_token() {
+        assert_eq!(parse_authorization_header("token").0, "token");
+        assert_eq!(parse_authorization_header(" plexo_key ").0, "plexo_key");
+    }
+
+    #[test]
+    fn email_must_be_an_address() {
+        assert!(validate_email("ada@plexo.app").is_ok());
+
+        for email in ["", "ada", "ada@", "@plexo.app", "ada@plexo.app\r\nBcc: eve@evil.com"] {
+            assert!(
+                matches!(validate_email(email), Err(PlexoAppError::InvalidEmail)),
+                "{email:?} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn redirect_to_accepts_paths_on_this_site() {
+        assert_eq!(validate_redirect_to("/projects?tab=tasks").unwrap(), "/projects?tab=tasks");
+    }
+
+    #[test]
+    fn redirect_to_rejects_other_origins() {
+        for redirect_to in [
+            "//evil.com",
+            "/\\evil.com",
+            "/\t/evil.com",
+            "/\n/evil.com",
+            "https://evil.com/",
+            "javascript:alert(1)",
+        ] {
+            assert!(
+                matches!(validate_redirect_to(redirect_to), Err(PlexoAppError::InvalidRedirect)),
+                "{redirect_to:?} was accepted"
+            );
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use chrono::{DateTime, Utc};
+use plexo_sdk::{
+    common::commons::UpdateListInput,
+    resources::{
+        members::operations::MemberCrudOperations,
+        tasks::{
+            operations::{TaskCrudOperations, UpdateTaskInputBuilder},
+            task::{Task, TaskStatus},
+        },
+    },
+};
+use uuid::Uuid;
+
+use crate::{core::app::Core, errors::app::PlexoAppError};
+
+impl Core {
+    pub async fn is_member_deactivated(&self, member_id: Uuid) -> Result<bool, PlexoAppError> {
+        Ok(self.get_member_deactivated_at(member_id).await?.is_some())
+    }
+
+    pub async fn get_member_deactivated_at(&self, member_id: Uuid) -> Result<Option<DateTime<Utc>>, PlexoAppError> {
+        let deactivated_at = sqlx::query_scalar("SELECT deactivated_at FROM member_deactivations WHERE member_id = $1")
+            .bind(member_id)
+            .fetch_optional(self.engine.db_pool.as_ref())
+            .await?;
+
+        Ok(deactivated_at)
+    }
+
+    pub async fn get_deactivated_member_ids(&self) -> Result<Vec<Uuid>, PlexoAppError> {
+        let member_ids = sqlx::query_scalar("SELECT member_id FROM member_deactivations")
+            .fetch_all(self.engine.db_pool.as_ref())
+            .await?;
+
+        Ok(member_ids)
+    }
+
+    /// Keeps the member and everything attributed to them, but signs them out everywhere and
+    /// refuses any further login or token. Deactivating twice keeps the first date.
+    pub async fn deactivate_member(&self, member_id: Uuid, deactivated_by_id: Option<Uuid>) -> Result<(), PlexoAppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO member_deactivations (member_id, deactivated_by_id)
+            VALUES ($1, $2)
+            ON CONFLICT (member_id) DO NOTHING
+            "#,
+        )
+        .bind(member_id)
+        .bind(deactivated_by_id)
+        .execute(self.engine.db_pool.as_ref())
+        .await?;
+
+        self.revoke_all_sessions(member_id).await?;
+
+        Ok(())
+    }
+
+    pub async fn reactivate_member(&self, member_id: Uuid) -> Result<(), PlexoAppError> {
+        sqlx::query("DELETE FROM member_deactivations WHERE member_id = $1")
+            .bind(member_id)
+            .execute(self.engine.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn ensure_member_active(&self, member_id: Uuid) -> Result<(), PlexoAppError> {
+        if self.is_member_deactivated(member_id).await? {
+            return Err(PlexoAppError::MemberDeactivated);
+        }
+
+        Ok(())
+    }
+
+    /// Hands the open tasks a member leads or is assigned to over to another member, so work
+    /// doesn't stall when someone leaves. Done and canceled tasks keep their people.
+    pub async fn reassign_open_tasks(&self, from_member_id: Uuid, to_member_id: Uuid) -> Result<Vec<Task>, PlexoAppError> {
+        if from_member_id == to_member_id {
+            return Err(PlexoAppError::InvalidRequest(
+                "tasks can't be reassigned to the same member".to_string(),
+            ));
+        }
+
+        self.engine
+            .get_member(to_member_id)
+            .await
+            .map_err(|_| PlexoAppError::MemberNotFound)?;
+
+        if self.is_member_deactivated(to_member_id).await? {
+            return Err(PlexoAppError::InvalidRequest(
+                "tasks can't be reassigned to a deactivated member".to_string(),
+            ));
+        }
+
+        let task_ids: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            SELECT id FROM tasks
+            WHERE (status IS NULL OR status NOT IN ($2, $3))
+                AND (lead_id = $1 OR EXISTS (SELECT 1 FROM tasks_by_assignees WHERE task_id = tasks.id AND assignee_id = $1))
+            ORDER BY created_at
+            "#,
+        )
+        .bind(from_member_id)
+        .bind(TaskStatus::Done.to_string())
+        .bind(TaskStatus::Canceled.to_string())
+        .fetch_all(self.engine.db_pool.as_ref())
+        .await?;
+
+        let mut tasks = Vec::with_capacity(task_ids.len());
+
+        for task_id in task_ids {
+            let assignee_ids: Vec<Uuid> = sqlx::query_scalar("SELECT assignee_id FROM tasks_by_assignees WHERE task_id = $1")
+                .bind(task_id)
+                .fetch_all(self.engine.db_pool.as_ref())
+                .await?;
+
+            let task = self.engine.get_task(task_id).await?;
+
+            let mut input = UpdateTaskInputBuilder::default();
+
+            if task.lead_id == Some(from_member_id) {
+                input = input.lead_id(to_member_id);
+            }
+
+            if assignee_ids.contains(&from_member_id) {
+                input = input.assignees(UpdateListInput {
+                    add: match assignee_ids.contains(&to_member_id) {
+                        true => vec![],
+                        false => vec![to_member_id],
+                    },
+                    remove: vec![from_member_id],
+                });
+            }
+
+            tasks.push(self.engine.update_task(task_id, input.build().unwrap()).await?);
+        }
+
+        Ok(tasks)
+    }
+}
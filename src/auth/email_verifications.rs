@@ -0,0 +1,268 @@
+use askama::Template;
+use async_graphql::SimpleObject;
+use chrono::{Duration, Utc};
+use plexo_sdk::{
+    organization::operations::{OrganizationCrudOperations, SetOrganizationInput},
+    resources::members::member::Member,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        app::Core,
+        config::{EMAIL_VERIFICATION_TOKEN_TTL_HOURS, EMAIL_VERIFICATION_URL},
+        email::EmailVerificationTemplate,
+    },
+    errors::app::PlexoAppError,
+};
+
+use super::commons::{generate_link_token, hash_link_token};
+
+const EMAIL_VERIFICATION_TOKEN_LENGTH: usize = 48;
+const SELF_REGISTRATION_SETTING_NAME: &str = "self_registration";
+
+/// Organization-wide rules for members who sign up on their own, stored as an organization
+/// setting. The domains apply to registrations and to first sign-ins with an external provider,
+/// not to invited or provisioned members.
+#[derive(SimpleObject, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelfRegistrationSettings {
+    /// Members with an unverified email can't log in.
+    pub require_verified_email: bool,
+    /// Email domains allowed to self-register, like `plexo.app`. Anyone can register when empty.
+    pub allowed_email_domains: Vec<String>,
+}
+
+fn email_domain(email: &str) -> Option<String> {
+    email
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.trim().to_lowercase())
+        .filter(|domain| !domain.is_empty())
+}
+
+fn normalize_email_domains(domains: Vec<String>) -> Vec<String> {
+    let mut domains: Vec<String> = domains
+        .into_iter()
+        .map(|domain| domain.trim().trim_start_matches('@').to_lowercase())
+        .filter(|domain| !domain.is_empty())
+        .collect();
+
+    domains.sort();
+    domains.dedup();
+
+    domains
+}
+
+impl Core {
+    pub async fn get_self_registration_settings(&self) -> Result<SelfRegistrationSettings, PlexoAppError> {
+        let Some(setting) = self
+            .engine
+            .get_organization_setting(SELF_REGISTRATION_SETTING_NAME.to_string())
+            .await?
+        else {
+            return Ok(SelfRegistrationSettings::default());
+        };
+
+        Ok(serde_json::from_str(&setting.value).unwrap_or_else(|err| {
+            warn!("self registration settings can't be read, using defaults: {:?}", err);
+            SelfRegistrationSettings::default()
+        }))
+    }
+
+    pub async fn update_self_registration_settings(
+        &self,
+        require_verified_email: Option<bool>,
+        allowed_email_domains: Option<Vec<String>>,
+        updated_by_id: Uuid,
+    ) -> Result<SelfRegistrationSettings, PlexoAppError> {
+        let mut settings = self.get_self_registration_settings().await?;
+
+        if let Some(require_verified_email) = require_verified_email {
+            settings.require_verified_email = require_verified_email;
+        }
+
+        if let Some(allowed_email_domains) = allowed_email_domains {
+            settings.allowed_email_domains = normalize_email_domains(allowed_email_domains);
+        }
+
+        let value = serde_json::to_string(&settings).unwrap();
+
+        // The SDK only inserts settings, so an existing row is updated in place to keep a
+        // single value per name.
+        let updated = sqlx::query("UPDATE organization SET value = $2 WHERE name = $1")
+            .bind(SELF_REGISTRATION_SETTING_NAME)
+            .bind(value.clone())
+            .execute(self.engine.db_pool.as_ref())
+            .await?;
+
+        if updated.rows_affected() == 0 {
+            self.engine
+                .set_organization_setting(SetOrganizationInput {
+                    owner_id: updated_by_id,
+                    name: SELF_REGISTRATION_SETTING_NAME.to_string(),
+                    value,
+                })
+                .await?;
+        }
+
+        Ok(settings)
+    }
+
+    pub async fn ensure_email_domain_allowed(&self, email: &str) -> Result<(), PlexoAppError> {
+        let settings = self.get_self_registration_settings().await?;
+
+        if settings.allowed_email_domains.is_empty() {
+            return Ok(());
+        }
+
+        match email_domain(email) {
+            Some(domain) if settings.allowed_email_domains.contains(&domain) => Ok(()),
+            _ => Err(PlexoAppError::EmailDomainNotAllowed),
+        }
+    }
+
+    /// Only emails that went through verification, on registration or when changed from the
+    /// profile, can be unverified. Everyone else was vouched for by an invitation, an identity
+    /// provider or an admin.
+    pub async fn is_email_verified(&self, member_id: Uuid) -> Result<bool, PlexoAppError> {
+        let verified: Option<bool> =
+            sqlx::query_scalar("SELECT verified_at IS NOT NULL FROM member_email_verifications WHERE member_id = $1")
+                .bind(member_id)
+                .fetch_optional(self.engine.db_pool.as_ref())
+                .await?;
+
+        Ok(verified.unwrap_or(true))
+    }
+
+    pub async fn ensure_email_verified(&self, member_id: Uuid) -> Result<(), PlexoAppError> {
+        if !self.get_self_registration_settings().await?.require_verified_email {
+            return Ok(());
+        }
+
+        if !self.is_email_verified(member_id).await? {
+            return Err(PlexoAppError::EmailNotVerified);
+        }
+
+        Ok(())
+    }
+
+    /// Marks the member's current email as unverified and emails them a single-use link to
+    /// verify it. The email is sent in the background.
+    pub async fn start_email_verification(&self, member: &Member) -> Result<(), PlexoAppError> {
+        let token = generate_link_token(EMAIL_VERIFICATION_TOKEN_LENGTH);
+        let expires_at = Utc::now() + Duration::try_hours(*EMAIL_VERIFICATION_TOKEN_TTL_HOURS).unwrap();
+
+        let mut tx = self.engine.db_pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO member_email_verifications (member_id, email)
+            VALUES ($1, $2)
+            ON CONFLICT (member_id) DO UPDATE SET email = excluded.email, verified_at = NULL
+            "#,
+        )
+        .bind(member.id)
+        .bind(member.email.clone())
+        .execute(&mut *tx)
+        .await?;
+
+        // Only the latest link stays valid.
+        sqlx::query(
+            r#"
+            UPDATE email_verification_tokens
+            SET used_at = now()
+            WHERE member_id = $1 AND used_at IS NULL
+            "#,
+        )
+        .bind(member.id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO email_verification_tokens (member_id, email, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(member.id)
+        .bind(member.email.clone())
+        .bind(hash_link_token(&token))
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let html = EmailVerificationTemplate {
+            member_name: member.name.clone(),
+            verification_url: format!("{}?token={}", *EMAIL_VERIFICATION_URL, token),
+            expires_in_hours: *EMAIL_VERIFICATION_TOKEN_TTL_HOURS,
+        }
+        .render()?;
+
+        let core = self.clone();
+        let email = member.email.clone();
+
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = core.send_email("no-reply@plexo.app", &email, "Verify your Plexo email", &html) {
+                warn!("email verification not sent: {:?}", err);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Sends a new link to a member whose email is still unverified.
+    pub async fn resend_email_verification(&self, member: &Member) -> Result<(), PlexoAppError> {
+        if self.is_email_verified(member.id).await? {
+            return Err(PlexoAppError::InvalidRequest("email is already verified".to_string()));
+        }
+
+        self.start_email_verification(member).await
+    }
+
+    /// Consumes a verification token. It only verifies the email it was sent to, so a link
+    /// for an address the member has since changed no longer counts.
+    pub async fn verify_email(&self, token: &str) -> Result<Uuid, PlexoAppError> {
+        let mut tx = self.engine.db_pool.begin().await?;
+
+        let verification: Option<(Uuid, String)> = sqlx::query_as(
+            r#"
+            UPDATE email_verification_tokens
+            SET used_at = now()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+            RETURNING member_id, email
+            "#,
+        )
+        .bind(hash_link_token(token))
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some((member_id, email)) = verification else {
+            return Err(PlexoAppError::InvalidEmailVerificationToken);
+        };
+
+        let verified = sqlx::query(
+            r#"
+            UPDATE member_email_verifications
+            SET verified_at = now()
+            WHERE member_id = $1 AND email = $2
+                AND EXISTS (SELECT 1 FROM members WHERE members.id = $1 AND members.email = $2)
+            "#,
+        )
+        .bind(member_id)
+        .bind(email)
+        .execute(&mut *tx)
+        .await?;
+
+        if verified.rows_affected() == 0 {
+            return Err(PlexoAppError::InvalidEmailVerificationToken);
+        }
+
+        tx.commit().await?;
+
+        Ok(member_id)
+    }
+}
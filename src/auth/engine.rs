@@ -4,16 +4,20 @@ use argon2::{
 };
 use oauth2::{
     basic::BasicClient, reqwest::async_http_client, AuthUrl, AuthorizationCode, ClientId,
-    ClientSecret, CsrfToken, RedirectUrl, Scope, TokenResponse, TokenUrl,
+    ClientSecret, CsrfToken, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope,
+    TokenResponse, TokenUrl,
 };
 
 use reqwest::Url;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::errors::app::PlexoAppError;
 
 use super::{
     jwt::{JWTEngine, PlexoAuthTokenClaims},
+    oidc::{OidcProvider, OidcProviderConfig},
     resources::PlexoAuthToken,
+    signing_keys::JwtSigningKey,
 };
 
 #[derive(Clone)]
@@ -21,17 +25,20 @@ pub struct AuthEngine {
     pub jwt_engine: JWTEngine,
 
     github_client: Option<BasicClient>,
-    _google_client: Option<BasicClient>,
+    oidc_providers: Arc<HashMap<String, OidcProvider>>,
 }
 
 impl AuthEngine {
     pub fn new(
         jwt_access_token_secret: String,
         jwt_refresh_token_secret: String,
+        jwt_signing_keys: Vec<JwtSigningKey>,
         //
         github_client_id: Option<String>,
         github_client_secret: Option<String>,
         github_redirect_url: Option<String>,
+        //
+        oidc_provider_configs: Vec<OidcProviderConfig>,
     ) -> Self {
         let mut github_client: Option<BasicClient> = None;
 
@@ -91,41 +98,54 @@ impl AuthEngine {
         let jwt_engine = JWTEngine::new(
             jwt_access_token_secret.to_string(),
             jwt_refresh_token_secret.to_string(),
+            jwt_signing_keys,
         );
 
+        let oidc_providers = oidc_provider_configs
+            .into_iter()
+            .map(|config| (config.name.clone(), OidcProvider::new(config)))
+            .collect();
+
         Self {
             jwt_engine,
             github_client,
-            _google_client: None,
+            oidc_providers: Arc::new(oidc_providers),
         }
     }
 
-    pub fn new_github_authorize_url(&self) -> Option<(Url, CsrfToken)> {
+    /// The state and PKCE verifier must be kept until the callback to check it's the same
+    /// sign-in.
+    pub fn new_github_authorize_url(&self) -> Option<(Url, CsrfToken, PkceCodeVerifier)> {
         self.github_client.as_ref().map(|client| {
-            client
+            let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+            let (url, state) = client
                 .authorize_url(CsrfToken::new_random)
                 .add_scope(Scope::new("user:email".to_string()))
-                .url()
+                .set_pkce_challenge(pkce_challenge)
+                .url();
+
+            (url, state, pkce_verifier)
         })
     }
 
     pub async fn exchange_github_code(
         &self,
         code: AuthorizationCode,
-        _state: CsrfToken,
-    ) -> Result<String, String> {
-        let token_result = self
-            .github_client
-            .as_ref()
-            .unwrap()
+        pkce_verifier: PkceCodeVerifier,
+    ) -> Result<String, PlexoAppError> {
+        let Some(github_client) = self.github_client.as_ref() else {
+            return Err(PlexoAppError::UnknownAuthProvider);
+        };
+
+        let token = github_client
             .exchange_code(code)
+            .set_pkce_verifier(pkce_verifier)
             .request_async(async_http_client)
-            .await;
+            .await
+            .map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))?;
 
-        match token_result {
-            Ok(token) => Ok(token.access_token().secret().to_string()),
-            Err(e) => Err(e.to_string()),
-        }
+        Ok(token.access_token().secret().to_string())
     }
 
     pub fn extract_claims(
@@ -159,7 +179,7 @@ impl AuthEngine {
         self.github_client.is_some()
     }
 
-    pub fn has_google_client(&self) -> bool {
-        self._google_client.is_some()
+    pub fn oidc_provider(&self, name: &str) -> Option<&OidcProvider> {
+        self.oidc_providers.get(name)
     }
 }
@@ -0,0 +1,55 @@
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{core::config::GITHUB_API_URL, errors::app::PlexoAppError};
+
+use super::identities::{ExternalIdentity, GITHUB_PROVIDER};
+
+#[derive(Debug, Deserialize)]
+struct GithubUser {
+    id: i64,
+    login: String,
+    name: Option<String>,
+    avatar_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+async fn get_github_api<T: DeserializeOwned>(access_token: &str, path: &str) -> Result<T, PlexoAppError> {
+    reqwest::Client::new()
+        .get(format!("{}{}", GITHUB_API_URL.trim_end_matches('/'), path))
+        .bearer_auth(access_token)
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", "plexo-agent")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))?
+        .json::<T>()
+        .await
+        .map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))
+}
+
+/// The GitHub account behind an access token. The email is the verified primary one, which
+/// GitHub only lists under `/user/emails` when the profile email is private.
+pub async fn fetch_github_identity(access_token: &str) -> Result<ExternalIdentity, PlexoAppError> {
+    let user = get_github_api::<GithubUser>(access_token, "/user").await?;
+    let emails = get_github_api::<Vec<GithubEmail>>(access_token, "/user/emails").await?;
+
+    let Some(primary_email) = emails.into_iter().find(|email| email.primary && email.verified) else {
+        return Err(PlexoAppError::MissingIdentityEmail);
+    };
+
+    Ok(ExternalIdentity {
+        provider: GITHUB_PROVIDER.to_string(),
+        subject: user.id.to_string(),
+        email: Some(primary_email.email),
+        email_verified: true,
+        name: user.name.filter(|name| !name.trim().is_empty()).or(Some(user.login)),
+        photo_url: user.avatar_url,
+    })
+}
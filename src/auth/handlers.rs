@@ -1,178 +1,355 @@
 use async_graphql::Error;
 use chrono::{Duration, Utc};
-use oauth2::{AuthorizationCode, CsrfToken};
-use plexo_sdk::resources::members::extensions::{
-    CreateMemberFromEmailInputBuilder, CreateMemberFromGithubInputBuilder, MembersExtensionOperations,
-};
-use plexo_sdk::resources::members::member::Member;
+use jsonwebtoken::jwk::JwkSet;
+use oauth2::{AuthorizationCode, PkceCodeVerifier};
+use plexo_sdk::resources::members::extensions::{CreateMemberFromEmailInputBuilder, MembersExtensionOperations};
 
 use poem::http::header::{CACHE_CONTROL, EXPIRES, LOCATION, PRAGMA, SET_COOKIE};
-use poem::http::StatusCode;
+use poem::http::{HeaderMap, StatusCode};
 use poem::web::cookie::{Cookie, SameSite};
-use poem::web::{Data, Json, Query, Redirect};
-use poem::{handler, Body, IntoResponse, Response, Result};
+use poem::web::{Data, Json, Path, Query, Redirect, RemoteAddr};
+use poem::{error::ResponseError, handler, Body, IntoResponse, Response, ResponseBuilder, Result};
 
-use serde_json::{json, Value};
+use serde_json::json;
+use tracing::info;
+use uuid::Uuid;
 
 use crate::core::app::Core;
-use crate::core::config::{COOKIE_SESSION_DOMAIN, COOKIE_SESSION_NAME, COOKIE_SESSION_SAME_SITE, COOKIE_SESSION_SECURE};
+use crate::core::config::{
+    COOKIE_OAUTH_STATE_NAME, COOKIE_REFRESH_NAME, COOKIE_SESSION_DOMAIN, COOKIE_SESSION_NAME, COOKIE_SESSION_SAME_SITE,
+    COOKIE_SESSION_SECURE,
+};
+use crate::errors::app::PlexoAppError;
 
 use super::{
-    commons::GITHUB_USER_API,
-    resources::{EmailLoginParams, EmailRegisterParams, GithubCallbackParams},
+    commons::{
+        get_client_ip, get_oauth_state_from_cookie, get_refresh_token_from_cookie, get_token_from_cookie, get_token_from_headers,
+        validate_email, validate_redirect_to,
+    },
+    github::fetch_github_identity,
+    identities::{ExternalIdentity, GITHUB_PROVIDER},
+    invitations::InvitationCredentials,
+    jwt::OAuthState,
+    refresh_tokens::SessionTokens,
+    resources::{
+        AcceptInvitationParams, EmailLoginChallengeParams, EmailLoginParams, EmailRegisterParams, ForgotPasswordParams,
+        GithubCallbackParams, GithubSignInParams, OidcCallbackParams, OidcSignInParams, RefreshTokenParams, ResetPasswordParams,
+        VerifyEmailParams,
+    },
 };
 
 #[handler]
-pub async fn github_sign_in_handler(plexo_core: Data<&Core>) -> impl IntoResponse {
-    let Some((url, _)) = plexo_core.0.auth.new_github_authorize_url() else {
-        return Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .header("Content-Type", "application/json")
-            .body(Body::from_json(&Error::new("Internal Server Error (github)")).unwrap());
+pub async fn github_sign_in_handler(plexo_core: Data<&Core>, params: Query<GithubSignInParams>) -> Result<Response> {
+    let Some((url, state, pkce_verifier)) = plexo_core.0.auth.new_github_authorize_url() else {
+        return Err(PlexoAppError::UnknownAuthProvider.into());
     };
 
-    Redirect::temporary(url.to_string())
-        // .with_header("Set-Cookie", session_token_cookie.to_string())
-        // .with_header(CACHE_CONTROL, "no-cache, no-store, must-revalidate")
-        // .with_header(PRAGMA, "no-cache")
-        // .with_header(EXPIRES, "0")
-        .into_response()
+    let redirect_to = params.0.redirect_to.as_deref().map(validate_redirect_to).transpose()?;
+    let link_member_id = identity_link_member_id(&plexo_core, params.0.link_token.as_deref())?;
+
+    // The invitation is carried over to the callback, where it's accepted with the GitHub account.
+    let response = new_oauth_redirect(
+        &plexo_core,
+        url.as_str(),
+        OAuthState {
+            provider: GITHUB_PROVIDER.to_string(),
+            state: state.secret().to_string(),
+            pkce_verifier: pkce_verifier.secret().to_string(),
+            nonce: None,
+            invitation: params.0.invitation,
+            redirect_to,
+            link_member_id,
+        },
+    )?;
+
+    Ok(response)
 }
 
+/// Checks the state against the cookie set by `github_sign_in_handler` before redeeming the
+/// code, then signs the member in, or links the GitHub account.
 #[handler]
-pub async fn github_callback_handler(plexo_core: Data<&Core>, params: Query<GithubCallbackParams>) -> impl IntoResponse {
-    let code = AuthorizationCode::new(params.code.clone());
-    let state = CsrfToken::new(params.state.clone());
+pub async fn github_callback_handler(
+    plexo_core: Data<&Core>,
+    headers: &HeaderMap,
+    params: Query<GithubCallbackParams>,
+) -> Result<Response> {
+    let params = params.0;
 
-    let gh_response = plexo_core.auth.exchange_github_code(code, state).await;
+    if let Some(error) = params.error {
+        let description = params.error_description.unwrap_or_default();
 
-    let Ok(access_token) = gh_response else {
-        return Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .header("Content-Type", "application/json")
-            .body(Body::from_json(&gh_response).unwrap());
+        return Err(PlexoAppError::AuthProviderError(format!("{} {}", error, description).trim().to_string()).into());
+    }
+
+    let (Some(code), Some(state)) = (params.code, params.state) else {
+        return Err(PlexoAppError::InvalidOAuthState.into());
     };
 
-    let client = reqwest::Client::new();
+    let oauth_state = validate_oauth_callback(&plexo_core, headers, GITHUB_PROVIDER, state.as_str())?;
 
-    let github_user_data = client
-        .get(GITHUB_USER_API)
-        .header("Authorization", format!("token {}", access_token))
-        .header("User-Agent", "plexo-agent")
-        .send()
-        .await
-        .unwrap()
-        .json::<Value>()
-        .await
-        .unwrap();
-
-    let github_id: String = github_user_data.get("id").unwrap().as_i64().unwrap().to_string();
-
-    let user_email = github_user_data
-        .get("email")
-        .map(|v| {
-            v.as_str()
-                .map(|s| s.to_string())
-                .unwrap_or(format!("{}@no-email.github.com", github_id.clone()))
-        })
-        .unwrap();
-
-    let user_name = github_user_data
-        .get("name")
-        .map(|v| v.as_str().map(|s| s.to_string()).unwrap_or(github_id.clone()))
-        .unwrap();
-
-    let member: Member = match plexo_core.0.engine.get_member_by_github_id(github_id.clone()).await {
-        Ok(Some(member)) => member,
-        Ok(None) | Err(_) => plexo_core
-            .0
-            .engine
-            .create_member_from_github(
-                CreateMemberFromGithubInputBuilder::default()
-                    .email(user_email)
-                    .name(user_name)
-                    .github_id(github_id)
-                    .build()
-                    .unwrap(),
-            )
-            .await
-            .unwrap(),
+    let access_token = plexo_core
+        .auth
+        .exchange_github_code(
+            AuthorizationCode::new(code),
+            PkceCodeVerifier::new(oauth_state.pkce_verifier.clone()),
+        )
+        .await?;
+
+    let identity = fetch_github_identity(access_token.as_str()).await?;
+
+    let response = finish_oauth_callback(&plexo_core, headers, oauth_state, identity).await?;
+
+    Ok(response)
+}
+
+#[handler]
+pub async fn oidc_sign_in_handler(
+    plexo_core: Data<&Core>,
+    Path(provider): Path<String>,
+    params: Query<OidcSignInParams>,
+) -> Result<Response> {
+    let Some(oidc_provider) = plexo_core.auth.oidc_provider(provider.as_str()) else {
+        return Err(PlexoAppError::UnknownAuthProvider.into());
     };
 
-    let Ok(session_token) = plexo_core.auth.jwt_engine.create_session_token(&member) else {
-        return Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .header("Content-Type", "application/json")
-            .body(Body::from_json(&Error::new("Internal Server Error")).unwrap());
+    let redirect_to = params.0.redirect_to.as_deref().map(validate_redirect_to).transpose()?;
+    let link_member_id = identity_link_member_id(&plexo_core, params.0.link_token.as_deref())?;
+
+    let authorization = oidc_provider.authorize_url().await?;
+
+    let response = new_oauth_redirect(
+        &plexo_core,
+        authorization.url.as_str(),
+        OAuthState {
+            provider,
+            state: authorization.state,
+            pkce_verifier: authorization.pkce_verifier,
+            nonce: Some(authorization.nonce),
+            invitation: params.0.invitation,
+            redirect_to,
+            link_member_id,
+        },
+    )?;
+
+    Ok(response)
+}
+
+/// Checks the state against the cookie set by `oidc_sign_in_handler` before redeeming the
+/// code, then signs the member in, or links the identity, like the GitHub callback does.
+#[handler]
+pub async fn oidc_callback_handler(
+    plexo_core: Data<&Core>,
+    Path(provider): Path<String>,
+    headers: &HeaderMap,
+    params: Query<OidcCallbackParams>,
+) -> Result<Response> {
+    let params = params.0;
+
+    if let Some(error) = params.error {
+        let description = params.error_description.unwrap_or_default();
+
+        return Err(PlexoAppError::AuthProviderError(format!("{} {}", error, description).trim().to_string()).into());
+    }
+
+    let (Some(code), Some(state)) = (params.code, params.state) else {
+        return Err(PlexoAppError::InvalidOAuthState.into());
     };
 
-    let mut session_token_cookie = Cookie::named(COOKIE_SESSION_NAME.to_string());
+    let oauth_state = validate_oauth_callback(&plexo_core, headers, provider.as_str(), state.as_str())?;
 
-    session_token_cookie.set_value_str(session_token);
-    session_token_cookie.set_http_only(true);
-    session_token_cookie.set_secure(true);
-    session_token_cookie.set_same_site(SameSite::Lax);
-    session_token_cookie.set_expires(Utc::now() + Duration::try_days(7).unwrap());
-    session_token_cookie.set_path("/");
+    let Some(oidc_provider) = plexo_core.auth.oidc_provider(provider.as_str()) else {
+        return Err(PlexoAppError::UnknownAuthProvider.into());
+    };
 
-    Response::builder()
-        .status(StatusCode::FOUND)
-        .header(LOCATION, "/")
-        .header(CACHE_CONTROL, "no-cache, no-store, must-revalidate")
-        .header(PRAGMA, "no-cache")
-        .header(EXPIRES, "0")
-        .header(SET_COOKIE, session_token_cookie.to_string())
-        .body(Body::empty())
+    let claims = oidc_provider
+        .exchange_code(
+            code.as_str(),
+            oauth_state.pkce_verifier.clone(),
+            oauth_state.nonce.as_deref().unwrap_or_default(),
+        )
+        .await?;
+
+    let response = finish_oauth_callback(&plexo_core, headers, oauth_state, claims.into_identity(provider.as_str())).await?;
+
+    Ok(response)
 }
 
 #[handler]
-pub async fn email_basic_login_handler(plexo_engine: Data<&Core>, params: Json<EmailLoginParams>) -> impl IntoResponse {
-    let Ok(Some(member)) = plexo_engine.0.engine.get_member_by_email(params.email.clone()).await else {
-        return Response::builder()
-            .status(StatusCode::UNAUTHORIZED)
-            .header("Content-Type", "application/json")
-            .body(
-                Body::from_json(json!({
-                    "error": "Member not found"
-                }))
-                .unwrap(),
-            );
+pub async fn email_basic_login_handler(
+    plexo_engine: Data<&Core>,
+    headers: &HeaderMap,
+    remote_addr: &RemoteAddr,
+    params: Json<EmailLoginParams>,
+) -> impl IntoResponse {
+    let client_ip = get_client_ip(headers, remote_addr);
+
+    let member = match plexo_engine
+        .login_with_password(params.email.as_str(), params.password.as_str(), &client_ip)
+        .await
+    {
+        Ok(member) => member,
+        Err(err @ (PlexoAppError::InvalidCredentials | PlexoAppError::TooManyLoginAttempts)) => {
+            return Response::builder()
+                .status(err.status())
+                .header("Content-Type", "application/json")
+                .body(
+                    Body::from_json(json!({
+                        "error": err.to_string()
+                    }))
+                    .unwrap(),
+                );
+        }
+        Err(_) => {
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .header("Content-Type", "application/json")
+                .body(Body::from_json(Error::new("Internal Server Error")).unwrap());
+        }
     };
 
-    let Some(password_hash) = member.password_hash.clone() else {
-        return Response::builder()
-            .status(StatusCode::UNAUTHORIZED)
-            .header("Content-Type", "application/json")
-            .body(
-                Body::from_json(json!({
-                    "error": "Invalid password"
-                }))
-                .unwrap(),
-            );
+    let challenge_token = match plexo_engine.create_login_challenge(member.id).await {
+        Ok(challenge_token) => challenge_token,
+        Err(_) => {
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .header("Content-Type", "application/json")
+                .body(Body::from_json(Error::new("Internal Server Error")).unwrap());
+        }
     };
 
-    if !plexo_engine
-        .auth
-        .validate_password(params.password.as_str(), password_hash.as_str())
-    {
+    if let Some(challenge_token) = challenge_token {
         return Response::builder()
-            .status(StatusCode::UNAUTHORIZED)
+            .status(StatusCode::OK)
             .header("Content-Type", "application/json")
             .body(
                 Body::from_json(json!({
-                    "error": "Invalid password"
+                    "two_factor_required": true,
+                    "challenge_token": challenge_token
                 }))
                 .unwrap(),
             );
-    };
+    }
 
-    let Ok(session_token) = plexo_engine.auth.jwt_engine.create_session_token(&member) else {
+    let Ok(session_tokens) = plexo_engine.create_session_tokens(&member).await else {
         return Response::builder()
             .status(StatusCode::INTERNAL_SERVER_ERROR)
             .header("Content-Type", "application/json")
-            .body(Body::from_json(&Error::new("Internal Server Error")).unwrap());
+            .body(Body::from_json(Error::new("Internal Server Error")).unwrap());
     };
 
+    new_session_response(&session_tokens)
+}
+
+/// Second step of the email login for members with two-factor authentication enabled.
+#[handler]
+pub async fn email_login_challenge_handler(
+    plexo_engine: Data<&Core>,
+    headers: &HeaderMap,
+    remote_addr: &RemoteAddr,
+    params: Json<EmailLoginChallengeParams>,
+) -> Result<Response> {
+    let client_ip = get_client_ip(headers, remote_addr);
+
+    let member = plexo_engine
+        .complete_login_challenge(params.challenge_token.as_str(), params.code.as_str(), &client_ip)
+        .await?;
+
+    let session_tokens = plexo_engine.create_session_tokens(&member).await?;
+
+    Ok(new_session_response(&session_tokens))
+}
+
+#[handler]
+pub async fn refresh_handler(
+    plexo_engine: Data<&Core>,
+    headers: &HeaderMap,
+    params: Option<Json<RefreshTokenParams>>,
+) -> Result<Response> {
+    let refresh_token = params
+        .map(|params| params.0.refresh_token)
+        .or_else(|| get_refresh_token_from_cookie(headers));
+
+    let Some(refresh_token) = refresh_token else {
+        return Err(PlexoAppError::MissingAuthorizationToken.into());
+    };
+
+    let session_tokens = plexo_engine.refresh_session_tokens(refresh_token.as_str()).await?;
+
+    Ok(new_session_response(&session_tokens))
+}
+
+/// Public keys that verify the tokens Plexo issues, for other services.
+#[handler]
+pub async fn jwks_handler(plexo_engine: Data<&Core>) -> Json<JwkSet> {
+    Json(plexo_engine.auth.jwt_engine.jwks())
+}
+
+#[handler]
+pub async fn accept_invitation_handler(plexo_engine: Data<&Core>, params: Json<AcceptInvitationParams>) -> Result<Response> {
+    let params = params.0;
+
+    let member = plexo_engine
+        .accept_invitation(
+            params.token.as_str(),
+            params.name,
+            InvitationCredentials::Password(params.password),
+        )
+        .await?;
+
+    let session_tokens = plexo_engine.create_session_tokens(&member).await?;
+
+    Ok(new_session_response(&session_tokens))
+}
+
+/// Always answers the same way, whether or not the email belongs to a member.
+#[handler]
+pub async fn forgot_password_handler(plexo_engine: Data<&Core>, params: Json<ForgotPasswordParams>) -> Result<Response> {
+    plexo_engine.request_password_reset(params.email.clone());
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(
+            Body::from_json(json!({
+                "message": "If the email belongs to an account, a password reset link has been sent"
+            }))
+            .unwrap(),
+        ))
+}
+
+#[handler]
+pub async fn reset_password_handler(plexo_engine: Data<&Core>, params: Json<ResetPasswordParams>) -> Result<Response> {
+    plexo_engine
+        .reset_password(params.token.as_str(), params.password.as_str())
+        .await?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(
+            Body::from_json(json!({
+                "message": "Password updated"
+            }))
+            .unwrap(),
+        ))
+}
+
+#[handler]
+pub async fn verify_email_handler(plexo_engine: Data<&Core>, params: Json<VerifyEmailParams>) -> Result<Response> {
+    plexo_engine.verify_email(params.token.as_str()).await?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(
+            Body::from_json(json!({
+                "message": "Email verified"
+            }))
+            .unwrap(),
+        ))
+}
+
+fn new_session_response(session_tokens: &SessionTokens) -> Response {
     let mut session_token_cookie = Cookie::named(COOKIE_SESSION_NAME.to_string());
 
     let cookie_secure = *COOKIE_SESSION_SECURE.to_lowercase() == *"true";
@@ -184,19 +361,174 @@ pub async fn email_basic_login_handler(plexo_engine: Data<&Core>, params: Json<E
 
     let cookie_domain = COOKIE_SESSION_DOMAIN.to_string();
 
-    session_token_cookie.set_value_str(session_token.clone());
+    session_token_cookie.set_value_str(session_tokens.access_token.clone());
     session_token_cookie.set_http_only(true);
     session_token_cookie.set_secure(cookie_secure);
     session_token_cookie.set_same_site(cookie_same_site);
-    session_token_cookie.set_expires(Utc::now() + Duration::try_days(7).unwrap());
+    session_token_cookie.set_expires(session_tokens.access_token_expires_at);
     session_token_cookie.set_path("/");
     session_token_cookie.set_domain(cookie_domain);
 
+    let refresh_token_cookie = new_refresh_token_cookie(&session_token_cookie, session_tokens);
+
     Response::builder()
         .status(StatusCode::OK)
         .header(SET_COOKIE, session_token_cookie.to_string())
+        .header(SET_COOKIE, refresh_token_cookie.to_string())
         .header("Content-Type", "application/json")
-        .body(Body::from_json(json!({ "access_token": session_token })).unwrap())
+        .body(
+            Body::from_json(json!({
+                "access_token": session_tokens.access_token,
+                "refresh_token": session_tokens.refresh_token,
+            }))
+            .unwrap(),
+        )
+}
+
+/// Starts an OAuth sign-in. The state is signed into a cookie scoped to the auth routes and
+/// checked by the callback, so a callback the browser didn't start is rejected.
+fn new_oauth_redirect(plexo_core: &Core, url: &str, oauth_state: OAuthState) -> Result<Response, PlexoAppError> {
+    let state_token = plexo_core.auth.jwt_engine.create_oauth_state_token(oauth_state)?;
+
+    let mut state_cookie = Cookie::named(COOKIE_OAUTH_STATE_NAME.to_string());
+
+    state_cookie.set_value_str(state_token);
+    state_cookie.set_http_only(true);
+    state_cookie.set_secure(true);
+    state_cookie.set_same_site(SameSite::Lax);
+    state_cookie.set_expires(plexo_core.auth.jwt_engine.oauth_state_expiration());
+    state_cookie.set_path("/auth");
+
+    let mut response = Redirect::temporary(url).into_response();
+
+    response
+        .headers_mut()
+        .append(SET_COOKIE, state_cookie.to_string().parse().unwrap());
+
+    Ok(response)
+}
+
+fn validate_oauth_callback(
+    plexo_core: &Core,
+    headers: &HeaderMap,
+    provider: &str,
+    state: &str,
+) -> Result<OAuthState, PlexoAppError> {
+    let Some(state_token) = get_oauth_state_from_cookie(headers) else {
+        return Err(PlexoAppError::InvalidOAuthState);
+    };
+
+    let oauth_state = plexo_core.auth.jwt_engine.decode_oauth_state_token(state_token.as_str())?;
+
+    if oauth_state.provider != provider || oauth_state.state != state {
+        return Err(PlexoAppError::InvalidOAuthState);
+    }
+
+    Ok(oauth_state)
+}
+
+fn identity_link_member_id(plexo_core: &Core, link_token: Option<&str>) -> Result<Option<Uuid>, PlexoAppError> {
+    let Some(link_token) = link_token else {
+        return Ok(None);
+    };
+
+    let claims = plexo_core.auth.jwt_engine.decode_identity_link_token(link_token)?;
+
+    claims.member_id().map(Some)
+}
+
+/// Links the identity to the member who started the flow, or signs in with it. Linking
+/// leaves the current session as it is, and only goes through when the browser is signed in
+/// as that member, so a started link can't be handed to someone else to finish.
+async fn finish_oauth_callback(
+    plexo_core: &Core,
+    headers: &HeaderMap,
+    oauth_state: OAuthState,
+    identity: ExternalIdentity,
+) -> Result<Response, PlexoAppError> {
+    let redirect_to = oauth_state.redirect_to.as_deref();
+
+    let response = match oauth_state.link_member_id {
+        Some(member_id) => {
+            let session_member_id = match get_token_from_cookie(headers) {
+                Some(session_token) => plexo_core
+                    .authenticate(&session_token)
+                    .await
+                    .ok()
+                    .map(|principal| principal.member_id),
+                None => None,
+            };
+
+            if session_member_id != Some(member_id) {
+                return Err(PlexoAppError::InvalidOAuthState);
+            }
+
+            plexo_core.link_identity(member_id, identity).await?;
+
+            Response::builder()
+                .status(StatusCode::FOUND)
+                .header(LOCATION, redirect_to.unwrap_or("/"))
+        }
+        None => {
+            let member = plexo_core
+                .sign_in_with_identity(identity, oauth_state.invitation.clone())
+                .await?;
+
+            let session_tokens = plexo_core.create_session_tokens(&member).await?;
+
+            new_session_redirect(&session_tokens, redirect_to)
+        }
+    };
+
+    Ok(response
+        .header(SET_COOKIE, expired_oauth_state_cookie().to_string())
+        .body(Body::empty()))
+}
+
+fn expired_oauth_state_cookie() -> Cookie {
+    let mut state_cookie = Cookie::named(COOKIE_OAUTH_STATE_NAME.to_string());
+
+    state_cookie.set_value_str("");
+    state_cookie.set_expires(Utc::now() - Duration::try_days(1).unwrap());
+    state_cookie.set_path("/auth");
+
+    state_cookie
+}
+
+/// Sends browser sign-ins back to the app with the session cookies set.
+fn new_session_redirect(session_tokens: &SessionTokens, redirect_to: Option<&str>) -> ResponseBuilder {
+    let mut session_token_cookie = Cookie::named(COOKIE_SESSION_NAME.to_string());
+
+    session_token_cookie.set_value_str(session_tokens.access_token.clone());
+    session_token_cookie.set_http_only(true);
+    session_token_cookie.set_secure(true);
+    session_token_cookie.set_same_site(SameSite::Lax);
+    session_token_cookie.set_expires(session_tokens.access_token_expires_at);
+    session_token_cookie.set_path("/");
+
+    let refresh_token_cookie = new_refresh_token_cookie(&session_token_cookie, session_tokens);
+
+    Response::builder()
+        .status(StatusCode::FOUND)
+        .header(LOCATION, redirect_to.unwrap_or("/"))
+        .header(CACHE_CONTROL, "no-cache, no-store, must-revalidate")
+        .header(PRAGMA, "no-cache")
+        .header(EXPIRES, "0")
+        .header(SET_COOKIE, session_token_cookie.to_string())
+        .header(SET_COOKIE, refresh_token_cookie.to_string())
+}
+
+/// The refresh token cookie mirrors the session cookie settings, but it's only sent to the
+/// auth routes and outlives the access token.
+fn new_refresh_token_cookie(session_token_cookie: &Cookie, session_tokens: &SessionTokens) -> Cookie {
+    let mut refresh_token_cookie = session_token_cookie.clone();
+
+    refresh_token_cookie.set_name(COOKIE_REFRESH_NAME.to_string());
+    refresh_token_cookie.set_value_str(session_tokens.refresh_token.clone());
+    refresh_token_cookie.set_expires(session_tokens.refresh_token_expires_at);
+    refresh_token_cookie.set_path("/auth");
+
+    refresh_token_cookie
 }
 
 #[handler]
@@ -209,7 +541,7 @@ pub async fn email_basic_register_handler(
 
     // let (plexo_engine, member_id) = extract_context(ctx)?;
 
-    if (plexo_engine.0.engine.get_member_by_email(params.email.clone()).await).is_ok() {
+    if (plexo_engine.0.engine.get_member_by_email(params.email.clone()).await).is_ok_and(|member| member.is_some()) {
         return Ok(Response::builder()
             .status(StatusCode::UNAUTHORIZED)
             .header("Content-Type", "application/json")
@@ -221,6 +553,9 @@ pub async fn email_basic_register_handler(
             ));
     };
 
+    validate_email(params.email.as_str())?;
+    plexo_engine.ensure_email_domain_allowed(params.email.as_str()).await?;
+
     let password_hash = plexo_engine.auth.hash_password(params.password.as_str());
 
     let Ok(member) = plexo_engine
@@ -239,35 +574,52 @@ pub async fn email_basic_register_handler(
         return Ok(Response::builder()
             .status(StatusCode::INTERNAL_SERVER_ERROR)
             .header("Content-Type", "application/json")
-            .body(Body::from_json(&Error::new("Internal Server Error")).unwrap()));
+            .body(Body::from_json(Error::new("Internal Server Error")).unwrap()));
     };
 
-    let Ok(session_token) = plexo_engine.auth.jwt_engine.create_session_token(&member) else {
+    plexo_engine.start_email_verification(&member).await?;
+
+    if plexo_engine.get_self_registration_settings().await?.require_verified_email {
+        return Ok(Response::builder()
+            .status(StatusCode::ACCEPTED)
+            .header("Content-Type", "application/json")
+            .body(
+                Body::from_json(json!({
+                    "message": "Check your email to verify it before logging in",
+                    "member_id": member.id,
+                }))
+                .unwrap(),
+            ));
+    }
+
+    let Ok(session_tokens) = plexo_engine.create_session_tokens(&member).await else {
         return Ok(Response::builder()
             .status(StatusCode::INTERNAL_SERVER_ERROR)
             .header("Content-Type", "application/json")
-            .body(Body::from_json(&Error::new("Internal Server Error")).unwrap()));
+            .body(Body::from_json(Error::new("Internal Server Error")).unwrap()));
     };
 
-    let mut session_token_cookie = Cookie::named(COOKIE_SESSION_NAME.to_string());
-
-    session_token_cookie.set_value_str(session_token.clone());
-    session_token_cookie.set_http_only(true);
-    session_token_cookie.set_secure(true);
-    session_token_cookie.set_same_site(SameSite::Lax);
-    session_token_cookie.set_expires(Utc::now() + Duration::try_days(7).unwrap());
-    session_token_cookie.set_path("/");
-
-    Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header(SET_COOKIE, session_token_cookie.to_string())
-        .header("Content-Type", "application/json")
-        .body(Body::from_json(json!({ "access_token": session_token })).unwrap()))
+    Ok(new_session_response(&session_tokens))
 }
 
 #[handler]
-pub async fn logout_handler() -> Result<Response> {
-    // plexo_engine: Data<&Engine>
+pub async fn logout_handler(plexo_engine: Data<&Core>, headers: &HeaderMap) -> Result<Response> {
+    let session_token = get_token_from_cookie(headers).or_else(|| get_token_from_headers(headers));
+
+    if let Some(claims) = session_token.and_then(|token| plexo_engine.auth.extract_claims(&token).ok()) {
+        if let (Ok(member_id), Ok(session_id)) = (claims.member_id(), claims.session_id()) {
+            if let Err(err) = plexo_engine.revoke_session(member_id, session_id).await {
+                info!("session not revoked on logout: {:?}", err);
+            }
+        }
+    }
+
+    if let Some(refresh_token) = get_refresh_token_from_cookie(headers) {
+        if let Err(err) = plexo_engine.revoke_refresh_token(refresh_token.as_str()).await {
+            info!("refresh token not revoked on logout: {:?}", err);
+        }
+    }
+
     let mut session_token_cookie = Cookie::named(COOKIE_SESSION_NAME.to_string());
 
     let cookie_domain = COOKIE_SESSION_DOMAIN.to_string();
@@ -280,9 +632,15 @@ pub async fn logout_handler() -> Result<Response> {
     session_token_cookie.set_domain(cookie_domain);
     session_token_cookie.set_path("/");
 
+    let mut refresh_token_cookie = session_token_cookie.clone();
+
+    refresh_token_cookie.set_name(COOKIE_REFRESH_NAME.to_string());
+    refresh_token_cookie.set_path("/auth");
+
     Ok(Response::builder()
         .status(StatusCode::OK)
         .header(SET_COOKIE, session_token_cookie.to_string())
+        .header(SET_COOKIE, refresh_token_cookie.to_string())
         .header("Content-Type", "application/json")
         .body(Body::from_json(json!({ "access_token": "" })).unwrap()))
 }
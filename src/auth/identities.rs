@@ -0,0 +1,272 @@
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use plexo_sdk::resources::members::{
+    extensions::{CreateMemberFromEmailInputBuilder, CreateMemberFromGithubInputBuilder, MembersExtensionOperations},
+    member::Member,
+    operations::{MemberCrudOperations, UpdateMemberInputBuilder},
+};
+use reqwest::Url;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{
+    core::{app::Core, config::DOMAIN},
+    errors::app::PlexoAppError,
+};
+
+use super::{commons::validate_redirect_to, invitations::InvitationCredentials};
+
+/// Identities from this provider are mirrored into `members.github_id`, which the SDK uses.
+pub const GITHUB_PROVIDER: &str = "github";
+
+/// An external account the member can sign in with.
+#[derive(Debug, SimpleObject, FromRow, Clone)]
+pub struct MemberIdentity {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+
+    pub member_id: Uuid,
+    /// `github`, or the name of an OIDC provider.
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+}
+
+/// A provider's account as returned by its callback.
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    /// Only verified emails are trusted to match an existing member.
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub photo_url: Option<String>,
+}
+
+impl Core {
+    pub async fn get_identities(&self, member_id: Uuid) -> Result<Vec<MemberIdentity>, PlexoAppError> {
+        let identities = sqlx::query_as::<_, MemberIdentity>(
+            r#"
+            SELECT * FROM member_identities
+            WHERE member_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(member_id)
+        .fetch_all(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(identities)
+    }
+
+    /// The address that starts linking a provider's account to the member. It must be
+    /// opened in the member's browser shortly after, since the token in it expires quickly.
+    pub fn identity_link_url(&self, member_id: Uuid, provider: &str, redirect_to: Option<String>) -> Result<String, PlexoAppError> {
+        let path = if provider == GITHUB_PROVIDER && self.auth.has_github_client() {
+            "/auth/github".to_string()
+        } else if self.auth.oidc_provider(provider).is_some() {
+            format!("/auth/oidc/{provider}")
+        } else {
+            return Err(PlexoAppError::UnknownAuthProvider);
+        };
+
+        let link_token = self.auth.jwt_engine.create_identity_link_token(member_id)?;
+
+        let mut url = Url::parse(&format!("{}{}", *DOMAIN, path)).map_err(|_| PlexoAppError::InvalidRedirect)?;
+        url.query_pairs_mut().append_pair("link_token", &link_token);
+
+        if let Some(redirect_to) = redirect_to {
+            url.query_pairs_mut()
+                .append_pair("redirect_to", &validate_redirect_to(&redirect_to)?);
+        }
+
+        Ok(url.to_string())
+    }
+
+    async fn get_identity_member_id(&self, provider: &str, subject: &str) -> Result<Option<Uuid>, PlexoAppError> {
+        let member_id = sqlx::query_scalar("SELECT member_id FROM member_identities WHERE provider = $1 AND subject = $2")
+            .bind(provider)
+            .bind(subject)
+            .fetch_optional(self.engine.db_pool.as_ref())
+            .await?;
+
+        Ok(member_id)
+    }
+
+    /// Finds or creates the member behind an external identity. Unknown identities accept
+    /// the pending invitation when there is one. Otherwise they're matched to the member with
+    /// the same verified email, and a new member is only created when no one has it. An
+    /// unverified email that's already taken is rejected, so the member confirms by signing
+    /// in and linking the identity from their profile. New members need an email domain that
+    /// may self-register.
+    pub async fn sign_in_with_identity(
+        &self,
+        identity: ExternalIdentity,
+        invitation_token: Option<String>,
+    ) -> Result<Member, PlexoAppError> {
+        if let Some(member_id) = self.get_identity_member_id(&identity.provider, &identity.subject).await? {
+            let member = self.engine.get_member(member_id).await?;
+
+            return self.sync_identity_photo(member, identity.photo_url).await;
+        }
+
+        let name = identity.name.clone().unwrap_or(identity.subject.clone());
+
+        if let Some(invitation_token) = invitation_token {
+            return self
+                .accept_invitation(invitation_token.as_str(), name, InvitationCredentials::Identity(identity))
+                .await;
+        }
+
+        let Some(email) = identity.email.clone() else {
+            return Err(PlexoAppError::MissingIdentityEmail);
+        };
+
+        let existing_member = self.engine.get_member_by_email(email.clone()).await.ok().flatten();
+
+        // Signing up through a provider is self-registration too
+        if existing_member.is_none() {
+            self.ensure_email_domain_allowed(&email).await?;
+        }
+
+        let member = match existing_member {
+            Some(member) if identity.email_verified => self.sync_identity_photo(member, identity.photo_url.clone()).await?,
+            Some(_member) => return Err(PlexoAppError::IdentityNotLinked),
+            None if identity.provider == GITHUB_PROVIDER => {
+                self.engine
+                    .create_member_from_github(
+                        CreateMemberFromGithubInputBuilder::default()
+                            .email(email)
+                            .name(name)
+                            .github_id(identity.subject.clone())
+                            .photo_url(identity.photo_url.clone())
+                            .build()
+                            .unwrap(),
+                    )
+                    .await?
+            }
+            None => {
+                let mut member = self
+                    .engine
+                    .create_member_from_email(
+                        CreateMemberFromEmailInputBuilder::default()
+                            .email(email)
+                            .name(name)
+                            .password_hash("".to_string())
+                            .build()
+                            .unwrap(),
+                    )
+                    .await?;
+
+                if let Some(photo_url) = identity.photo_url.clone() {
+                    member = self
+                        .engine
+                        .update_member(
+                            member.id,
+                            UpdateMemberInputBuilder::default().photo_url(photo_url).build().unwrap(),
+                        )
+                        .await?;
+                }
+
+                member
+            }
+        };
+
+        self.link_identity(member.id, identity).await?;
+
+        Ok(member)
+    }
+
+    /// Connects an identity to the member. A member has at most one identity per provider,
+    /// so linking a new account replaces the previous one.
+    pub async fn link_identity(&self, member_id: Uuid, identity: ExternalIdentity) -> Result<MemberIdentity, PlexoAppError> {
+        if let Some(linked_member_id) = self.get_identity_member_id(&identity.provider, &identity.subject).await? {
+            if linked_member_id != member_id {
+                return Err(PlexoAppError::IdentityAlreadyLinked);
+            }
+        }
+
+        let mut tx = self.engine.db_pool.begin().await?;
+
+        let member_identity = sqlx::query_as::<_, MemberIdentity>(
+            r#"
+            INSERT INTO member_identities (member_id, provider, subject, email)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (member_id, provider) DO UPDATE SET subject = EXCLUDED.subject, email = EXCLUDED.email
+            RETURNING *
+            "#,
+        )
+        .bind(member_id)
+        .bind(identity.provider.clone())
+        .bind(identity.subject.clone())
+        .bind(identity.email)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if identity.provider == GITHUB_PROVIDER {
+            sqlx::query("UPDATE members SET github_id = $1 WHERE id = $2")
+                .bind(identity.subject)
+                .bind(member_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(member_identity)
+    }
+
+    /// Disconnects an identity, as long as the member can still sign in with a password or
+    /// another identity.
+    pub async fn unlink_identity(&self, member_id: Uuid, id: Uuid) -> Result<MemberIdentity, PlexoAppError> {
+        let member = self.engine.get_member(member_id).await?;
+        let identities = self.get_identities(member_id).await?;
+
+        let Some(identity) = identities.iter().find(|identity| identity.id == id).cloned() else {
+            return Err(PlexoAppError::IdentityNotFound);
+        };
+
+        let has_password = member.password_hash.is_some_and(|password_hash| !password_hash.is_empty());
+
+        if !has_password && identities.len() <= 1 {
+            return Err(PlexoAppError::LastLoginMethod);
+        }
+
+        let mut tx = self.engine.db_pool.begin().await?;
+
+        sqlx::query("DELETE FROM member_identities WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        if identity.provider == GITHUB_PROVIDER {
+            sqlx::query("UPDATE members SET github_id = NULL WHERE id = $1")
+                .bind(member_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(identity)
+    }
+
+    /// Keeps the member's photo in step with the provider's avatar.
+    async fn sync_identity_photo(&self, member: Member, photo_url: Option<String>) -> Result<Member, PlexoAppError> {
+        let Some(photo_url) = photo_url.filter(|photo_url| member.photo_url.as_ref() != Some(photo_url)) else {
+            return Ok(member);
+        };
+
+        let member = self
+            .engine
+            .update_member(
+                member.id,
+                UpdateMemberInputBuilder::default().photo_url(photo_url).build().unwrap(),
+            )
+            .await?;
+
+        Ok(member)
+    }
+}
@@ -0,0 +1,364 @@
+use std::str::FromStr;
+
+use askama::Template;
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Duration, Utc};
+use plexo_sdk::resources::members::{
+    extensions::{CreateMemberFromEmailInputBuilder, MembersExtensionOperations},
+    member::{Member, MemberRole},
+    operations::{MemberCrudOperations, UpdateMemberInputBuilder},
+};
+use sqlx::{FromRow, Postgres, Transaction};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        app::Core,
+        config::{INVITATION_TTL_DAYS, INVITATION_URL, ORGANIZATION_NAME},
+        email::InvitationTemplate,
+    },
+    errors::app::PlexoAppError,
+};
+
+use super::{
+    commons::{generate_link_token, hash_link_token, validate_email},
+    identities::{ExternalIdentity, GITHUB_PROVIDER},
+};
+
+const INVITATION_TOKEN_LENGTH: usize = 48;
+
+#[derive(Debug, SimpleObject, Clone)]
+pub struct Invitation {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+
+    pub email: String,
+    pub role: MemberRole,
+    /// Projects the member joins once the invitation is accepted.
+    pub project_ids: Vec<Uuid>,
+    pub invited_by_id: Uuid,
+
+    pub expires_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub member_id: Option<Uuid>,
+}
+
+#[derive(FromRow)]
+struct InvitationRow {
+    id: Uuid,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    email: String,
+    role: String,
+    project_ids: Vec<Uuid>,
+    invited_by_id: Uuid,
+    expires_at: DateTime<Utc>,
+    accepted_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+    member_id: Option<Uuid>,
+}
+
+impl From<InvitationRow> for Invitation {
+    fn from(row: InvitationRow) -> Self {
+        Invitation {
+            id: row.id,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            email: row.email,
+            role: MemberRole::from_str(&row.role).unwrap_or_default(),
+            project_ids: row.project_ids,
+            invited_by_id: row.invited_by_id,
+            expires_at: row.expires_at,
+            accepted_at: row.accepted_at,
+            revoked_at: row.revoked_at,
+            member_id: row.member_id,
+        }
+    }
+}
+
+/// How the invitee signs in once their account exists.
+pub enum InvitationCredentials {
+    Password(String),
+    Identity(ExternalIdentity),
+}
+
+fn invitation_expiration() -> DateTime<Utc> {
+    Utc::now() + Duration::try_days(*INVITATION_TTL_DAYS).unwrap()
+}
+
+impl Core {
+    /// Stores a pending invitation and emails its link. Pending invitations to the same
+    /// email are revoked, so only the latest link works.
+    pub async fn invite_member(
+        &self,
+        invited_by_id: Uuid,
+        email: String,
+        role: MemberRole,
+        project_ids: Vec<Uuid>,
+    ) -> Result<Invitation, PlexoAppError> {
+        validate_email(&email)?;
+
+        if let Ok(Some(_member)) = self.engine.get_member_by_email(email.clone()).await {
+            return Err(PlexoAppError::EmailAlreadyExists);
+        }
+
+        let token = generate_link_token(INVITATION_TOKEN_LENGTH);
+
+        let mut tx = self.engine.db_pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE invitations
+            SET revoked_at = now()
+            WHERE email = $1 AND accepted_at IS NULL AND revoked_at IS NULL
+            "#,
+        )
+        .bind(email.clone())
+        .execute(&mut *tx)
+        .await?;
+
+        let invitation: Invitation = sqlx::query_as::<_, InvitationRow>(
+            r#"
+            INSERT INTO invitations (email, role, project_ids, invited_by_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+        )
+        .bind(email)
+        .bind(role.to_string())
+        .bind(project_ids)
+        .bind(invited_by_id)
+        .bind(hash_link_token(&token))
+        .bind(invitation_expiration())
+        .fetch_one(&mut *tx)
+        .await?
+        .into();
+
+        tx.commit().await?;
+
+        self.send_invitation_email(&invitation, &token).await?;
+
+        Ok(invitation)
+    }
+
+    pub async fn get_pending_invitations(&self) -> Result<Vec<Invitation>, PlexoAppError> {
+        let invitations = sqlx::query_as::<_, InvitationRow>(
+            r#"
+            SELECT * FROM invitations
+            WHERE accepted_at IS NULL AND revoked_at IS NULL
+            ORDER BY created_at DESC
+            "#,
+        )
+        .fetch_all(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(invitations.into_iter().map(|row| row.into()).collect())
+    }
+
+    /// Emails a fresh link for a pending invitation, which invalidates the previous one and
+    /// restarts its expiration.
+    pub async fn resend_invitation(&self, id: Uuid) -> Result<Invitation, PlexoAppError> {
+        let token = generate_link_token(INVITATION_TOKEN_LENGTH);
+
+        let invitation: Option<Invitation> = sqlx::query_as::<_, InvitationRow>(
+            r#"
+            UPDATE invitations
+            SET token_hash = $1, expires_at = $2
+            WHERE id = $3 AND accepted_at IS NULL AND revoked_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(hash_link_token(&token))
+        .bind(invitation_expiration())
+        .bind(id)
+        .fetch_optional(self.engine.db_pool.as_ref())
+        .await?
+        .map(|row| row.into());
+
+        let Some(invitation) = invitation else {
+            return Err(PlexoAppError::InvalidInvitation);
+        };
+
+        self.send_invitation_email(&invitation, &token).await?;
+
+        Ok(invitation)
+    }
+
+    pub async fn revoke_invitation(&self, id: Uuid) -> Result<Invitation, PlexoAppError> {
+        let invitation = sqlx::query_as::<_, InvitationRow>(
+            r#"
+            UPDATE invitations
+            SET revoked_at = COALESCE(revoked_at, now())
+            WHERE id = $1 AND accepted_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(self.engine.db_pool.as_ref())
+        .await?;
+
+        let Some(invitation) = invitation else {
+            return Err(PlexoAppError::InvalidInvitation);
+        };
+
+        Ok(invitation.into())
+    }
+
+    /// Consumes the invitation and creates the invited member with its role, adding them to
+    /// the invited projects. Nothing is consumed, and no member is left behind, if any step fails.
+    pub async fn accept_invitation(
+        &self,
+        token: &str,
+        name: String,
+        credentials: InvitationCredentials,
+    ) -> Result<Member, PlexoAppError> {
+        let mut tx = self.engine.db_pool.begin().await?;
+
+        let invitation: Option<Invitation> = sqlx::query_as::<_, InvitationRow>(
+            r#"
+            UPDATE invitations
+            SET accepted_at = now()
+            WHERE token_hash = $1 AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > now()
+            RETURNING *
+            "#,
+        )
+        .bind(hash_link_token(token))
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| row.into());
+
+        let Some(invitation) = invitation else {
+            return Err(PlexoAppError::InvalidInvitation);
+        };
+
+        if let Ok(Some(_member)) = self.engine.get_member_by_email(invitation.email.clone()).await {
+            return Err(PlexoAppError::EmailAlreadyExists);
+        }
+
+        // Members from external providers have no password; an empty hash never validates.
+        let password_hash = match &credentials {
+            InvitationCredentials::Password(password) => self.auth.hash_password(password),
+            InvitationCredentials::Identity(_) => "".to_string(),
+        };
+
+        let member = self
+            .engine
+            .create_member_from_email(
+                CreateMemberFromEmailInputBuilder::default()
+                    .email(invitation.email.clone())
+                    .name(name)
+                    .password_hash(password_hash)
+                    .role(invitation.role)
+                    .build()
+                    .unwrap(),
+            )
+            .await?;
+
+        let member_id = member.id;
+
+        match self.complete_invitation(tx, &invitation, member, credentials).await {
+            Ok(member) => Ok(member),
+            Err(err) => {
+                // The invitation is rolled back, so a member left behind would keep it from
+                // ever being accepted.
+                if let Err(delete_err) = self.engine.delete_member(member_id).await {
+                    warn!("invited member {} not cleaned up: {:?}", member_id, delete_err);
+                }
+
+                Err(err)
+            }
+        }
+    }
+
+    /// Links the invited member's identity and projects, and commits the accepted invitation.
+    async fn complete_invitation(
+        &self,
+        mut tx: Transaction<'static, Postgres>,
+        invitation: &Invitation,
+        mut member: Member,
+        credentials: InvitationCredentials,
+    ) -> Result<Member, PlexoAppError> {
+        if let InvitationCredentials::Identity(identity) = credentials {
+            if let Some(photo_url) = identity.photo_url {
+                member = self
+                    .engine
+                    .update_member(
+                        member.id,
+                        UpdateMemberInputBuilder::default().photo_url(photo_url).build().unwrap(),
+                    )
+                    .await?;
+            }
+
+            sqlx::query(
+                r#"
+                INSERT INTO member_identities (member_id, provider, subject, email)
+                VALUES ($1, $2, $3, $4)
+                "#,
+            )
+            .bind(member.id)
+            .bind(identity.provider.clone())
+            .bind(identity.subject.clone())
+            .bind(identity.email)
+            .execute(&mut *tx)
+            .await?;
+
+            if identity.provider == GITHUB_PROVIDER {
+                sqlx::query("UPDATE members SET github_id = $1 WHERE id = $2")
+                    .bind(identity.subject.clone())
+                    .bind(member.id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                member.github_id = Some(identity.subject);
+            }
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO members_by_projects (member_id, project_id)
+            SELECT $1, id FROM projects WHERE id = ANY($2)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(member.id)
+        .bind(&invitation.project_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE invitations SET member_id = $1 WHERE id = $2")
+            .bind(member.id)
+            .bind(invitation.id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(member)
+    }
+
+    async fn send_invitation_email(&self, invitation: &Invitation, token: &str) -> Result<(), PlexoAppError> {
+        let inviter = self.engine.get_member(invitation.invited_by_id).await?;
+
+        let html = InvitationTemplate {
+            inviter_name: inviter.name,
+            organization_name: (*ORGANIZATION_NAME).to_owned(),
+            invitation_url: format!("{}?token={}", *INVITATION_URL, token),
+            expires_in_days: *INVITATION_TTL_DAYS,
+        }
+        .render()?;
+
+        let core = self.clone();
+        let email = invitation.email.clone();
+
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = core.send_email("no-reply@plexo.app", &email, "You have been invited to Plexo", &html) {
+                warn!("invitation email not sent: {:?}", err);
+            }
+        });
+
+        Ok(())
+    }
+}
@@ -1,17 +1,37 @@
-use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    errors::{Error as JwtError, ErrorKind, Result as JwtResult},
+    jwk::JwkSet,
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
+};
 use plexo_sdk::resources::members::member::Member;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::errors::app::PlexoAppError;
+use crate::{
+    core::config::{JWT_ACCESS_TOKEN_TTL_MINUTES, JWT_REFRESH_TOKEN_TTL_DAYS, TWO_FACTOR_CHALLENGE_TTL_MINUTES},
+    errors::app::PlexoAppError,
+};
+
+use super::signing_keys::JwtSigningKey;
 
 // use crate::sdk::member::Member;
 
+/// Long enough to sign in at the provider, short enough that a leaked state is useless.
+const OAUTH_STATE_TTL_MINUTES: i64 = 10;
+/// Only needs to survive the redirect from the profile to the provider.
+const IDENTITY_LINK_TTL_MINUTES: i64 = 5;
+
+/// Signs with the first of `signing_keys` when there are any, and otherwise with HS256 and
+/// the secret of each token kind.
 #[derive(Default, Clone)]
 pub struct JWTEngine {
     access_token_secret: String,
-    // refresh_token_secret: String,
+    refresh_token_secret: String,
+    signing_keys: Arc<Vec<JwtSigningKey>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,93 +40,399 @@ pub struct PlexoAuthTokenClaims {
     aud: String,
     sub: String,
     exp: usize,
+    jti: String,
 }
 
 impl PlexoAuthTokenClaims {
-    pub fn member_id(&self) -> Uuid {
-        Uuid::parse_str(&self.sub).unwrap()
+    pub fn member_id(&self) -> Result<Uuid, PlexoAppError> {
+        Uuid::parse_str(&self.sub).map_err(|_| PlexoAppError::MalformedAuthorizationToken)
+    }
+
+    pub fn session_id(&self) -> Result<Uuid, PlexoAppError> {
+        Uuid::parse_str(&self.jti).map_err(|_| PlexoAppError::InvalidAuthorizationToken)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlexoRefreshTokenClaims {
+    iss: String,
+    aud: String,
+    sub: String,
+    exp: usize,
+    jti: String,
+}
+
+impl PlexoRefreshTokenClaims {
+    pub fn token_id(&self) -> Result<Uuid, PlexoAppError> {
+        Uuid::parse_str(&self.jti).map_err(|_| PlexoAppError::InvalidAuthorizationToken)
+    }
+}
+
+/// Proves the password step of a two-factor login, exchanged for a session with a code.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlexoChallengeTokenClaims {
+    iss: String,
+    aud: String,
+    sub: String,
+    exp: usize,
+}
+
+impl PlexoChallengeTokenClaims {
+    pub fn member_id(&self) -> Result<Uuid, PlexoAppError> {
+        Uuid::parse_str(&self.sub).map_err(|_| PlexoAppError::InvalidAuthorizationToken)
+    }
+}
+
+/// Lets a browser redirect start linking an identity to the member who requested it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlexoIdentityLinkTokenClaims {
+    iss: String,
+    aud: String,
+    sub: String,
+    exp: usize,
+}
+
+impl PlexoIdentityLinkTokenClaims {
+    pub fn member_id(&self) -> Result<Uuid, PlexoAppError> {
+        Uuid::parse_str(&self.sub).map_err(|_| PlexoAppError::InvalidAuthorizationToken)
+    }
+}
+
+/// What an OAuth sign-in carries from the redirect to the callback. The PKCE verifier is a
+/// secret, so it only travels signed in an HTTP-only cookie.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthState {
+    /// `github`, or the name of an OIDC provider.
+    pub provider: String,
+    pub state: String,
+    pub pkce_verifier: String,
+    /// Only used by OIDC providers, which echo it back in the ID token.
+    pub nonce: Option<String>,
+    pub invitation: Option<String>,
+    /// Already validated when the sign-in started.
+    pub redirect_to: Option<String>,
+    /// Set when the identity is being linked to a signed-in member instead of signing in.
+    #[serde(default)]
+    pub link_member_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PlexoOAuthStateClaims {
+    iss: String,
+    aud: String,
+    exp: usize,
+    #[serde(flatten)]
+    oauth_state: OAuthState,
+}
+
+/// Tells clients why a session token was rejected, so they know whether refreshing it helps.
+fn session_token_error(err: JwtError) -> PlexoAppError {
+    match err.kind() {
+        ErrorKind::ExpiredSignature => PlexoAppError::ExpiredAuthorizationToken,
+        ErrorKind::InvalidSignature | ErrorKind::InvalidAlgorithm => PlexoAppError::InvalidAuthorizationTokenSignature,
+        ErrorKind::InvalidAudience | ErrorKind::InvalidIssuer => PlexoAppError::InvalidAuthorizationTokenAudience,
+        _ => PlexoAppError::MalformedAuthorizationToken,
     }
 }
 
 impl JWTEngine {
-    pub fn new(access_token_secret: String, _refresh_token_secret: String) -> Self {
+    pub fn new(access_token_secret: String, refresh_token_secret: String, signing_keys: Vec<JwtSigningKey>) -> Self {
         Self {
             access_token_secret,
-            // refresh_token_secret,
+            refresh_token_secret,
+            signing_keys: Arc::new(signing_keys),
         }
     }
 
-    pub fn create_session_token(&self, member: &Member) -> Result<String, PlexoAppError> {
+    /// Public keys for other services to verify tokens with. Empty while signing with secrets.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.signing_keys.iter().map(|signing_key| signing_key.jwk()).collect(),
+        }
+    }
+
+    fn encode_token<T: Serialize>(&self, claims: &T, secret: &str) -> Result<String, PlexoAppError> {
+        let Some(signing_key) = self.signing_keys.first() else {
+            return Ok(encode(
+                &Header::default(),
+                claims,
+                &EncodingKey::from_secret(secret.as_ref()),
+            )?);
+        };
+
+        let Some(encoding_key) = signing_key.encoding_key() else {
+            return Err(PlexoAppError::InvalidConfiguration(format!(
+                "JWT key {} has no private key",
+                signing_key.kid
+            )));
+        };
+
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(signing_key.kid.clone());
+
+        Ok(encode(&header, claims, encoding_key)?)
+    }
+
+    /// Picks the verification key from the `kid` header, so tokens signed before a rotation
+    /// stay valid while their key is still listed.
+    fn decode_token<T: DeserializeOwned>(&self, token: &str, secret: &str, audience: &str) -> JwtResult<TokenData<T>> {
+        if self.signing_keys.is_empty() {
+            let mut validator = Validation::default();
+
+            validator.set_audience(&[audience]);
+
+            return decode::<T>(token, &DecodingKey::from_secret(secret.as_ref()), &validator);
+        }
+
+        let header = decode_header(token)?;
+
+        let Some(kid) = header.kid else {
+            return Err(ErrorKind::InvalidToken.into());
+        };
+
+        // A key that isn't listed anymore can't vouch for the token.
+        let Some(signing_key) = self.signing_keys.iter().find(|signing_key| signing_key.kid == kid) else {
+            return Err(ErrorKind::InvalidSignature.into());
+        };
+
+        let mut validator = Validation::new(Algorithm::EdDSA);
+
+        validator.set_audience(&[audience]);
+
+        decode::<T>(token, signing_key.decoding_key(), &validator)
+    }
+
+    pub fn access_token_expiration(&self) -> DateTime<Utc> {
+        Utc::now() + Duration::try_minutes(*JWT_ACCESS_TOKEN_TTL_MINUTES).unwrap_or(Duration::zero())
+    }
+
+    pub fn refresh_token_expiration(&self) -> DateTime<Utc> {
+        Utc::now() + Duration::try_days(*JWT_REFRESH_TOKEN_TTL_DAYS).unwrap_or(Duration::zero())
+    }
+
+    pub fn challenge_token_expiration(&self) -> DateTime<Utc> {
+        Utc::now() + Duration::try_minutes(*TWO_FACTOR_CHALLENGE_TTL_MINUTES).unwrap_or(Duration::zero())
+    }
+
+    pub fn oauth_state_expiration(&self) -> DateTime<Utc> {
+        Utc::now() + Duration::try_minutes(OAUTH_STATE_TTL_MINUTES).unwrap_or(Duration::zero())
+    }
+
+    pub fn create_session_token(&self, member: &Member, session_id: Uuid) -> Result<String, PlexoAppError> {
         let claims = PlexoAuthTokenClaims {
             iss: "Plexo".to_string(),
             aud: "session.plexo.app".to_string(),
             sub: member.id.to_string(),
-            exp: (Utc::now() + chrono::Duration::try_days(7).unwrap()).timestamp() as usize,
+            exp: self.access_token_expiration().timestamp() as usize,
+            jti: session_id.to_string(),
         };
 
-        let token = encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(self.access_token_secret.as_ref()),
-        )?;
-
-        Ok(token)
+        self.encode_token(&claims, &self.access_token_secret)
     }
 
     pub fn decode_session_token(&self, token: &str) -> Result<PlexoAuthTokenClaims, PlexoAppError> {
-        let key = self.access_token_secret.as_ref();
+        let token_data = self
+            .decode_token::<PlexoAuthTokenClaims>(token, &self.access_token_secret, "session.plexo.app")
+            .map_err(session_token_error)?;
+
+        Ok(token_data.claims)
+    }
 
-        // println!("key: {:?}", key);
+    pub fn create_refresh_token(
+        &self,
+        member_id: Uuid,
+        token_id: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<String, PlexoAppError> {
+        let claims = PlexoRefreshTokenClaims {
+            iss: "Plexo".to_string(),
+            aud: "refresh.plexo.app".to_string(),
+            sub: member_id.to_string(),
+            exp: expires_at.timestamp() as usize,
+            jti: token_id.to_string(),
+        };
 
-        let mut validator = Validation::default();
+        self.encode_token(&claims, &self.refresh_token_secret)
+    }
 
-        validator.set_audience(&["session.plexo.app"]);
+    pub fn decode_refresh_token(&self, token: &str) -> Result<PlexoRefreshTokenClaims, PlexoAppError> {
+        let token_data = self
+            .decode_token::<PlexoRefreshTokenClaims>(token, &self.refresh_token_secret, "refresh.plexo.app")
+            .map_err(|_| PlexoAppError::InvalidAuthorizationToken)?;
 
-        let token_data = decode::<PlexoAuthTokenClaims>(token, &DecodingKey::from_secret(key), &validator);
+        Ok(token_data.claims)
+    }
 
-        // println!("token_data: {:?}", token_data);
+    pub fn create_challenge_token(&self, member_id: Uuid) -> Result<String, PlexoAppError> {
+        let claims = PlexoChallengeTokenClaims {
+            iss: "Plexo".to_string(),
+            aud: "challenge.plexo.app".to_string(),
+            sub: member_id.to_string(),
+            exp: self.challenge_token_expiration().timestamp() as usize,
+        };
 
-        let token_data = token_data.unwrap();
+        self.encode_token(&claims, &self.access_token_secret)
+    }
+
+    pub fn decode_challenge_token(&self, token: &str) -> Result<PlexoChallengeTokenClaims, PlexoAppError> {
+        let token_data = self
+            .decode_token::<PlexoChallengeTokenClaims>(token, &self.access_token_secret, "challenge.plexo.app")
+            .map_err(|_| PlexoAppError::InvalidAuthorizationToken)?;
 
         Ok(token_data.claims)
     }
 
-    // pub fn decode_access_token(&self, token: &str) -> Result<PlexoAuthTokenClaims, Error> {
-    //     let token_data = decode::<PlexoAuthTokenClaims>(
-    //         token,
-    //         &DecodingKey::from_secret(self.access_token_secret.as_ref()),
-    //         &jsonwebtoken::Validation::default(),
-    //     )?;
-
-    //     Ok(token_data.claims)
-    // }
-
-    // pub fn decode_refresh_token(&self, token: &str) -> Result<PlexoAuthTokenClaims, Error> {
-    //     let token_data = decode::<PlexoAuthTokenClaims>(
-    //         token,
-    //         &DecodingKey::from_secret(self.refresh_token_secret.as_ref()),
-    //         &jsonwebtoken::Validation::default(),
-    //     )?;
-
-    //     Ok(token_data.claims)
-    // }
-
-    // pub fn refresh_access_token(
-    //     &self,
-    //     access_token: &str,
-    //     refresh_token: &str,
-    // ) -> Result<String, jsonwebtoken::errors::Error> {
-    //     let mut claims_access_token = self.decode_access_token(access_token)?;
-    //     let _claims_refresh_token = self.decode_refresh_token(refresh_token)?;
-
-    //     claims_access_token.exp += 1000; // TODO
-
-    //     let token = encode(
-    //         &Header::default(),
-    //         &claims_access_token,
-    //         &EncodingKey::from_secret(self.access_token_secret.as_ref()),
-    //     )?;
-
-    //     Ok(token)
-    // }
+    pub fn create_identity_link_token(&self, member_id: Uuid) -> Result<String, PlexoAppError> {
+        let claims = PlexoIdentityLinkTokenClaims {
+            iss: "Plexo".to_string(),
+            aud: "link.plexo.app".to_string(),
+            sub: member_id.to_string(),
+            exp: (Utc::now() + Duration::try_minutes(IDENTITY_LINK_TTL_MINUTES).unwrap_or(Duration::zero())).timestamp() as usize,
+        };
+
+        self.encode_token(&claims, &self.access_token_secret)
+    }
+
+    pub fn decode_identity_link_token(&self, token: &str) -> Result<PlexoIdentityLinkTokenClaims, PlexoAppError> {
+        let token_data = self
+            .decode_token::<PlexoIdentityLinkTokenClaims>(token, &self.access_token_secret, "link.plexo.app")
+            .map_err(|_| PlexoAppError::InvalidAuthorizationToken)?;
+
+        Ok(token_data.claims)
+    }
+
+    pub fn create_oauth_state_token(&self, oauth_state: OAuthState) -> Result<String, PlexoAppError> {
+        let claims = PlexoOAuthStateClaims {
+            iss: "Plexo".to_string(),
+            aud: "oauth.plexo.app".to_string(),
+            exp: self.oauth_state_expiration().timestamp() as usize,
+            oauth_state,
+        };
+
+        self.encode_token(&claims, &self.access_token_secret)
+    }
+
+    pub fn decode_oauth_state_token(&self, token: &str) -> Result<OAuthState, PlexoAppError> {
+        let token_data = self
+            .decode_token::<PlexoOAuthStateClaims>(token, &self.access_token_secret, "oauth.plexo.app")
+            .map_err(|_| PlexoAppError::InvalidOAuthState)?;
+
+        Ok(token_data.claims.oauth_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::{rand::SystemRandom, signature::Ed25519KeyPair};
+
+    use super::*;
+
+    fn session_claims(aud: &str, expires_at: DateTime<Utc>) -> PlexoAuthTokenClaims {
+        PlexoAuthTokenClaims {
+            iss: "Plexo".to_string(),
+            aud: aud.to_string(),
+            sub: Uuid::new_v4().to_string(),
+            exp: expires_at.timestamp() as usize,
+            jti: Uuid::new_v4().to_string(),
+        }
+    }
+
+    fn valid_session_claims() -> PlexoAuthTokenClaims {
+        session_claims("session.plexo.app", Utc::now() + Duration::try_minutes(5).unwrap())
+    }
+
+    fn secret_engine(secret: &str) -> JWTEngine {
+        JWTEngine::new(secret.to_string(), secret.to_string(), vec![])
+    }
+
+    fn key_engine(kid: &str) -> JWTEngine {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pem = pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref().to_vec()));
+
+        JWTEngine::new(
+            "unused".to_string(),
+            "unused".to_string(),
+            vec![JwtSigningKey::from_private_pem(kid, &pem).unwrap()],
+        )
+    }
+
+    fn assert_rejected(result: Result<PlexoAuthTokenClaims, PlexoAppError>, expected: PlexoAppError, code: &str) {
+        let err = result.unwrap_err();
+
+        assert_eq!(std::mem::discriminant(&err), std::mem::discriminant(&expected), "{err:?}");
+        assert_eq!(err.code(), code);
+    }
+
+    #[test]
+    fn accepts_valid_session_token() {
+        let engine = secret_engine("secret");
+        let claims = valid_session_claims();
+        let token = engine.encode_token(&claims, "secret").unwrap();
+
+        assert_eq!(engine.decode_session_token(&token).unwrap().sub, claims.sub);
+    }
+
+    #[test]
+    fn rejects_expired_session_token() {
+        let engine = secret_engine("secret");
+        let claims = session_claims("session.plexo.app", Utc::now() - Duration::try_hours(1).unwrap());
+        let token = engine.encode_token(&claims, "secret").unwrap();
+
+        assert_rejected(
+            engine.decode_session_token(&token),
+            PlexoAppError::ExpiredAuthorizationToken,
+            "EXPIRED_AUTHORIZATION_TOKEN",
+        );
+    }
+
+    #[test]
+    fn rejects_session_token_signed_with_another_secret() {
+        let token = secret_engine("other").encode_token(&valid_session_claims(), "other").unwrap();
+
+        assert_rejected(
+            secret_engine("secret").decode_session_token(&token),
+            PlexoAppError::InvalidAuthorizationTokenSignature,
+            "INVALID_AUTHORIZATION_TOKEN_SIGNATURE",
+        );
+    }
+
+    #[test]
+    fn rejects_session_token_signed_with_another_key() {
+        let token = key_engine("active").encode_token(&valid_session_claims(), "").unwrap();
+
+        assert_rejected(
+            key_engine("active").decode_session_token(&token),
+            PlexoAppError::InvalidAuthorizationTokenSignature,
+            "INVALID_AUTHORIZATION_TOKEN_SIGNATURE",
+        );
+        assert_rejected(
+            key_engine("rotated").decode_session_token(&token),
+            PlexoAppError::InvalidAuthorizationTokenSignature,
+            "INVALID_AUTHORIZATION_TOKEN_SIGNATURE",
+        );
+    }
+
+    #[test]
+    fn rejects_session_token_for_another_audience() {
+        let engine = secret_engine("secret");
+        let claims = session_claims("refresh.plexo.app", Utc::now() + Duration::try_minutes(5).unwrap());
+        let token = engine.encode_token(&claims, "secret").unwrap();
+
+        assert_rejected(
+            engine.decode_session_token(&token),
+            PlexoAppError::InvalidAuthorizationTokenAudience,
+            "INVALID_AUTHORIZATION_TOKEN_AUDIENCE",
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_session_token() {
+        for token in ["", "not-a-token", "a.b.c"] {
+            assert_rejected(
+                secret_engine("secret").decode_session_token(token),
+                PlexoAppError::MalformedAuthorizationToken,
+                "MALFORMED_AUTHORIZATION_TOKEN",
+            );
+        }
+    }
 }
@@ -0,0 +1,291 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Argon2, PasswordHasher,
+};
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use plexo_sdk::resources::members::{extensions::MembersExtensionOperations, member::Member};
+use sqlx::FromRow;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        app::Core,
+        config::{
+            LOGIN_FAILED_ATTEMPTS_WINDOW_MINUTES, LOGIN_LOCKOUT_BASE_SECONDS, LOGIN_LOCKOUT_MAX_MINUTES, LOGIN_MAX_FAILED_ATTEMPTS,
+            LOGIN_MAX_FAILED_ATTEMPTS_PER_IP,
+        },
+    },
+    errors::app::PlexoAppError,
+};
+
+use super::resources::ClientIp;
+
+/// A temporary block on logins, either for an account (`email` is set) or for every
+/// account from an address (`email` is empty).
+#[derive(Debug, SimpleObject, FromRow, Clone)]
+pub struct LoginLockout {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+
+    pub email: Option<String>,
+    pub member_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+    pub failed_attempts: i32,
+
+    pub locked_until: DateTime<Utc>,
+    pub unlocked_at: Option<DateTime<Utc>>,
+    pub unlocked_by_id: Option<Uuid>,
+}
+
+lazy_static! {
+    /// Checked when there's no password to check, so unknown emails take as long to fail as
+    /// wrong passwords.
+    static ref DUMMY_PASSWORD_HASH: String = Argon2::default()
+        .hash_password(b"plexo-dummy-password", &SaltString::generate(&mut OsRng))
+        .unwrap()
+        .to_string();
+}
+
+/// Doubles with every lockout already served, up to `LOGIN_LOCKOUT_MAX_MINUTES`.
+fn lockout_duration(previous_lockouts: i64) -> Duration {
+    let seconds = LOGIN_LOCKOUT_BASE_SECONDS.saturating_mul(1 << previous_lockouts.clamp(0, 20));
+    let max_seconds = LOGIN_LOCKOUT_MAX_MINUTES.saturating_mul(60);
+
+    Duration::try_seconds(seconds.min(max_seconds)).unwrap_or(Duration::zero())
+}
+
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+impl Core {
+    /// Checks the password for an email, throttling failed attempts per account and per
+    /// address. Unknown emails and wrong passwords fail the same way.
+    pub async fn login_with_password(&self, email: &str, password: &str, ip: &ClientIp) -> Result<Member, PlexoAppError> {
+        self.ensure_login_allowed(email, ip).await?;
+
+        let member = self.engine.get_member_by_email(email.to_string()).await.ok().flatten();
+
+        let password_hash = member
+            .as_ref()
+            .and_then(|member| member.password_hash.as_deref())
+            .filter(|password_hash| !password_hash.is_empty());
+
+        let valid_password = match password_hash {
+            Some(password_hash) => self.auth.validate_password(password, password_hash),
+            None => {
+                self.auth.validate_password(password, &DUMMY_PASSWORD_HASH);
+                false
+            }
+        };
+
+        let Some(member) = member.filter(|_| valid_password) else {
+            self.record_failed_login(email, ip).await?;
+            return Err(PlexoAppError::InvalidCredentials);
+        };
+
+        // With two-factor enabled the login only succeeds once the code is verified.
+        if !self.is_totp_enabled(member.id).await? {
+            self.record_successful_login(email, ip).await?;
+        }
+
+        Ok(member)
+    }
+
+    pub async fn ensure_login_allowed(&self, email: &str, ip: &ClientIp) -> Result<(), PlexoAppError> {
+        let locked: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM login_lockouts
+                WHERE unlocked_at IS NULL
+                    AND locked_until > now()
+                    AND (email = $1 OR (email IS NULL AND ip_address = $2))
+            )
+            "#,
+        )
+        .bind(normalize_email(email))
+        .bind(ip.0.clone())
+        .fetch_one(self.engine.db_pool.as_ref())
+        .await?;
+
+        if locked {
+            return Err(PlexoAppError::TooManyLoginAttempts);
+        }
+
+        Ok(())
+    }
+
+    pub async fn record_successful_login(&self, email: &str, ip: &ClientIp) -> Result<(), PlexoAppError> {
+        sqlx::query("INSERT INTO login_attempts (email, ip_address, succeeded) VALUES ($1, $2, true)")
+            .bind(normalize_email(email))
+            .bind(ip.0.clone())
+            .execute(self.engine.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Records the failure and locks the account, or the address, once too many failures
+    /// pile up within the window. A successful login or an admin unlock resets the account
+    /// count.
+    pub async fn record_failed_login(&self, email: &str, ip: &ClientIp) -> Result<(), PlexoAppError> {
+        let email = normalize_email(email);
+        let window_start = Utc::now() - Duration::try_minutes(*LOGIN_FAILED_ATTEMPTS_WINDOW_MINUTES).unwrap_or(Duration::zero());
+
+        sqlx::query("INSERT INTO login_attempts (email, ip_address, succeeded) VALUES ($1, $2, false)")
+            .bind(email.clone())
+            .bind(ip.0.clone())
+            .execute(self.engine.db_pool.as_ref())
+            .await?;
+
+        let (account_failures, account_lockouts): (i64, i64) = sqlx::query_as(
+            r#"
+            WITH reset AS (
+                SELECT GREATEST(
+                    (SELECT max(created_at) FROM login_attempts WHERE email = $1 AND succeeded),
+                    (SELECT max(unlocked_at) FROM login_lockouts WHERE email = $1),
+                    '-infinity'::timestamptz
+                ) AS at
+            )
+            SELECT
+                (SELECT count(*) FROM login_attempts, reset
+                    WHERE email = $1 AND NOT succeeded AND created_at > GREATEST(reset.at, $2)),
+                (SELECT count(*) FROM login_lockouts, reset
+                    WHERE email = $1 AND created_at > GREATEST(reset.at, now() - interval '1 day'))
+            "#,
+        )
+        .bind(email.clone())
+        .bind(window_start)
+        .fetch_one(self.engine.db_pool.as_ref())
+        .await?;
+
+        if account_failures >= *LOGIN_MAX_FAILED_ATTEMPTS {
+            warn!("locking logins for {} after {} failed attempts", email, account_failures);
+
+            let member_id = self
+                .engine
+                .get_member_by_email(email.clone())
+                .await
+                .ok()
+                .flatten()
+                .map(|member| member.id);
+
+            self.create_login_lockout(Some(email), member_id, ip.0.clone(), account_failures, account_lockouts)
+                .await?;
+        }
+
+        let Some(ip_address) = ip.0.clone() else {
+            return Ok(());
+        };
+
+        let (ip_failures, ip_lockouts): (i64, i64) = sqlx::query_as(
+            r#"
+            WITH reset AS (
+                SELECT COALESCE(
+                    (SELECT max(unlocked_at) FROM login_lockouts WHERE email IS NULL AND ip_address = $1),
+                    '-infinity'::timestamptz
+                ) AS at
+            )
+            SELECT
+                (SELECT count(*) FROM login_attempts, reset
+                    WHERE ip_address = $1 AND NOT succeeded AND created_at > GREATEST(reset.at, $2)),
+                (SELECT count(*) FROM login_lockouts, reset
+                    WHERE email IS NULL AND ip_address = $1 AND created_at > GREATEST(reset.at, now() - interval '1 day'))
+            "#,
+        )
+        .bind(ip_address.clone())
+        .bind(window_start)
+        .fetch_one(self.engine.db_pool.as_ref())
+        .await?;
+
+        if ip_failures >= *LOGIN_MAX_FAILED_ATTEMPTS_PER_IP {
+            warn!("locking logins from {} after {} failed attempts", ip_address, ip_failures);
+
+            self.create_login_lockout(None, None, Some(ip_address), ip_failures, ip_lockouts)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn create_login_lockout(
+        &self,
+        email: Option<String>,
+        member_id: Option<Uuid>,
+        ip_address: Option<String>,
+        failed_attempts: i64,
+        previous_lockouts: i64,
+    ) -> Result<(), PlexoAppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO login_lockouts (email, member_id, ip_address, failed_attempts, locked_until)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(email)
+        .bind(member_id)
+        .bind(ip_address)
+        .bind(failed_attempts as i32)
+        .bind(Utc::now() + lockout_duration(previous_lockouts))
+        .execute(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_login_lockouts(&self, active_only: bool) -> Result<Vec<LoginLockout>, PlexoAppError> {
+        let lockouts = sqlx::query_as::<_, LoginLockout>(
+            r#"
+            SELECT * FROM login_lockouts
+            WHERE NOT $1 OR (unlocked_at IS NULL AND locked_until > now())
+            ORDER BY created_at DESC
+            LIMIT 500
+            "#,
+        )
+        .bind(active_only)
+        .fetch_all(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(lockouts)
+    }
+
+    pub async fn unlock_login_lockout(&self, id: Uuid, unlocked_by_id: Uuid) -> Result<LoginLockout, PlexoAppError> {
+        let lockout = sqlx::query_as::<_, LoginLockout>(
+            r#"
+            UPDATE login_lockouts
+            SET unlocked_at = COALESCE(unlocked_at, now()), unlocked_by_id = COALESCE(unlocked_by_id, $2)
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(unlocked_by_id)
+        .fetch_one(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(lockout)
+    }
+
+    /// Lifts every active lockout on the member's account.
+    pub async fn unlock_member_logins(&self, member_id: Uuid, unlocked_by_id: Uuid) -> Result<Vec<LoginLockout>, PlexoAppError> {
+        let lockouts = sqlx::query_as::<_, LoginLockout>(
+            r#"
+            UPDATE login_lockouts
+            SET unlocked_at = now(), unlocked_by_id = $2
+            WHERE unlocked_at IS NULL
+                AND locked_until > now()
+                AND (member_id = $1 OR email = (SELECT lower(email) FROM members WHERE id = $1))
+            RETURNING *
+            "#,
+        )
+        .bind(member_id)
+        .bind(unlocked_by_id)
+        .fetch_all(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(lockouts)
+    }
+}
@@ -1,5 +1,21 @@
+pub mod api_keys;
 pub mod commons;
+pub mod deactivations;
+pub mod email_verifications;
 pub mod engine;
+pub mod github;
 pub mod handlers;
+pub mod identities;
+pub mod invitations;
 pub mod jwt;
+pub mod login_attempts;
+pub mod oidc;
+pub mod password_resets;
+pub mod principal;
+pub mod project_roles;
+pub mod refresh_tokens;
 pub mod resources;
+pub mod service_accounts;
+pub mod sessions;
+pub mod signing_keys;
+pub mod totp;
@@ -0,0 +1,493 @@
+use std::{env::var, sync::Arc};
+
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use oauth2::{PkceCodeChallenge, PkceCodeVerifier};
+use reqwest::Url;
+use serde::Deserialize;
+use tokio::sync::{OnceCell, RwLock};
+use tracing::{info, warn};
+
+use crate::{core::config::DOMAIN, errors::app::PlexoAppError};
+
+use super::{commons::generate_link_token, identities::ExternalIdentity};
+
+const OIDC_STATE_LENGTH: usize = 32;
+const OIDC_NONCE_LENGTH: usize = 32;
+
+/// An OpenID Connect provider, read from `OIDC_{NAME}_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct OidcProviderConfig {
+    pub name: String,
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+    pub scopes: Vec<String>,
+}
+
+impl OidcProviderConfig {
+    pub fn from_env(name: &str) -> Option<Self> {
+        let prefix = format!("OIDC_{}", name.to_uppercase().replace('-', "_"));
+
+        let (Ok(issuer_url), Ok(client_id)) = (var(format!("{prefix}_ISSUER_URL")), var(format!("{prefix}_CLIENT_ID"))) else {
+            warn!(
+                "OIDC provider {} is missing {}_ISSUER_URL or {}_CLIENT_ID",
+                name, prefix, prefix
+            );
+            return None;
+        };
+
+        Some(Self {
+            name: name.to_string(),
+            issuer_url: issuer_url.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret: var(format!("{prefix}_CLIENT_SECRET")).ok(),
+            redirect_url: var(format!("{prefix}_REDIRECT_URL")).unwrap_or(format!("{}/auth/oidc/{}/callback", *DOMAIN, name)),
+            scopes: var(format!("{prefix}_SCOPES"))
+                .unwrap_or("openid email profile".into())
+                .split_whitespace()
+                .map(|scope| scope.to_string())
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OidcMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OidcTokenResponse {
+    id_token: String,
+}
+
+/// The ID token claims mapped onto members.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcIdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub preferred_username: Option<String>,
+    pub picture: Option<String>,
+    nonce: Option<String>,
+}
+
+/// What the callback needs to finish a sign-in. It round-trips through a signed cookie.
+pub struct OidcAuthorization {
+    pub url: Url,
+    pub state: String,
+    pub nonce: String,
+    pub pkce_verifier: String,
+}
+
+#[derive(Clone)]
+pub struct OidcProvider {
+    pub config: OidcProviderConfig,
+    metadata: Arc<OnceCell<OidcMetadata>>,
+    jwks: Arc<RwLock<JwkSet>>,
+}
+
+impl OidcProvider {
+    pub fn new(config: OidcProviderConfig) -> Self {
+        Self {
+            config,
+            metadata: Arc::new(OnceCell::new()),
+            jwks: Arc::new(RwLock::new(JwkSet { keys: vec![] })),
+        }
+    }
+
+    /// Discovery document, fetched once and kept for the life of the process.
+    async fn metadata(&self) -> Result<&OidcMetadata, PlexoAppError> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!("{}/.well-known/openid-configuration", self.config.issuer_url);
+
+                let metadata = reqwest::get(url.as_str())
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))?
+                    .json::<OidcMetadata>()
+                    .await
+                    .map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))?;
+
+                if metadata.issuer.trim_end_matches('/') != self.config.issuer_url {
+                    return Err(PlexoAppError::AuthProviderError(format!(
+                        "discovery issuer {} doesn't match {}",
+                        metadata.issuer, self.config.issuer_url
+                    )));
+                }
+
+                info!("discovered OIDC provider {}", self.config.name);
+
+                Ok(metadata)
+            })
+            .await
+    }
+
+    pub async fn authorize_url(&self) -> Result<OidcAuthorization, PlexoAppError> {
+        let metadata = self.metadata().await?;
+
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let state = generate_link_token(OIDC_STATE_LENGTH);
+        let nonce = generate_link_token(OIDC_NONCE_LENGTH);
+
+        let url = Url::parse_with_params(
+            metadata.authorization_endpoint.as_str(),
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("scope", self.config.scopes.join(" ").as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+                ("code_challenge", pkce_challenge.as_str()),
+                ("code_challenge_method", pkce_challenge.method().as_str()),
+            ],
+        )
+        .map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))?;
+
+        Ok(OidcAuthorization {
+            url,
+            state,
+            nonce,
+            pkce_verifier: pkce_verifier.secret().to_string(),
+        })
+    }
+
+    /// Redeems the authorization code and returns the claims of the validated ID token.
+    pub async fn exchange_code(&self, code: &str, pkce_verifier: String, nonce: &str) -> Result<OidcIdTokenClaims, PlexoAppError> {
+        let metadata = self.metadata().await?;
+        let pkce_verifier = PkceCodeVerifier::new(pkce_verifier);
+
+        let mut request = reqwest::Client::new().post(metadata.token_endpoint.as_str()).form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", pkce_verifier.secret().as_str()),
+        ]);
+
+        if let Some(client_secret) = &self.config.client_secret {
+            request = request.basic_auth(&self.config.client_id, Some(client_secret));
+        }
+
+        let token_response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))?
+            .json::<OidcTokenResponse>()
+            .await
+            .map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))?;
+
+        let claims = self.validate_id_token(metadata, &token_response.id_token).await?;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(PlexoAppError::InvalidOAuthState);
+        }
+
+        Ok(claims)
+    }
+
+    /// The key an ID token was signed with. The provider's JWKS is kept and only fetched again
+    /// when a token names a key that isn't in it, which is how key rotations show up.
+    async fn signing_key(&self, metadata: &OidcMetadata, kid: Option<&str>) -> Result<Jwk, PlexoAppError> {
+        let find = |jwks: &JwkSet| match kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None => jwks.keys.first().cloned(),
+        };
+
+        if let Some(jwk) = find(&*self.jwks.read().await) {
+            return Ok(jwk);
+        }
+
+        let jwks = reqwest::get(metadata.jwks_uri.as_str())
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))?
+            .json::<JwkSet>()
+            .await
+            .map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))?;
+
+        let jwk = find(&jwks);
+
+        *self.jwks.write().await = jwks;
+
+        jwk.ok_or(PlexoAppError::AuthProviderError("ID token signing key not found".to_string()))
+    }
+
+    /// Checks the signature against the provider's JWKS, plus the issuer, audience and
+    /// expiration. Only asymmetric algorithms are accepted.
+    async fn validate_id_token(&self, metadata: &OidcMetadata, id_token: &str) -> Result<OidcIdTokenClaims, PlexoAppError> {
+        let header = decode_header(id_token).map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))?;
+
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(PlexoAppError::AuthProviderError(format!(
+                "unsupported ID token algorithm {:?}",
+                header.alg
+            )));
+        }
+
+        let jwk = self.signing_key(metadata, header.kid.as_deref()).await?;
+
+        let key = DecodingKey::from_jwk(&jwk).map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[self.config.client_id.as_str()]);
+        validation.set_issuer(&[metadata.issuer.as_str()]);
+
+        let token_data = decode::<OidcIdTokenClaims>(id_token, &key, &validation)
+            .map_err(|err| PlexoAppError::AuthProviderError(err.to_string()))?;
+
+        Ok(token_data.claims)
+    }
+}
+
+impl OidcIdTokenClaims {
+    pub fn into_identity(self, provider: &str) -> ExternalIdentity {
+        ExternalIdentity {
+            provider: provider.to_string(),
+            subject: self.sub,
+            email: self.email,
+            email_verified: self.email_verified,
+            name: self.name.or(self.preferred_username),
+            photo_url: self.picture,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    use chrono::{Duration, Utc};
+    use jsonwebtoken::{encode, Header};
+    use ring::{rand::SystemRandom, signature::Ed25519KeyPair};
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use crate::auth::signing_keys::JwtSigningKey;
+
+    use super::*;
+
+    const CLIENT_ID: &str = "plexo";
+    const NONCE: &str = "nonce";
+
+    /// A local OIDC provider serving discovery, its JWKS and an ID token from the token
+    /// endpoint.
+    struct StubProvider {
+        issuer: String,
+        keys: Mutex<Vec<JwtSigningKey>>,
+        id_token: Mutex<String>,
+        jwks_requests: AtomicUsize,
+    }
+
+    impl StubProvider {
+        async fn start() -> Arc<Self> {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+            let stub = Arc::new(Self {
+                issuer: format!("http://{}", listener.local_addr().unwrap()),
+                keys: Mutex::new(vec![signing_key("first")]),
+                id_token: Mutex::new(String::new()),
+                jwks_requests: AtomicUsize::new(0),
+            });
+
+            let server = stub.clone();
+
+            tokio::spawn(async move {
+                while let Ok((stream, _addr)) = listener.accept().await {
+                    tokio::spawn(server.clone().respond(stream));
+                }
+            });
+
+            stub
+        }
+
+        async fn respond(self: Arc<Self>, mut stream: TcpStream) {
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+
+            // The headers, plus the form body the token endpoint is called with
+            while let Ok(read) = stream.read(&mut buffer).await {
+                request.extend_from_slice(&buffer[..read]);
+
+                let text = String::from_utf8_lossy(&request);
+
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let content_length = head
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().to_string())
+                        })
+                        .and_then(|length| length.parse::<usize>().ok())
+                        .unwrap_or(0);
+
+                    if body.len() >= content_length {
+                        break;
+                    }
+                }
+
+                if read == 0 {
+                    break;
+                }
+            }
+
+            let request = String::from_utf8_lossy(&request);
+            let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+            let body = match path {
+                "/.well-known/openid-configuration" => json!({
+                    "issuer": self.issuer,
+                    "authorization_endpoint": format!("{}/authorize", self.issuer),
+                    "token_endpoint": format!("{}/token", self.issuer),
+                    "jwks_uri": format!("{}/jwks", self.issuer),
+                }),
+                "/jwks" => {
+                    self.jwks_requests.fetch_add(1, Ordering::SeqCst);
+
+                    json!({ "keys": self.keys.lock().unwrap().iter().map(|key| key.jwk()).collect::<Vec<_>>() })
+                }
+                "/token" => json!({ "id_token": *self.id_token.lock().unwrap() }),
+                _ => json!({}),
+            }
+            .to_string();
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+
+        fn provider(&self) -> OidcProvider {
+            OidcProvider::new(OidcProviderConfig {
+                name: "stub".to_string(),
+                issuer_url: self.issuer.clone(),
+                client_id: CLIENT_ID.to_string(),
+                client_secret: None,
+                redirect_url: "http://localhost/auth/oidc/stub/callback".to_string(),
+                scopes: vec!["openid".to_string()],
+            })
+        }
+
+        /// Signs the next ID token with the provider's latest key.
+        fn issue(&self, aud: &str, nonce: &str, expires_in: Duration) {
+            let claims = json!({
+                "iss": self.issuer,
+                "aud": aud,
+                "sub": "subject",
+                "exp": (Utc::now() + expires_in).timestamp(),
+                "nonce": nonce,
+                "email": "member@plexo.app",
+                "email_verified": true,
+            });
+
+            let keys = self.keys.lock().unwrap();
+            let key = keys.last().unwrap();
+
+            let mut header = Header::new(Algorithm::EdDSA);
+            header.kid = Some(key.kid.clone());
+
+            *self.id_token.lock().unwrap() = encode(&header, &claims, key.encoding_key().unwrap()).unwrap();
+        }
+
+        fn rotate(&self, kid: &str) {
+            self.keys.lock().unwrap().push(signing_key(kid));
+        }
+    }
+
+    fn signing_key(kid: &str) -> JwtSigningKey {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+
+        JwtSigningKey::from_private_pem(kid, &pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref().to_vec()))).unwrap()
+    }
+
+    fn valid_for() -> Duration {
+        Duration::try_minutes(5).unwrap()
+    }
+
+    #[tokio::test]
+    async fn accepts_valid_id_token() {
+        let stub = StubProvider::start().await;
+        stub.issue(CLIENT_ID, NONCE, valid_for());
+
+        let claims = stub
+            .provider()
+            .exchange_code("code", "verifier".to_string(), NONCE)
+            .await
+            .unwrap();
+
+        assert_eq!(claims.sub, "subject");
+        assert_eq!(claims.email.as_deref(), Some("member@plexo.app"));
+        assert!(claims.email_verified);
+    }
+
+    #[tokio::test]
+    async fn rejects_nonce_mismatch() {
+        let stub = StubProvider::start().await;
+        stub.issue(CLIENT_ID, "other", valid_for());
+
+        let result = stub.provider().exchange_code("code", "verifier".to_string(), NONCE).await;
+
+        assert!(matches!(result, Err(PlexoAppError::InvalidOAuthState)));
+    }
+
+    #[tokio::test]
+    async fn rejects_id_token_for_another_client() {
+        let stub = StubProvider::start().await;
+        stub.issue("other", NONCE, valid_for());
+
+        let result = stub.provider().exchange_code("code", "verifier".to_string(), NONCE).await;
+
+        assert!(matches!(result, Err(PlexoAppError::AuthProviderError(_))));
+    }
+
+    #[tokio::test]
+    async fn rejects_expired_id_token() {
+        let stub = StubProvider::start().await;
+        stub.issue(CLIENT_ID, NONCE, -Duration::try_hours(1).unwrap());
+
+        let result = stub.provider().exchange_code("code", "verifier".to_string(), NONCE).await;
+
+        assert!(matches!(result, Err(PlexoAppError::AuthProviderError(_))));
+    }
+
+    #[tokio::test]
+    async fn caches_jwks_until_a_key_is_missing() {
+        let stub = StubProvider::start().await;
+        let provider = stub.provider();
+
+        stub.issue(CLIENT_ID, NONCE, valid_for());
+        provider.exchange_code("code", "verifier".to_string(), NONCE).await.unwrap();
+        provider.exchange_code("code", "verifier".to_string(), NONCE).await.unwrap();
+
+        assert_eq!(stub.jwks_requests.load(Ordering::SeqCst), 1);
+
+        stub.rotate("second");
+        stub.issue(CLIENT_ID, NONCE, valid_for());
+        provider.exchange_code("code", "verifier".to_string(), NONCE).await.unwrap();
+
+        assert_eq!(stub.jwks_requests.load(Ordering::SeqCst), 2);
+    }
+}
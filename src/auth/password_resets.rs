@@ -0,0 +1,127 @@
+use askama::Template;
+use chrono::{Duration, Utc};
+use plexo_sdk::resources::members::{
+    extensions::MembersExtensionOperations,
+    operations::{MemberCrudOperations, UpdateMemberInputBuilder},
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        app::Core,
+        config::{PASSWORD_RESET_TOKEN_TTL_MINUTES, PASSWORD_RESET_URL},
+        email::PasswordResetTemplate,
+    },
+    errors::app::PlexoAppError,
+};
+
+use super::commons::{generate_link_token, hash_link_token};
+
+const PASSWORD_RESET_TOKEN_LENGTH: usize = 48;
+
+impl Core {
+    /// Emails a single-use reset link to the member with the given email. All the work happens
+    /// in the background and unknown emails are silently ignored, so neither the response nor
+    /// how long it takes tells callers whether an account exists.
+    pub fn request_password_reset(&self, email: String) {
+        let core = self.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = core.send_password_reset(email).await {
+                warn!("password reset not sent: {:?}", err);
+            }
+        });
+    }
+
+    async fn send_password_reset(&self, email: String) -> Result<(), PlexoAppError> {
+        let Ok(Some(member)) = self.engine.get_member_by_email(email).await else {
+            info!("password reset requested for an unknown email");
+            return Ok(());
+        };
+
+        let token = generate_link_token(PASSWORD_RESET_TOKEN_LENGTH);
+        let expires_at = Utc::now() + Duration::try_minutes(*PASSWORD_RESET_TOKEN_TTL_MINUTES).unwrap();
+
+        let mut tx = self.engine.db_pool.begin().await?;
+
+        // Only the latest link stays valid.
+        sqlx::query(
+            r#"
+            UPDATE password_reset_tokens
+            SET used_at = now()
+            WHERE member_id = $1 AND used_at IS NULL
+            "#,
+        )
+        .bind(member.id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO password_reset_tokens (member_id, token_hash, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(member.id)
+        .bind(hash_link_token(&token))
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let html = PasswordResetTemplate {
+            member_name: member.name.clone(),
+            reset_url: format!("{}?token={}", *PASSWORD_RESET_URL, token),
+            expires_in_minutes: *PASSWORD_RESET_TOKEN_TTL_MINUTES,
+        }
+        .render()?;
+
+        let core = self.clone();
+
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = core.send_email("no-reply@plexo.app", &member.email, "Reset your Plexo password", &html) {
+                warn!("password reset email not sent: {:?}", err);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Sets a new password using a reset token, which is consumed in the process. Every
+    /// session of the member is revoked afterwards.
+    pub async fn reset_password(&self, token: &str, new_password: &str) -> Result<Uuid, PlexoAppError> {
+        let member_id: Option<Uuid> = sqlx::query_scalar(
+            r#"
+            UPDATE password_reset_tokens
+            SET used_at = now()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+            RETURNING member_id
+            "#,
+        )
+        .bind(hash_link_token(token))
+        .fetch_optional(self.engine.db_pool.as_ref())
+        .await?;
+
+        let Some(member_id) = member_id else {
+            return Err(PlexoAppError::InvalidPasswordResetToken);
+        };
+
+        let password_hash = self.auth.hash_password(new_password);
+
+        self.engine
+            .update_member(
+                member_id,
+                UpdateMemberInputBuilder::default()
+                    .password_hash(password_hash)
+                    .build()
+                    .unwrap(),
+            )
+            .await?;
+
+        self.revoke_all_sessions(member_id).await?;
+
+        Ok(member_id)
+    }
+}
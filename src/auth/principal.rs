@@ -0,0 +1,149 @@
+use plexo_sdk::resources::members::{member::MemberRole, operations::MemberCrudOperations};
+use uuid::Uuid;
+
+use crate::{core::app::Core, errors::app::PlexoAppError};
+
+use super::{
+    api_keys::{is_api_key, ApiKey, ApiKeyScope},
+    resources::PlexoAuthToken,
+    service_accounts::ServiceAccount,
+};
+
+/// The authenticated caller behind a request, resolved once per request or WebSocket
+/// connection from a session token or an API key.
+#[derive(Debug, Clone)]
+pub struct PlexoPrincipal {
+    pub member_id: Uuid,
+    pub role: MemberRole,
+    pub session_id: Option<Uuid>,
+    pub api_key: Option<ApiKey>,
+    /// Set when the API key belongs to a service account, whose limits apply on top of the key's.
+    pub service_account: Option<ServiceAccount>,
+}
+
+impl PlexoPrincipal {
+    pub fn can_write(&self) -> bool {
+        self.api_key
+            .as_ref()
+            .is_none_or(|api_key| api_key.scope == ApiKeyScope::ReadWrite)
+            && self
+                .service_account
+                .as_ref()
+                .is_none_or(|service_account| service_account.can_write())
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == MemberRole::Admin
+    }
+
+    /// Project-limited API keys can only reach resources of their own project, and service
+    /// accounts only those of their projects.
+    pub fn can_access_project(&self, project_id: Option<Uuid>) -> bool {
+        let api_key_allowed = match self.api_key.as_ref().and_then(|api_key| api_key.project_id) {
+            Some(allowed_project_id) => project_id == Some(allowed_project_id),
+            None => true,
+        };
+
+        api_key_allowed
+            && self
+                .service_account
+                .as_ref()
+                .is_none_or(|service_account| service_account.can_access_project(project_id))
+    }
+
+    /// Writes need both a read-write API key scope, when authenticated with a key, and a
+    /// member role other than `ReadOnly`.
+    pub fn authorize_write(&self) -> Result<(), PlexoAppError> {
+        if !self.can_write() {
+            return Err(PlexoAppError::InsufficientScope);
+        }
+
+        if self.role == MemberRole::ReadOnly {
+            return Err(PlexoAppError::Forbidden);
+        }
+
+        Ok(())
+    }
+
+    pub fn authorize_admin(&self) -> Result<(), PlexoAppError> {
+        if !self.is_admin() {
+            return Err(PlexoAppError::Forbidden);
+        }
+
+        Ok(())
+    }
+
+    pub fn authorize_project(&self, project_id: Option<Uuid>) -> Result<(), PlexoAppError> {
+        if !self.can_access_project(project_id) {
+            return Err(PlexoAppError::InsufficientScope);
+        }
+
+        Ok(())
+    }
+
+    pub fn is_api_key(&self) -> bool {
+        self.api_key.is_some()
+    }
+}
+
+impl Core {
+    pub async fn authenticate(&self, plexo_auth_token: &PlexoAuthToken) -> Result<PlexoPrincipal, PlexoAppError> {
+        if is_api_key(plexo_auth_token.0.as_str()) {
+            let api_key = self.authenticate_api_key(plexo_auth_token.0.as_str()).await?;
+
+            self.ensure_member_active(api_key.member_id).await?;
+
+            return Ok(PlexoPrincipal {
+                member_id: api_key.member_id,
+                role: self.get_member_role(api_key.member_id).await?,
+                session_id: None,
+                service_account: self.get_service_account(api_key.member_id).await?,
+                api_key: Some(api_key),
+            });
+        }
+
+        let claims = self.auth.extract_claims(plexo_auth_token)?;
+        let member_id = claims.member_id()?;
+        let session_id = claims.session_id()?;
+
+        if !self.is_session_active(member_id, session_id).await? {
+            return Err(PlexoAppError::InvalidAuthorizationToken);
+        }
+
+        self.ensure_member_active(member_id).await?;
+
+        Ok(PlexoPrincipal {
+            member_id,
+            role: self.get_member_role(member_id).await?,
+            session_id: Some(session_id),
+            api_key: None,
+            service_account: None,
+        })
+    }
+
+    /// Whether the session or API key behind the principal still holds and its member is
+    /// still active, for connections that outlive the request that authenticated them.
+    pub async fn is_principal_active(&self, principal: &PlexoPrincipal) -> Result<bool, PlexoAppError> {
+        if self.is_member_deactivated(principal.member_id).await? {
+            return Ok(false);
+        }
+
+        if let Some(session_id) = principal.session_id {
+            return self.is_session_active(principal.member_id, session_id).await;
+        }
+
+        if let Some(api_key) = &principal.api_key {
+            return self.is_api_key_active(api_key.id).await;
+        }
+
+        Ok(true)
+    }
+
+    async fn get_member_role(&self, member_id: Uuid) -> Result<MemberRole, PlexoAppError> {
+        self.engine
+            .get_member(member_id)
+            .await
+            .map(|member| member.role)
+            .map_err(|_| PlexoAppError::InvalidAuthorizationToken)
+    }
+}
@@ -0,0 +1,432 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use async_graphql::{Enum, SimpleObject};
+use chrono::{DateTime, Utc};
+use plexo_sdk::resources::{
+    changes::change::{Change, ChangeResourceType, ListenEvent},
+    projects::project::ProjectVisibility,
+};
+use sqlx::{FromRow, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::{core::app::Core, errors::app::PlexoAppError};
+
+use super::principal::PlexoPrincipal;
+
+/// What a member can do inside a single project, ordered from least to most privileged.
+#[derive(Debug, Enum, Copy, Clone, Eq, PartialEq, PartialOrd, Ord)]
+pub enum ProjectRole {
+    Viewer,
+    Member,
+    Lead,
+    Owner,
+}
+
+impl ProjectRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProjectRole::Viewer => "Viewer",
+            ProjectRole::Member => "Member",
+            ProjectRole::Lead => "Lead",
+            ProjectRole::Owner => "Owner",
+        }
+    }
+}
+
+impl FromStr for ProjectRole {
+    type Err = PlexoAppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Viewer" => Ok(ProjectRole::Viewer),
+            "Member" => Ok(ProjectRole::Member),
+            "Lead" => Ok(ProjectRole::Lead),
+            "Owner" => Ok(ProjectRole::Owner),
+            _ => Err(PlexoAppError::Forbidden),
+        }
+    }
+}
+
+/// A role explicitly granted to a member, on top of the ones implied by the project's
+/// owner, lead and members.
+#[derive(Debug, SimpleObject, Clone)]
+pub struct ProjectRoleAssignment {
+    pub project_id: Uuid,
+    pub member_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+
+    pub role: ProjectRole,
+}
+
+#[derive(FromRow)]
+struct ProjectRoleAssignmentRow {
+    project_id: Uuid,
+    member_id: Uuid,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    role: String,
+}
+
+impl TryFrom<ProjectRoleAssignmentRow> for ProjectRoleAssignment {
+    type Error = PlexoAppError;
+
+    fn try_from(row: ProjectRoleAssignmentRow) -> Result<Self, Self::Error> {
+        Ok(ProjectRoleAssignment {
+            project_id: row.project_id,
+            member_id: row.member_id,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            role: ProjectRole::from_str(&row.role)?,
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct ProjectAccessRow {
+    owner_id: Uuid,
+    lead_id: Option<Uuid>,
+    visibility: Option<String>,
+    role: Option<String>,
+    is_member: bool,
+}
+
+#[derive(FromRow)]
+struct DeletedResourceRow {
+    project_id: Option<Uuid>,
+    private: bool,
+    member_ids: Vec<Uuid>,
+}
+
+/// The projects a principal can see, resolved once so lists can be filtered in memory.
+pub struct VisibleProjects {
+    principal: PlexoPrincipal,
+    hidden: HashSet<Uuid>,
+}
+
+impl VisibleProjects {
+    /// Resources outside of any project are visible to everyone in the organization.
+    pub fn contains(&self, project_id: Option<Uuid>) -> bool {
+        self.principal.can_access_project(project_id) && project_id.is_none_or(|project_id| !self.hidden.contains(&project_id))
+    }
+
+    /// Whether a change log entry is visible, given the project of the resource it's about.
+    /// Redactions name the entry they erased and why, so only admins see them.
+    pub fn contains_change(&self, change: &Change, project_id: Option<Uuid>) -> bool {
+        match change.resource_type {
+            ChangeResourceType::Changes => self.principal.is_admin() && self.contains(None),
+            _ => self.contains(project_id),
+        }
+    }
+
+    /// Whether every project is visible, so nothing needs to be filtered out.
+    pub fn is_unrestricted(&self) -> bool {
+        self.hidden.is_empty() && self.principal.can_access_project(None)
+    }
+
+    /// The same rules as [`VisibleProjects::contains`], pushed as `AND` conditions on the
+    /// project id in `column`.
+    pub fn push_conditions(&self, query: &mut QueryBuilder<'_, Postgres>, column: &str) {
+        if !self.hidden.is_empty() {
+            query
+                .push(format!(" AND ({column} IS NULL OR {column} <> ALL("))
+                .push_bind(self.hidden.iter().copied().collect::<Vec<Uuid>>())
+                .push("))");
+        }
+
+        if let Some(project_id) = self.principal.api_key.as_ref().and_then(|api_key| api_key.project_id) {
+            query.push(format!(" AND {column} = ")).push_bind(project_id);
+        }
+
+        if let Some(service_account) = self.principal.service_account.as_ref() {
+            if !service_account.project_ids.is_empty() {
+                query
+                    .push(format!(" AND {column} = ANY("))
+                    .push_bind(service_account.project_ids.clone())
+                    .push(")");
+            }
+        }
+    }
+}
+
+/// The project of the resource a change is about, given the projects of the resources that
+/// still exist. Changes about deleted rows fall back to the project recorded in their diff.
+fn change_project_id(change: &Change, project_ids: &HashMap<Uuid, Option<Uuid>>) -> Option<Uuid> {
+    match change.resource_type {
+        ChangeResourceType::Projects => Some(change.resource_id),
+        ChangeResourceType::Tasks | ChangeResourceType::Assets => match project_ids.get(&change.resource_id) {
+            Some(project_id) => *project_id,
+            None => serde_json::from_str::<serde_json::Value>(&change.diff_json)
+                .ok()
+                .and_then(|diff| diff["result"]["project_id"].as_str().and_then(|id| id.parse().ok())),
+        },
+        _ => None,
+    }
+}
+
+impl Core {
+    /// Resolves the caller's role in a project. Organization admins own every project, and
+    /// anyone in the organization can view projects that aren't private.
+    pub async fn get_project_role(
+        &self,
+        principal: &PlexoPrincipal,
+        project_id: Uuid,
+    ) -> Result<Option<ProjectRole>, PlexoAppError> {
+        let row = sqlx::query_as::<_, ProjectAccessRow>(
+            r#"
+            SELECT
+                p.owner_id,
+                p.lead_id,
+                p.visibility,
+                r.role,
+                EXISTS (
+                    SELECT 1 FROM members_by_projects m
+                    WHERE m.project_id = p.id AND m.member_id = $2
+                ) AS is_member
+            FROM projects p
+            LEFT JOIN project_roles r ON r.project_id = p.id AND r.member_id = $2
+            WHERE p.id = $1
+            "#,
+        )
+        .bind(project_id)
+        .bind(principal.member_id)
+        .fetch_optional(self.engine.db_pool.as_ref())
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        if principal.is_admin() || row.owner_id == principal.member_id {
+            return Ok(Some(ProjectRole::Owner));
+        }
+
+        if row.lead_id == Some(principal.member_id) {
+            return Ok(Some(ProjectRole::Lead));
+        }
+
+        if let Some(role) = row.role {
+            return ProjectRole::from_str(&role).map(Some);
+        }
+
+        if row.is_member {
+            return Ok(Some(ProjectRole::Member));
+        }
+
+        if row.visibility.as_deref() != Some(ProjectVisibility::Private.to_string().as_str()) {
+            return Ok(Some(ProjectRole::Viewer));
+        }
+
+        Ok(None)
+    }
+
+    /// Checks that the caller holds at least `role` in the project. Resources outside of
+    /// any project only go through the API key's project limit.
+    pub async fn authorize_project_role(
+        &self,
+        principal: &PlexoPrincipal,
+        project_id: Option<Uuid>,
+        role: ProjectRole,
+    ) -> Result<(), PlexoAppError> {
+        principal.authorize_project(project_id)?;
+
+        let Some(project_id) = project_id else {
+            return Ok(());
+        };
+
+        match self.get_project_role(principal, project_id).await? {
+            Some(project_role) if project_role >= role => Ok(()),
+            _ => Err(PlexoAppError::Forbidden),
+        }
+    }
+
+    pub async fn visible_projects(&self, principal: &PlexoPrincipal) -> Result<VisibleProjects, PlexoAppError> {
+        let hidden = if principal.is_admin() {
+            HashSet::new()
+        } else {
+            self.hidden_project_ids(principal.member_id).await?
+        };
+
+        Ok(VisibleProjects {
+            principal: principal.clone(),
+            hidden,
+        })
+    }
+
+    /// Private projects where the member holds no role at all.
+    async fn hidden_project_ids(&self, member_id: Uuid) -> Result<HashSet<Uuid>, PlexoAppError> {
+        let ids: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            SELECT p.id FROM projects p
+            WHERE p.visibility = $1
+                AND p.owner_id <> $2
+                AND p.lead_id IS DISTINCT FROM $2
+                AND NOT EXISTS (
+                    SELECT 1 FROM project_roles r
+                    WHERE r.project_id = p.id AND r.member_id = $2
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM members_by_projects m
+                    WHERE m.project_id = p.id AND m.member_id = $2
+                )
+            "#,
+        )
+        .bind(ProjectVisibility::Private.to_string())
+        .bind(member_id)
+        .fetch_all(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(ids.into_iter().collect())
+    }
+
+    /// Maps task, asset and project ids to the project they belong to. Ids of rows that no
+    /// longer exist are left out.
+    async fn get_resource_project_ids(&self, ids: &[Uuid]) -> Result<HashMap<Uuid, Option<Uuid>>, PlexoAppError> {
+        let rows: Vec<(Uuid, Option<Uuid>)> = sqlx::query_as(
+            r#"
+            SELECT id, project_id FROM tasks WHERE id = ANY($1)
+            UNION ALL
+            SELECT id, project_id FROM assets WHERE id = ANY($1)
+            UNION ALL
+            SELECT id, id AS project_id FROM projects WHERE id = ANY($1)
+            "#,
+        )
+        .bind(ids)
+        .fetch_all(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Drops changes about resources in projects the caller can't see, and redactions unless
+    /// the caller is an admin.
+    pub async fn filter_visible_changes(
+        &self,
+        principal: &PlexoPrincipal,
+        changes: Vec<Change>,
+    ) -> Result<Vec<Change>, PlexoAppError> {
+        let visible_projects = self.visible_projects(principal).await?;
+
+        let ids: Vec<Uuid> = changes.iter().map(|change| change.resource_id).collect();
+        let project_ids = self.get_resource_project_ids(&ids).await?;
+
+        Ok(changes
+            .into_iter()
+            .filter(|change| visible_projects.contains_change(change, change_project_id(change, &project_ids)))
+            .collect())
+    }
+
+    /// The project of the resource a change is about, if any.
+    pub async fn get_change_project_id(&self, change: &Change) -> Result<Option<Uuid>, PlexoAppError> {
+        let project_ids = self.get_resource_project_ids(&[change.resource_id]).await?;
+
+        Ok(change_project_id(change, &project_ids))
+    }
+
+    /// What was recorded about a task, asset or project when it was deleted. Records are kept
+    /// for a day, long enough for subscriptions to catch up with the deletion.
+    async fn get_deleted_resource(&self, id: Uuid) -> Result<Option<DeletedResourceRow>, PlexoAppError> {
+        sqlx::query_as::<_, DeletedResourceRow>("SELECT project_id, private, member_ids FROM deleted_resources WHERE id = $1")
+            .bind(id)
+            .fetch_optional(self.engine.db_pool.as_ref())
+            .await
+            .map_err(|err| err.into())
+    }
+
+    /// The project of the row an event from `listen` is about. Rows that are already gone are
+    /// traced back through what was recorded when they were deleted, and rows that can't be
+    /// traced back at all are forbidden.
+    pub async fn get_event_project_id(&self, event: &ListenEvent) -> Result<Option<Uuid>, PlexoAppError> {
+        match event.resource {
+            ChangeResourceType::Projects => Ok(Some(event.row_id)),
+            ChangeResourceType::Tasks | ChangeResourceType::Assets => {
+                if let Some(project_id) = self.get_resource_project_ids(&[event.row_id]).await?.get(&event.row_id) {
+                    return Ok(*project_id);
+                }
+
+                self.get_deleted_resource(event.row_id)
+                    .await?
+                    .map(|deleted| deleted.project_id)
+                    .ok_or(PlexoAppError::Forbidden)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Whether the caller can see what's in a project. Once the project is deleted, only the
+    /// members who could see it right before still can.
+    pub async fn can_view_project(&self, principal: &PlexoPrincipal, project_id: Option<Uuid>) -> Result<bool, PlexoAppError> {
+        if !principal.can_access_project(project_id) {
+            return Ok(false);
+        }
+
+        let Some(project_id) = project_id else {
+            return Ok(true);
+        };
+
+        if self.get_project_role(principal, project_id).await?.is_some() {
+            return Ok(true);
+        }
+
+        Ok(self
+            .get_deleted_resource(project_id)
+            .await?
+            .is_some_and(|deleted| principal.is_admin() || !deleted.private || deleted.member_ids.contains(&principal.member_id)))
+    }
+
+    pub async fn get_project_roles(&self, project_id: Uuid) -> Result<Vec<ProjectRoleAssignment>, PlexoAppError> {
+        sqlx::query_as::<_, ProjectRoleAssignmentRow>(
+            r#"
+            SELECT * FROM project_roles
+            WHERE project_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(project_id)
+        .fetch_all(self.engine.db_pool.as_ref())
+        .await?
+        .into_iter()
+        .map(|row| row.try_into())
+        .collect()
+    }
+
+    pub async fn set_project_role(
+        &self,
+        project_id: Uuid,
+        member_id: Uuid,
+        role: ProjectRole,
+    ) -> Result<ProjectRoleAssignment, PlexoAppError> {
+        sqlx::query_as::<_, ProjectRoleAssignmentRow>(
+            r#"
+            INSERT INTO project_roles (project_id, member_id, role)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (project_id, member_id) DO UPDATE SET role = EXCLUDED.role
+            RETURNING *
+            "#,
+        )
+        .bind(project_id)
+        .bind(member_id)
+        .bind(role.as_str())
+        .fetch_one(self.engine.db_pool.as_ref())
+        .await?
+        .try_into()
+    }
+
+    pub async fn remove_project_role(&self, project_id: Uuid, member_id: Uuid) -> Result<ProjectRoleAssignment, PlexoAppError> {
+        sqlx::query_as::<_, ProjectRoleAssignmentRow>(
+            r#"
+            DELETE FROM project_roles
+            WHERE project_id = $1 AND member_id = $2
+            RETURNING *
+            "#,
+        )
+        .bind(project_id)
+        .bind(member_id)
+        .fetch_one(self.engine.db_pool.as_ref())
+        .await?
+        .try_into()
+    }
+}
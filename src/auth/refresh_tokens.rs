@@ -0,0 +1,155 @@
+use chrono::{DateTime, Utc};
+use plexo_sdk::resources::members::{member::Member, operations::MemberCrudOperations};
+use sqlx::FromRow;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{core::app::Core, errors::app::PlexoAppError};
+
+use super::service_accounts::MemberKind;
+
+/// A short-lived access token paired with the refresh token that can renew it.
+#[derive(Debug, Clone)]
+pub struct SessionTokens {
+    pub member_id: Uuid,
+    pub access_token: String,
+    pub access_token_expires_at: DateTime<Utc>,
+    pub refresh_token: String,
+    pub refresh_token_expires_at: DateTime<Utc>,
+}
+
+#[derive(FromRow)]
+struct RefreshTokenRow {
+    id: Uuid,
+    family_id: Uuid,
+    member_id: Uuid,
+}
+
+impl Core {
+    /// Starts a new session for the member, opening a new refresh token family. Service
+    /// accounts and deactivated members are refused here, which covers every interactive login.
+    pub async fn create_session_tokens(&self, member: &Member) -> Result<SessionTokens, PlexoAppError> {
+        if self.get_member_kind(member.id).await? == MemberKind::ServiceAccount {
+            return Err(PlexoAppError::ServiceAccountLogin);
+        }
+
+        self.ensure_member_active(member.id).await?;
+        self.ensure_email_verified(member.id).await?;
+
+        let session = self
+            .create_session(member.id, self.auth.jwt_engine.refresh_token_expiration())
+            .await?;
+
+        self.issue_session_tokens(member, session.id).await
+    }
+
+    /// Exchanges a refresh token for a new pair of tokens. Every refresh token can only be
+    /// used once; presenting one that was already used revokes its whole family, so a
+    /// stolen token stops working for both the attacker and the legitimate client.
+    pub async fn refresh_session_tokens(&self, refresh_token: &str) -> Result<SessionTokens, PlexoAppError> {
+        let claims = self.auth.jwt_engine.decode_refresh_token(refresh_token)?;
+        let token_id = claims.token_id()?;
+
+        let pool = self.engine.db_pool.as_ref();
+
+        let row = sqlx::query_as::<_, RefreshTokenRow>(
+            r#"
+            UPDATE refresh_tokens
+            SET used_at = now()
+            WHERE id = $1 AND used_at IS NULL AND revoked_at IS NULL AND expires_at > now()
+                AND EXISTS (SELECT 1 FROM sessions WHERE sessions.id = family_id AND sessions.revoked_at IS NULL)
+            RETURNING id, family_id, member_id
+            "#,
+        )
+        .bind(token_id)
+        .fetch_optional(pool)
+        .await?;
+
+        let Some(row) = row else {
+            let reused = sqlx::query_as::<_, RefreshTokenRow>(
+                r#"
+                SELECT id, family_id, member_id FROM refresh_tokens
+                WHERE id = $1 AND used_at IS NOT NULL
+                "#,
+            )
+            .bind(token_id)
+            .fetch_optional(pool)
+            .await?;
+
+            if let Some(reused) = reused {
+                warn!(
+                    "refresh token {} reused, revoking token family {} of member {}",
+                    reused.id, reused.family_id, reused.member_id
+                );
+
+                self.revoke_session(reused.member_id, reused.family_id).await?;
+            }
+
+            return Err(PlexoAppError::InvalidAuthorizationToken);
+        };
+
+        let member = self.engine.get_member(row.member_id).await?;
+
+        self.issue_session_tokens(&member, row.family_id).await
+    }
+
+    /// Revokes the session the given refresh token belongs to, used when a session ends.
+    pub async fn revoke_refresh_token(&self, refresh_token: &str) -> Result<(), PlexoAppError> {
+        let claims = self.auth.jwt_engine.decode_refresh_token(refresh_token)?;
+
+        let row = sqlx::query_as::<_, RefreshTokenRow>("SELECT id, family_id, member_id FROM refresh_tokens WHERE id = $1")
+            .bind(claims.token_id()?)
+            .fetch_optional(self.engine.db_pool.as_ref())
+            .await?;
+
+        if let Some(row) = row {
+            self.revoke_session(row.member_id, row.family_id).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn revoke_refresh_token_family(&self, family_id: Uuid) -> Result<(), PlexoAppError> {
+        sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = now()
+            WHERE family_id = $1 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(family_id)
+        .execute(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(())
+    }
+
+    async fn issue_session_tokens(&self, member: &Member, family_id: Uuid) -> Result<SessionTokens, PlexoAppError> {
+        let jwt_engine = &self.auth.jwt_engine;
+
+        let refresh_token_expires_at = jwt_engine.refresh_token_expiration();
+
+        let token_id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO refresh_tokens (family_id, member_id, expires_at)
+            VALUES ($1, $2, $3)
+            RETURNING id
+            "#,
+        )
+        .bind(family_id)
+        .bind(member.id)
+        .bind(refresh_token_expires_at)
+        .fetch_one(self.engine.db_pool.as_ref())
+        .await?;
+
+        self.extend_session(family_id, refresh_token_expires_at).await?;
+
+        Ok(SessionTokens {
+            member_id: member.id,
+            access_token: jwt_engine.create_session_token(member, family_id)?,
+            access_token_expires_at: jwt_engine.access_token_expiration(),
+            refresh_token: jwt_engine.create_refresh_token(member.id, token_id, refresh_token_expires_at)?,
+            refresh_token_expires_at,
+        })
+    }
+}
@@ -1,9 +1,43 @@
 use serde::{Deserialize, Serialize};
 
+use crate::errors::app::PlexoAppError;
+
+#[derive(Debug, Deserialize)]
+pub struct GithubSignInParams {
+    /// Invitation token to accept with the GitHub account once it's authorized.
+    pub invitation: Option<String>,
+    /// Where to send the browser once signed in, instead of `/`.
+    pub redirect_to: Option<String>,
+    /// Token from the `linkIdentity` mutation, to link the account instead of signing in.
+    pub link_token: Option<String>,
+}
+
+/// GitHub answers with either a code or an error, like OIDC providers.
 #[derive(Debug, Deserialize)]
 pub struct GithubCallbackParams {
-    pub code: String,
-    pub state: String,
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcSignInParams {
+    /// Invitation token to accept with the provider's identity once it's authorized.
+    pub invitation: Option<String>,
+    /// Where to send the browser once signed in, instead of `/`.
+    pub redirect_to: Option<String>,
+    /// Token from the `linkIdentity` mutation, to link the account instead of signing in.
+    pub link_token: Option<String>,
+}
+
+/// Providers answer with either a code or an error.
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackParams {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -15,15 +49,57 @@ pub struct AuthenticationResponse {
 
 pub struct PlexoAuthToken(pub String);
 
+/// Why the request's token was rejected, kept so resolvers can report it.
+pub struct AuthenticationFailure(pub PlexoAppError);
+
+/// Address of the client calling the API, used to throttle failed logins.
+#[derive(Debug, Clone)]
+pub struct ClientIp(pub Option<String>);
+
 #[derive(Debug, Deserialize)]
 pub struct EmailLoginParams {
     pub email: String,
     pub password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct EmailLoginChallengeParams {
+    pub challenge_token: String,
+    /// A code from the member's authenticator app, or one of their recovery codes.
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenParams {
+    pub refresh_token: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct EmailRegisterParams {
     pub email: String,
     pub name: String,
     pub password: String,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordParams {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordParams {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailParams {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptInvitationParams {
+    pub token: String,
+    pub name: String,
+    pub password: String,
+}
@@ -0,0 +1,234 @@
+use std::str::FromStr;
+
+use async_graphql::{Enum, SimpleObject};
+use chrono::{DateTime, Utc};
+use plexo_sdk::resources::members::{
+    extensions::{CreateMemberFromEmailInputBuilder, MembersExtensionOperations},
+    member::MemberRole,
+    operations::MemberCrudOperations,
+};
+use sqlx::FromRow;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{core::app::Core, errors::app::PlexoAppError};
+
+use super::api_keys::{ApiKey, ApiKeyScope};
+
+/// Service accounts still need a unique email, which is never used to sign in or mailed.
+const SERVICE_ACCOUNT_EMAIL_DOMAIN: &str = "service-accounts.plexo.app";
+
+#[derive(Debug, Enum, Copy, Clone, Eq, PartialEq)]
+pub enum MemberKind {
+    Human,
+    ServiceAccount,
+}
+
+/// A member for bots and integrations. It can't sign in interactively, only use API keys,
+/// and every key it has is capped by the account's scope and projects.
+#[derive(Debug, SimpleObject, Clone)]
+pub struct ServiceAccount {
+    pub member_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+
+    pub name: String,
+    pub description: Option<String>,
+    pub scope: ApiKeyScope,
+    /// Projects the account is limited to. Empty means every project.
+    pub project_ids: Vec<Uuid>,
+    pub created_by_id: Option<Uuid>,
+}
+
+impl ServiceAccount {
+    pub fn can_write(&self) -> bool {
+        self.scope == ApiKeyScope::ReadWrite
+    }
+
+    pub fn can_access_project(&self, project_id: Option<Uuid>) -> bool {
+        if self.project_ids.is_empty() {
+            return true;
+        }
+
+        project_id.is_some_and(|project_id| self.project_ids.contains(&project_id))
+    }
+}
+
+#[derive(FromRow)]
+struct ServiceAccountRow {
+    member_id: Uuid,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    name: String,
+    description: Option<String>,
+    scope: String,
+    project_ids: Vec<Uuid>,
+    created_by_id: Option<Uuid>,
+}
+
+impl TryFrom<ServiceAccountRow> for ServiceAccount {
+    type Error = PlexoAppError;
+
+    fn try_from(row: ServiceAccountRow) -> Result<Self, Self::Error> {
+        Ok(ServiceAccount {
+            member_id: row.member_id,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            name: row.name,
+            description: row.description,
+            scope: ApiKeyScope::from_str(&row.scope)?,
+            project_ids: row.project_ids,
+            created_by_id: row.created_by_id,
+        })
+    }
+}
+
+const SELECT_SERVICE_ACCOUNTS: &str = r#"
+    SELECT service_accounts.*, members.name
+    FROM service_accounts
+    JOIN members ON members.id = service_accounts.member_id
+"#;
+
+impl Core {
+    pub async fn create_service_account(
+        &self,
+        created_by_id: Uuid,
+        name: String,
+        description: Option<String>,
+        role: MemberRole,
+        scope: ApiKeyScope,
+        project_ids: Vec<Uuid>,
+    ) -> Result<ServiceAccount, PlexoAppError> {
+        // An empty hash never validates, so there's no password to log in with.
+        let member = self
+            .engine
+            .create_member_from_email(
+                CreateMemberFromEmailInputBuilder::default()
+                    .email(format!("{}@{}", Uuid::new_v4(), SERVICE_ACCOUNT_EMAIL_DOMAIN))
+                    .name(name)
+                    .password_hash("".to_string())
+                    .role(role)
+                    .build()
+                    .unwrap(),
+            )
+            .await?;
+
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO service_accounts (member_id, description, scope, project_ids, created_by_id)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(member.id)
+        .bind(description)
+        .bind(scope.as_str())
+        .bind(project_ids)
+        .bind(created_by_id)
+        .execute(self.engine.db_pool.as_ref())
+        .await;
+
+        if let Err(err) = inserted {
+            // Don't leave a member behind that would be mistaken for a person.
+            if let Err(delete_err) = self.engine.delete_member(member.id).await {
+                warn!("service account member {} not cleaned up: {:?}", member.id, delete_err);
+            }
+
+            return Err(err.into());
+        }
+
+        self.get_service_account(member.id)
+            .await?
+            .ok_or(PlexoAppError::ServiceAccountNotFound)
+    }
+
+    pub async fn get_service_accounts(&self) -> Result<Vec<ServiceAccount>, PlexoAppError> {
+        sqlx::query_as::<_, ServiceAccountRow>(&format!(
+            "{SELECT_SERVICE_ACCOUNTS} ORDER BY service_accounts.created_at DESC"
+        ))
+        .fetch_all(self.engine.db_pool.as_ref())
+        .await?
+        .into_iter()
+        .map(|row| row.try_into())
+        .collect()
+    }
+
+    pub async fn get_service_account(&self, member_id: Uuid) -> Result<Option<ServiceAccount>, PlexoAppError> {
+        sqlx::query_as::<_, ServiceAccountRow>(&format!("{SELECT_SERVICE_ACCOUNTS} WHERE service_accounts.member_id = $1"))
+            .bind(member_id)
+            .fetch_optional(self.engine.db_pool.as_ref())
+            .await?
+            .map(|row| row.try_into())
+            .transpose()
+    }
+
+    pub async fn get_member_kind(&self, member_id: Uuid) -> Result<MemberKind, PlexoAppError> {
+        let is_service_account: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM service_accounts WHERE member_id = $1)")
+            .bind(member_id)
+            .fetch_one(self.engine.db_pool.as_ref())
+            .await?;
+
+        Ok(match is_service_account {
+            true => MemberKind::ServiceAccount,
+            false => MemberKind::Human,
+        })
+    }
+
+    /// Narrowing the scope or the projects applies right away to the account's existing keys.
+    pub async fn update_service_account(
+        &self,
+        member_id: Uuid,
+        description: Option<String>,
+        scope: Option<ApiKeyScope>,
+        project_ids: Option<Vec<Uuid>>,
+    ) -> Result<ServiceAccount, PlexoAppError> {
+        let updated = sqlx::query(
+            r#"
+            UPDATE service_accounts
+            SET
+                description = COALESCE($1, description),
+                scope = COALESCE($2, scope),
+                project_ids = COALESCE($3, project_ids)
+            WHERE member_id = $4
+            "#,
+        )
+        .bind(description)
+        .bind(scope.map(|scope| scope.as_str()))
+        .bind(project_ids)
+        .bind(member_id)
+        .execute(self.engine.db_pool.as_ref())
+        .await?
+        .rows_affected();
+
+        if updated == 0 {
+            return Err(PlexoAppError::ServiceAccountNotFound);
+        }
+
+        self.get_service_account(member_id)
+            .await?
+            .ok_or(PlexoAppError::ServiceAccountNotFound)
+    }
+
+    /// Keys can't be broader than the account: a read-write key needs a read-write account,
+    /// and a project-limited account only gets keys for one of its projects.
+    pub async fn create_service_account_api_key(
+        &self,
+        member_id: Uuid,
+        name: String,
+        scope: ApiKeyScope,
+        project_id: Option<Uuid>,
+    ) -> Result<(ApiKey, String), PlexoAppError> {
+        let Some(service_account) = self.get_service_account(member_id).await? else {
+            return Err(PlexoAppError::ServiceAccountNotFound);
+        };
+
+        if scope == ApiKeyScope::ReadWrite && !service_account.can_write() {
+            return Err(PlexoAppError::InsufficientScope);
+        }
+
+        if !service_account.project_ids.is_empty() && !service_account.can_access_project(project_id) {
+            return Err(PlexoAppError::InsufficientScope);
+        }
+
+        self.create_api_key(member_id, name, scope, project_id).await
+    }
+}
@@ -0,0 +1,133 @@
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{core::app::Core, errors::app::PlexoAppError};
+
+/// A signed-in device or client. Access tokens carry the session id in their `jti` claim
+/// and the session's refresh tokens share it as their family id.
+#[derive(Debug, SimpleObject, FromRow, Clone)]
+pub struct Session {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+
+    pub member_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl Core {
+    pub async fn create_session(&self, member_id: Uuid, expires_at: DateTime<Utc>) -> Result<Session, PlexoAppError> {
+        let session = sqlx::query_as::<_, Session>(
+            r#"
+            INSERT INTO sessions (member_id, expires_at)
+            VALUES ($1, $2)
+            RETURNING *
+            "#,
+        )
+        .bind(member_id)
+        .bind(expires_at)
+        .fetch_one(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(session)
+    }
+
+    pub async fn get_sessions(&self, member_id: Uuid) -> Result<Vec<Session>, PlexoAppError> {
+        let sessions = sqlx::query_as::<_, Session>(
+            r#"
+            SELECT * FROM sessions
+            WHERE member_id = $1 AND revoked_at IS NULL AND expires_at > now()
+            ORDER BY updated_at DESC
+            "#,
+        )
+        .bind(member_id)
+        .fetch_all(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(sessions)
+    }
+
+    pub async fn is_session_active(&self, member_id: Uuid, id: Uuid) -> Result<bool, PlexoAppError> {
+        let active: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM sessions
+                WHERE id = $1 AND member_id = $2 AND revoked_at IS NULL AND expires_at > now()
+            )
+            "#,
+        )
+        .bind(id)
+        .bind(member_id)
+        .fetch_one(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(active)
+    }
+
+    /// Extends the session to match the expiration of its latest refresh token.
+    pub async fn extend_session(&self, id: Uuid, expires_at: DateTime<Utc>) -> Result<(), PlexoAppError> {
+        sqlx::query("UPDATE sessions SET expires_at = $1 WHERE id = $2")
+            .bind(expires_at)
+            .bind(id)
+            .execute(self.engine.db_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Revokes the session, which rejects its access tokens and its whole refresh token family.
+    pub async fn revoke_session(&self, member_id: Uuid, id: Uuid) -> Result<Session, PlexoAppError> {
+        let session = sqlx::query_as::<_, Session>(
+            r#"
+            UPDATE sessions
+            SET revoked_at = COALESCE(revoked_at, now())
+            WHERE id = $1 AND member_id = $2
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(member_id)
+        .fetch_optional(self.engine.db_pool.as_ref())
+        .await?
+        .ok_or(PlexoAppError::SessionNotFound)?;
+
+        self.revoke_refresh_token_family(session.id).await?;
+
+        Ok(session)
+    }
+
+    /// Signs the member out everywhere, returning how many sessions were still active.
+    pub async fn revoke_all_sessions(&self, member_id: Uuid) -> Result<u64, PlexoAppError> {
+        let mut tx = self.engine.db_pool.begin().await?;
+
+        let revoked = sqlx::query(
+            r#"
+            UPDATE sessions
+            SET revoked_at = now()
+            WHERE member_id = $1 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(member_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = now()
+            WHERE member_id = $1 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(member_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(revoked)
+    }
+}
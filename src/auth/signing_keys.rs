@@ -0,0 +1,157 @@
+use std::env::var;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+        PublicKeyUse,
+    },
+    DecodingKey, EncodingKey,
+};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+
+use crate::{
+    core::config::{DEVELOPMENT_MODE, JWT_ACCESS_TOKEN_SECRET, JWT_REFRESH_TOKEN_SECRET, JWT_SIGNING_KEYS},
+    errors::app::PlexoAppError,
+};
+
+/// DER prefix of an Ed25519 `SubjectPublicKeyInfo`, followed by the 32 bytes of the key.
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// The value `JWT_*_SECRET` falls back to, only accepted in development mode.
+const DEFAULT_JWT_SECRET: &str = "secret";
+
+/// An Ed25519 key tokens are signed with, identified by the `kid` header. Retired keys only
+/// have their public half, and keep verifying tokens issued before the rotation.
+#[derive(Clone)]
+pub struct JwtSigningKey {
+    pub kid: String,
+    encoding_key: Option<EncodingKey>,
+    decoding_key: DecodingKey,
+    public_key: Vec<u8>,
+}
+
+impl JwtSigningKey {
+    /// Reads `JWT_SIGNING_KEY_{KID}`, a PKCS#8 private key, or `JWT_VERIFICATION_KEY_{KID}`,
+    /// a public key, both PEM encoded.
+    pub fn from_env(kid: &str) -> Result<Self, PlexoAppError> {
+        let suffix = kid.to_uppercase().replace('-', "_");
+
+        if let Ok(private_key) = var(format!("JWT_SIGNING_KEY_{suffix}")) {
+            return Self::from_private_pem(kid, &private_key);
+        }
+
+        if let Ok(public_key) = var(format!("JWT_VERIFICATION_KEY_{suffix}")) {
+            return Self::from_public_pem(kid, &public_key);
+        }
+
+        Err(PlexoAppError::InvalidConfiguration(format!(
+            "JWT key {kid} is missing JWT_SIGNING_KEY_{suffix} or JWT_VERIFICATION_KEY_{suffix}"
+        )))
+    }
+
+    pub fn from_private_pem(kid: &str, pem: &str) -> Result<Self, PlexoAppError> {
+        let der = parse_pem(kid, pem, "PRIVATE KEY")?;
+
+        let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der)
+            .map_err(|_| PlexoAppError::InvalidConfiguration(format!("JWT key {kid} isn't an Ed25519 private key")))?;
+
+        let public_key = key_pair.public_key().as_ref().to_vec();
+
+        Ok(Self {
+            kid: kid.to_string(),
+            encoding_key: Some(EncodingKey::from_ed_der(&der)),
+            decoding_key: DecodingKey::from_ed_der(&public_key),
+            public_key,
+        })
+    }
+
+    pub fn from_public_pem(kid: &str, pem: &str) -> Result<Self, PlexoAppError> {
+        let der = parse_pem(kid, pem, "PUBLIC KEY")?;
+
+        let Some(public_key) = der.strip_prefix(&ED25519_SPKI_PREFIX).filter(|key| key.len() == 32) else {
+            return Err(PlexoAppError::InvalidConfiguration(format!(
+                "JWT key {kid} isn't an Ed25519 public key"
+            )));
+        };
+
+        Ok(Self {
+            kid: kid.to_string(),
+            encoding_key: None,
+            decoding_key: DecodingKey::from_ed_der(public_key),
+            public_key: public_key.to_vec(),
+        })
+    }
+
+    pub fn encoding_key(&self) -> Option<&EncodingKey> {
+        self.encoding_key.as_ref()
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    /// The public key as published in `/.well-known/jwks.json`.
+    pub fn jwk(&self) -> Jwk {
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(KeyAlgorithm::EdDSA),
+                key_id: Some(self.kid.clone()),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(&self.public_key),
+            }),
+        }
+    }
+}
+
+/// Environment variables can't always hold newlines, so `\n` escapes are accepted too.
+fn parse_pem(kid: &str, pem: &str, tag: &str) -> Result<Vec<u8>, PlexoAppError> {
+    let pem = pem::parse(pem.replace("\\n", "\n"))
+        .map_err(|err| PlexoAppError::InvalidConfiguration(format!("JWT key {kid} isn't valid PEM: {err}")))?;
+
+    if pem.tag() != tag {
+        return Err(PlexoAppError::InvalidConfiguration(format!(
+            "JWT key {kid} should be a {tag}, found {}",
+            pem.tag()
+        )));
+    }
+
+    Ok(pem.into_contents())
+}
+
+/// Loads the keys listed in `JWT_SIGNING_KEYS`. The first one signs new tokens, so it needs
+/// its private key. Without keys, tokens fall back to HS256 with the `JWT_*_SECRET` values,
+/// which must not be left to their default outside development mode.
+pub fn jwt_signing_keys_from_env() -> Result<Vec<JwtSigningKey>, PlexoAppError> {
+    let keys = JWT_SIGNING_KEYS
+        .iter()
+        .map(|kid| JwtSigningKey::from_env(kid))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(active_key) = keys.first() {
+        if active_key.encoding_key.is_none() {
+            return Err(PlexoAppError::InvalidConfiguration(format!(
+                "JWT key {} signs new tokens but has no private key",
+                active_key.kid
+            )));
+        }
+
+        return Ok(keys);
+    }
+
+    let default_secret = *JWT_ACCESS_TOKEN_SECRET == DEFAULT_JWT_SECRET || *JWT_REFRESH_TOKEN_SECRET == DEFAULT_JWT_SECRET;
+
+    if default_secret && !*DEVELOPMENT_MODE {
+        return Err(PlexoAppError::InvalidConfiguration(
+            "set JWT_SIGNING_KEYS, or non-default JWT_ACCESS_TOKEN_SECRET and JWT_REFRESH_TOKEN_SECRET, outside development mode"
+                .to_string(),
+        ));
+    }
+
+    Ok(keys)
+}
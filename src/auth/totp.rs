@@ -0,0 +1,458 @@
+use async_graphql::SimpleObject;
+use base32::Alphabet;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use oauth2::url::form_urlencoded::byte_serialize;
+use plexo_sdk::resources::members::{member::Member, operations::MemberCrudOperations};
+use rand::RngCore;
+use sha1::Sha1;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{
+    core::{app::Core, config::TWO_FACTOR_ISSUER},
+    errors::app::PlexoAppError,
+};
+
+use super::{
+    commons::{generate_link_token, hash_link_token},
+    resources::ClientIp,
+};
+
+const TOTP_SECRET_LENGTH: usize = 20;
+const TOTP_DIGITS: u32 = 6;
+const TOTP_PERIOD_SECONDS: i64 = 30;
+/// Steps accepted on each side of the current one, to tolerate clock drift.
+const TOTP_ALLOWED_DRIFT: i64 = 1;
+
+const RECOVERY_CODES_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+/// A pending enrollment. The secret is only returned here, before the first code is confirmed.
+#[derive(Debug, SimpleObject, Clone)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    /// `otpauth://` URI to render as a QR code for authenticator apps.
+    pub provisioning_uri: String,
+}
+
+type HmacSha1 = Hmac<Sha1>;
+
+fn encode_secret(secret: &[u8]) -> String {
+    base32::encode(Alphabet::Rfc4648 { padding: false }, secret)
+}
+
+fn decode_secret(secret: &str) -> Result<Vec<u8>, PlexoAppError> {
+    base32::decode(Alphabet::Rfc4648 { padding: false }, secret).ok_or(PlexoAppError::InvalidTwoFactorCode)
+}
+
+fn current_step() -> i64 {
+    Utc::now().timestamp() / TOTP_PERIOD_SECONDS
+}
+
+/// RFC 6238 code for a time step, using HMAC-SHA1 and dynamic truncation from RFC 4226.
+fn totp_code(secret: &[u8], step: i64) -> String {
+    let mut mac = HmacSha1::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+
+    let binary = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+
+    format!("{:0width$}", binary % 10u32.pow(TOTP_DIGITS), width = TOTP_DIGITS as usize)
+}
+
+/// The step within the allowed drift that `code` belongs to. Steps up to `last_used_step`
+/// were already used, so their codes can't be replayed.
+fn find_totp_step(secret: &[u8], code: &str, step: i64, last_used_step: Option<i64>) -> Option<i64> {
+    (step - TOTP_ALLOWED_DRIFT..=step + TOTP_ALLOWED_DRIFT)
+        .filter(|candidate| last_used_step.is_none_or(|last_used_step| *candidate > last_used_step))
+        .find(|candidate| totp_code(secret, *candidate) == code)
+}
+
+fn provisioning_uri(secret: &str, account: &str) -> String {
+    let issuer: String = byte_serialize(TWO_FACTOR_ISSUER.as_bytes()).collect();
+    let account: String = byte_serialize(account.as_bytes()).collect();
+
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_PERIOD_SECONDS}"
+    )
+}
+
+/// Recovery codes are compared case-insensitively and without their separator.
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == TOTP_DIGITS as usize && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Codes shown to the member, like `k3j9x-a0pq2`.
+fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODES_COUNT)
+        .map(|_| {
+            let code = generate_link_token(RECOVERY_CODE_LENGTH).to_ascii_lowercase();
+            let (head, tail) = code.split_at(RECOVERY_CODE_LENGTH / 2);
+
+            format!("{head}-{tail}")
+        })
+        .collect()
+}
+
+#[derive(FromRow)]
+struct RecoveryCode {
+    id: Uuid,
+    code_hash: String,
+    used_at: Option<DateTime<Utc>>,
+}
+
+/// The stored recovery code matching `code`, unless it was already used.
+fn find_recovery_code(recovery_codes: &[RecoveryCode], code: &str) -> Option<Uuid> {
+    let code_hash = hash_link_token(&normalize_recovery_code(code));
+
+    recovery_codes
+        .iter()
+        .find(|recovery_code| recovery_code.used_at.is_none() && recovery_code.code_hash == code_hash)
+        .map(|recovery_code| recovery_code.id)
+}
+
+impl Core {
+    pub async fn is_totp_enabled(&self, member_id: Uuid) -> Result<bool, PlexoAppError> {
+        let enabled: Option<bool> = sqlx::query_scalar("SELECT enabled_at IS NOT NULL FROM member_totp WHERE member_id = $1")
+            .bind(member_id)
+            .fetch_optional(self.engine.db_pool.as_ref())
+            .await?;
+
+        Ok(enabled.unwrap_or(false))
+    }
+
+    /// Generates a new secret for the member. It isn't enforced on login until a first code
+    /// is confirmed, and starting over replaces any pending secret.
+    pub async fn start_totp_enrollment(&self, member_id: Uuid) -> Result<TotpEnrollment, PlexoAppError> {
+        if self.is_totp_enabled(member_id).await? {
+            return Err(PlexoAppError::TwoFactorAlreadyEnabled);
+        }
+
+        let member = self.engine.get_member(member_id).await?;
+
+        let mut secret = [0u8; TOTP_SECRET_LENGTH];
+        rand::thread_rng().fill_bytes(&mut secret);
+        let secret = encode_secret(&secret);
+
+        sqlx::query(
+            r#"
+            INSERT INTO member_totp (member_id, secret)
+            VALUES ($1, $2)
+            ON CONFLICT (member_id) DO UPDATE SET secret = EXCLUDED.secret, last_used_step = NULL
+            "#,
+        )
+        .bind(member_id)
+        .bind(secret.clone())
+        .execute(self.engine.db_pool.as_ref())
+        .await?;
+
+        Ok(TotpEnrollment {
+            provisioning_uri: provisioning_uri(&secret, &member.email),
+            secret,
+        })
+    }
+
+    /// Enables two-factor authentication once the member proves their authenticator works.
+    /// Returns the recovery codes, which are only shown this once.
+    pub async fn confirm_totp_enrollment(&self, member_id: Uuid, code: &str) -> Result<Vec<String>, PlexoAppError> {
+        let totp: Option<(String, Option<i64>)> =
+            sqlx::query_as("SELECT secret, last_used_step FROM member_totp WHERE member_id = $1 AND enabled_at IS NULL")
+                .bind(member_id)
+                .fetch_optional(self.engine.db_pool.as_ref())
+                .await?;
+
+        let Some((secret, last_used_step)) = totp else {
+            return Err(PlexoAppError::TwoFactorNotEnabled);
+        };
+
+        self.verify_totp_code(member_id, &secret, last_used_step, code.trim()).await?;
+
+        sqlx::query("UPDATE member_totp SET enabled_at = now() WHERE member_id = $1")
+            .bind(member_id)
+            .execute(self.engine.db_pool.as_ref())
+            .await?;
+
+        self.replace_recovery_codes(member_id).await
+    }
+
+    /// Turns two-factor authentication off. Requires a current code or a recovery code.
+    pub async fn disable_totp(&self, member_id: Uuid, code: &str) -> Result<(), PlexoAppError> {
+        self.verify_second_factor(member_id, code).await?;
+
+        let mut tx = self.engine.db_pool.begin().await?;
+
+        sqlx::query("DELETE FROM member_totp WHERE member_id = $1")
+            .bind(member_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM totp_recovery_codes WHERE member_id = $1")
+            .bind(member_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Replaces every recovery code, used or not, with a fresh set.
+    pub async fn regenerate_recovery_codes(&self, member_id: Uuid, code: &str) -> Result<Vec<String>, PlexoAppError> {
+        self.verify_second_factor(member_id, code).await?;
+
+        self.replace_recovery_codes(member_id).await
+    }
+
+    /// Checks a code from the member's authenticator, or consumes one of their recovery codes.
+    pub async fn verify_second_factor(&self, member_id: Uuid, code: &str) -> Result<(), PlexoAppError> {
+        let totp: Option<(String, Option<i64>)> =
+            sqlx::query_as("SELECT secret, last_used_step FROM member_totp WHERE member_id = $1 AND enabled_at IS NOT NULL")
+                .bind(member_id)
+                .fetch_optional(self.engine.db_pool.as_ref())
+                .await?;
+
+        let Some((secret, last_used_step)) = totp else {
+            return Err(PlexoAppError::TwoFactorNotEnabled);
+        };
+
+        let code = code.trim();
+
+        if is_totp_code(code) {
+            return self.verify_totp_code(member_id, &secret, last_used_step, code).await;
+        }
+
+        let recovery_codes =
+            sqlx::query_as::<_, RecoveryCode>("SELECT id, code_hash, used_at FROM totp_recovery_codes WHERE member_id = $1")
+                .bind(member_id)
+                .fetch_all(self.engine.db_pool.as_ref())
+                .await?;
+
+        let Some(recovery_code_id) = find_recovery_code(&recovery_codes, code) else {
+            return Err(PlexoAppError::InvalidTwoFactorCode);
+        };
+
+        // Checked again on update, in case another login used the code in the meantime
+        let used = sqlx::query("UPDATE totp_recovery_codes SET used_at = now() WHERE id = $1 AND used_at IS NULL")
+            .bind(recovery_code_id)
+            .execute(self.engine.db_pool.as_ref())
+            .await?
+            .rows_affected();
+
+        if used == 0 {
+            return Err(PlexoAppError::InvalidTwoFactorCode);
+        }
+
+        Ok(())
+    }
+
+    /// Accepts codes from the surrounding steps too, but never the same step twice, so an
+    /// intercepted code can't be replayed.
+    async fn verify_totp_code(
+        &self,
+        member_id: Uuid,
+        secret: &str,
+        last_used_step: Option<i64>,
+        code: &str,
+    ) -> Result<(), PlexoAppError> {
+        let secret = decode_secret(secret)?;
+
+        let Some(matched_step) = find_totp_step(&secret, code, current_step(), last_used_step) else {
+            return Err(PlexoAppError::InvalidTwoFactorCode);
+        };
+
+        // Checked again on update, in case another login used the step in the meantime
+
+        let claimed = sqlx::query(
+            r#"
+            UPDATE member_totp
+            SET last_used_step = $2
+            WHERE member_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+        )
+        .bind(member_id)
+        .bind(matched_step)
+        .execute(self.engine.db_pool.as_ref())
+        .await?
+        .rows_affected();
+
+        if claimed == 0 {
+            return Err(PlexoAppError::InvalidTwoFactorCode);
+        }
+
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(&self, member_id: Uuid) -> Result<Vec<String>, PlexoAppError> {
+        let codes = generate_recovery_codes();
+
+        let code_hashes: Vec<String> = codes
+            .iter()
+            .map(|code| hash_link_token(&normalize_recovery_code(code)))
+            .collect();
+
+        let mut tx = self.engine.db_pool.begin().await?;
+
+        sqlx::query("DELETE FROM totp_recovery_codes WHERE member_id = $1")
+            .bind(member_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO totp_recovery_codes (member_id, code_hash)
+            SELECT $1, UNNEST($2::varchar[])
+            "#,
+        )
+        .bind(member_id)
+        .bind(code_hashes)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(codes)
+    }
+
+    /// First step of a login: `None` when the password is enough, or a challenge token to
+    /// exchange with `complete_login_challenge` when the member has two-factor enabled.
+    pub async fn create_login_challenge(&self, member_id: Uuid) -> Result<Option<String>, PlexoAppError> {
+        if !self.is_totp_enabled(member_id).await? {
+            return Ok(None);
+        }
+
+        self.auth.jwt_engine.create_challenge_token(member_id).map(Some)
+    }
+
+    /// Second step of a two-factor login. Returns the member to open a session for. Wrong
+    /// codes count as failed logins for the member's account.
+    pub async fn complete_login_challenge(
+        &self,
+        challenge_token: &str,
+        code: &str,
+        ip: &ClientIp,
+    ) -> Result<Member, PlexoAppError> {
+        let claims = self.auth.jwt_engine.decode_challenge_token(challenge_token)?;
+        let member = self.engine.get_member(claims.member_id()?).await?;
+
+        self.ensure_login_allowed(&member.email, ip).await?;
+
+        if let Err(err) = self.verify_second_factor(member.id, code).await {
+            if matches!(err, PlexoAppError::InvalidTwoFactorCode) {
+                self.record_failed_login(&member.email, ip).await?;
+            }
+
+            return Err(err);
+        }
+
+        self.record_successful_login(&member.email, ip).await?;
+
+        Ok(member)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA-1 seed of the RFC 6238 test vectors.
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    fn step_at(timestamp: i64) -> i64 {
+        timestamp / TOTP_PERIOD_SECONDS
+    }
+
+    #[test]
+    fn totp_code_matches_rfc_6238_vectors() {
+        // The RFC lists 8-digit codes, 6-digit codes are their last 6 digits
+        for (timestamp, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ] {
+            assert_eq!(totp_code(RFC_SECRET, step_at(timestamp)), code, "at {timestamp}");
+        }
+    }
+
+    #[test]
+    fn accepts_codes_one_step_away() {
+        let step = step_at(1111111111);
+
+        for drift in [-1, 0, 1] {
+            let code = totp_code(RFC_SECRET, step + drift);
+
+            assert_eq!(find_totp_step(RFC_SECRET, &code, step, None), Some(step + drift));
+        }
+    }
+
+    #[test]
+    fn rejects_codes_further_away() {
+        let step = step_at(1111111111);
+
+        for drift in [-2, 2] {
+            let code = totp_code(RFC_SECRET, step + drift);
+
+            assert_eq!(find_totp_step(RFC_SECRET, &code, step, None), None);
+        }
+    }
+
+    #[test]
+    fn rejects_replayed_steps() {
+        let step = step_at(1111111111);
+        let code = totp_code(RFC_SECRET, step);
+
+        assert_eq!(find_totp_step(RFC_SECRET, &code, step, Some(step)), None);
+        assert_eq!(find_totp_step(RFC_SECRET, &code, step, Some(step + 1)), None);
+        assert_eq!(find_totp_step(RFC_SECRET, &code, step, Some(step - 1)), Some(step));
+    }
+
+    #[test]
+    fn recovery_codes_are_used_once() {
+        let codes = generate_recovery_codes();
+
+        let mut recovery_codes: Vec<RecoveryCode> = codes
+            .iter()
+            .map(|code| RecoveryCode {
+                id: Uuid::new_v4(),
+                code_hash: hash_link_token(&normalize_recovery_code(code)),
+                used_at: None,
+            })
+            .collect();
+
+        // Typed without the separator and in capitals, as members often do
+        let typed = codes[3].replace('-', "").to_ascii_uppercase();
+
+        let id = find_recovery_code(&recovery_codes, &typed).unwrap();
+        assert_eq!(id, recovery_codes[3].id);
+
+        recovery_codes[3].used_at = Some(Utc::now());
+
+        assert_eq!(find_recovery_code(&recovery_codes, &typed), None);
+        assert_eq!(find_recovery_code(&recovery_codes, &codes[4]), Some(recovery_codes[4].id));
+    }
+
+    #[test]
+    fn recovery_codes_are_unique() {
+        let mut codes = generate_recovery_codes();
+
+        assert_eq!(codes.len(), RECOVERY_CODES_COUNT);
+        assert!(codes.iter().all(|code| !is_totp_code(code)));
+
+        codes.sort();
+        codes.dedup();
+
+        assert_eq!(codes.len(), RECOVERY_CODES_COUNT);
+    }
+}
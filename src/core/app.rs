@@ -5,16 +5,23 @@ use plexo_sdk::backend::{
     loaders::SDKLoaders,
 };
 
-use crate::{auth::engine::AuthEngine, errors::app::PlexoAppError};
+use crate::{
+    auth::{engine::AuthEngine, oidc::OidcProviderConfig, signing_keys::jwt_signing_keys_from_env},
+    errors::app::PlexoAppError,
+};
 
 use super::config::{
-    GITHUB_CLIENT_ID, GITHUB_CLIENT_SECRET, GITHUB_REDIRECT_URL, JWT_ACCESS_TOKEN_SECRET, SMTP_HOST, SMTP_PASSWORD, SMTP_PORT,
-    SMTP_USERNAME,
+    GITHUB_CLIENT_ID, GITHUB_CLIENT_SECRET, GITHUB_REDIRECT_URL, JWT_ACCESS_TOKEN_SECRET, JWT_REFRESH_TOKEN_SECRET, OIDC_PROVIDERS,
+    SMTP_HOST, SMTP_PASSWORD, SMTP_PORT, SMTP_USERNAME,
 };
 
-use tracing::info;
+use tracing::{error, info};
 
-use lettre::{message::header::ContentType, transport::smtp::authentication::Credentials, Message, SmtpTransport, Transport};
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    Message, SmtpTransport, Transport,
+};
 
 #[derive(Clone)]
 pub struct Core {
@@ -33,16 +40,23 @@ pub async fn new_core_from_env() -> Result<Core, PlexoAppError> {
         Err(err) => info!("database migration failed: {:?}", err),
     }
 
+    let jwt_signing_keys = jwt_signing_keys_from_env()?;
+
     let arc_engine = Arc::new(engine.clone());
 
     let loaders = Arc::new(SDKLoaders::new(arc_engine));
 
     let auth = AuthEngine::new(
         (*JWT_ACCESS_TOKEN_SECRET).to_string(),
-        (*JWT_ACCESS_TOKEN_SECRET).to_string(),
+        (*JWT_REFRESH_TOKEN_SECRET).to_string(),
+        jwt_signing_keys,
         (*GITHUB_CLIENT_ID).to_owned(),
         (*GITHUB_CLIENT_SECRET).to_owned(),
         Some((*GITHUB_REDIRECT_URL).to_owned()),
+        OIDC_PROVIDERS
+            .iter()
+            .filter_map(|name| OidcProviderConfig::from_env(name))
+            .collect(),
     );
 
     let mail_client = match (
@@ -69,29 +83,47 @@ pub async fn new_core_from_env() -> Result<Core, PlexoAppError> {
         }
     };
 
-    Ok(Core {
+    let core = Core {
         engine,
         auth,
         loaders,
         mail_client,
-    })
+    };
+
+    if let Err(err) = core.migrate().await {
+        error!("core migration failed: {:?}", err);
+        return Err(err);
+    }
+
+    info!("core migration successful");
+
+    Ok(core)
 }
 
 impl Core {
     pub fn send_email(&self, from: &str, to: &str, subject: &str, html: &str) -> Result<(), PlexoAppError> {
+        let from = from
+            .parse::<Mailbox>()
+            .map_err(|err| PlexoAppError::EmailNotSent(err.to_string()))?;
+        let to = to
+            .parse::<Mailbox>()
+            .map_err(|err| PlexoAppError::EmailNotSent(err.to_string()))?;
+
         let email = Message::builder()
-            .from(from.parse().unwrap())
-            .to(to.parse().unwrap())
+            .from(from)
+            .to(to)
             .subject(subject)
             .header(ContentType::TEXT_HTML)
             .body(html.to_string())
-            .unwrap();
+            .map_err(|err| PlexoAppError::EmailNotSent(err.to_string()))?;
 
         match &self.mail_client {
             Some(client) => {
-                let response = client.send(&email).unwrap();
+                let response = client
+                    .send(&email)
+                    .map_err(|err| PlexoAppError::EmailNotSent(err.to_string()))?;
                 info!("email sent: {:?}", response);
-            } // client.send(mail)?,
+            }
             None => info!("no mail client configured, skipping email send"),
         };
 
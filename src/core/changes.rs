@@ -0,0 +1,78 @@
+use plexo_sdk::resources::changes::{
+    change::{Change, ChangeOperation, ChangeResourceType},
+    operations::{ChangeCrudOperations, CreateChangeInputBuilder, UpdateChangeInputBuilder},
+};
+use serde_json::{json, Value};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::errors::app::PlexoAppError;
+
+use super::app::Core;
+
+impl Core {
+    /// Erases what a change log entry recorded, like a secret pasted into a task, while keeping
+    /// that the change happened. The redaction is itself logged, without the erased content,
+    /// as a change about the entry. Those entries are the trail of redactions, so they can't
+    /// be redacted.
+    pub async fn redact_change(&self, id: Uuid, redacted_by_id: Uuid, reason: Option<String>) -> Result<Change, PlexoAppError> {
+        let change = self.engine.get_change(id).await.map_err(|_| PlexoAppError::ChangeNotFound)?;
+
+        if change.resource_type == ChangeResourceType::Changes {
+            return Err(PlexoAppError::InvalidRequest("redactions can't be redacted".to_string()));
+        }
+
+        let diff = serde_json::from_str::<Value>(&change.diff_json).unwrap_or_default();
+
+        if diff["redacted"] == Value::Bool(true) {
+            return Err(PlexoAppError::InvalidRequest("change is already redacted".to_string()));
+        }
+
+        // The project stays, since changes about deleted rows are only visible through it
+        let redacted_diff = json!({
+            "redacted": true,
+            "result": { "project_id": diff["result"]["project_id"] },
+        });
+
+        let redacted = self
+            .engine
+            .update_change(
+                id,
+                UpdateChangeInputBuilder::default()
+                    .diff_json(redacted_diff.to_string())
+                    .build()
+                    .unwrap(),
+            )
+            .await?;
+
+        let redaction = self
+            .engine
+            .create_change(
+                CreateChangeInputBuilder::default()
+                    .owner_id(redacted_by_id)
+                    .resource_id(id)
+                    .operation(ChangeOperation::Update)
+                    .resource_type(ChangeResourceType::Changes)
+                    .diff_json(
+                        json!({
+                            "redacted": true,
+                            "reason": reason,
+                            "change": {
+                                "owner_id": change.owner_id,
+                                "resource_id": change.resource_id,
+                                "resource_type": change.resource_type,
+                                "operation": change.operation,
+                            },
+                        })
+                        .to_string(),
+                    )
+                    .build()
+                    .unwrap(),
+            )
+            .await?;
+
+        info!("change {} redacted by {}, logged as {}", id, redacted_by_id, redaction.id);
+
+        Ok(redacted)
+    }
+}
@@ -8,16 +8,31 @@ lazy_static! {
     pub static ref URL: String = var("URL").unwrap_or(format!("{}:{}", *HOST, *PORT));
     pub static ref SCHEMA: String = var("SCHEMA").unwrap_or("http".into());
     pub static ref DOMAIN: String = var("DOMAIN").unwrap_or(format!("{}://{}", *SCHEMA, *URL));
+    /// Allows insecure defaults, like the default JWT secrets, for local development.
+    pub static ref DEVELOPMENT_MODE: bool = var("DEVELOPMENT_MODE").map(|v| v.to_lowercase() == "true").unwrap_or(false);
     //
     pub static ref COOKIE_SESSION_DOMAIN: String = var("COOKIE_SESSION_DOMAIN").unwrap_or(format!(".{}", *HOST));
     pub static ref COOKIE_SESSION_NAME: String = var("COOKIE_SESSION_NAME").unwrap_or("plexo-session-token".into());
     pub static ref COOKIE_SESSION_SECURE: String = var("COOKIE_SESSION_SECURE").unwrap_or("false".into());
     pub static ref COOKIE_SESSION_SAME_SITE: String = var("COOKIE_SESSION_SAME_SITE").unwrap_or("none".into());
+    pub static ref COOKIE_REFRESH_NAME: String = var("COOKIE_REFRESH_NAME").unwrap_or("plexo-refresh-token".into());
+    pub static ref COOKIE_OAUTH_STATE_NAME: String = var("COOKIE_OAUTH_STATE_NAME").unwrap_or("plexo-oauth-state".into());
     //
     pub static ref DATABASE_URL: String = var("DATABASE_URL").expect("DATABASE_URL environment variable not set");
     pub static ref GITHUB_CLIENT_ID: Option<String> = var("GITHUB_CLIENT_ID").ok();
     pub static ref GITHUB_CLIENT_SECRET: Option<String> = var("GITHUB_CLIENT_SECRET").ok();
     pub static ref GITHUB_REDIRECT_URL: String = var("GITHUB_REDIRECT_URL").unwrap_or(format!("{}/auth/github/callback", *DOMAIN));
+    pub static ref GITHUB_API_URL: String = var("GITHUB_API_URL").unwrap_or("https://api.github.com".into());
+    /// Comma-separated origins, besides `DOMAIN`, that sign-ins may redirect back to.
+    pub static ref AUTH_REDIRECT_ALLOWED_ORIGINS: Vec<String> = var("AUTH_REDIRECT_ALLOWED_ORIGINS")
+        .map(|origins| origins.split(',').map(|origin| origin.trim().trim_end_matches('/').to_string()).filter(|origin| !origin.is_empty()).collect())
+        .unwrap_or_default();
+    /// Comma-separated provider names, each configured through `OIDC_{NAME}_*` variables.
+    pub static ref OIDC_PROVIDERS: Vec<String> = var("OIDC_PROVIDERS")
+        .map(|names| names.split(',').map(|name| name.trim().to_lowercase()).filter(|name| !name.is_empty()).collect())
+        .unwrap_or_default();
+    /// Bearer token the identity provider calls `/scim/v2` with. SCIM stays off without it.
+    pub static ref SCIM_BEARER_TOKEN: Option<String> = var("SCIM_BEARER_TOKEN").ok().filter(|token| !token.is_empty());
     //
     pub static ref LLM_API_KEY: String = var("OPENAI_API_KEY").expect("OPENAI_API_KEY environment variable not set");
     pub static ref LLM_MODEL_NAME: String = var("LLM_MODEL_NAME").unwrap_or("gpt-3.5-turbo".into());
@@ -36,6 +51,45 @@ lazy_static! {
     //
     pub static ref JWT_ACCESS_TOKEN_SECRET: String = var("JWT_ACCESS_TOKEN_SECRET").unwrap_or("secret".into());
     pub static ref JWT_REFRESH_TOKEN_SECRET: String = var("JWT_REFRESH_TOKEN_SECRET").unwrap_or("secret".into());
+    /// Comma-separated key ids, each configured through `JWT_SIGNING_KEY_{KID}` or
+    /// `JWT_VERIFICATION_KEY_{KID}`. The first one signs new tokens.
+    pub static ref JWT_SIGNING_KEYS: Vec<String> = var("JWT_SIGNING_KEYS")
+        .map(|kids| kids.split(',').map(|kid| kid.trim().to_string()).filter(|kid| !kid.is_empty()).collect())
+        .unwrap_or_default();
+    pub static ref JWT_ACCESS_TOKEN_TTL_MINUTES: i64 = var("JWT_ACCESS_TOKEN_TTL_MINUTES").ok().and_then(|v| v.parse().ok()).unwrap_or(15);
+    pub static ref JWT_REFRESH_TOKEN_TTL_DAYS: i64 = var("JWT_REFRESH_TOKEN_TTL_DAYS").ok().and_then(|v| v.parse().ok()).unwrap_or(30);
+    pub static ref TWO_FACTOR_CHALLENGE_TTL_MINUTES: i64 = var("TWO_FACTOR_CHALLENGE_TTL_MINUTES").ok().and_then(|v| v.parse().ok()).unwrap_or(5);
+    pub static ref TWO_FACTOR_ISSUER: String = var("TWO_FACTOR_ISSUER").unwrap_or((*ORGANIZATION_NAME).clone());
+    //
+    pub static ref LOGIN_MAX_FAILED_ATTEMPTS: i64 = var("LOGIN_MAX_FAILED_ATTEMPTS").ok().and_then(|v| v.parse().ok()).unwrap_or(5);
+    pub static ref LOGIN_MAX_FAILED_ATTEMPTS_PER_IP: i64 = var("LOGIN_MAX_FAILED_ATTEMPTS_PER_IP").ok().and_then(|v| v.parse().ok()).unwrap_or(50);
+    pub static ref LOGIN_FAILED_ATTEMPTS_WINDOW_MINUTES: i64 = var("LOGIN_FAILED_ATTEMPTS_WINDOW_MINUTES").ok().and_then(|v| v.parse().ok()).unwrap_or(15);
+    pub static ref LOGIN_LOCKOUT_BASE_SECONDS: i64 = var("LOGIN_LOCKOUT_BASE_SECONDS").ok().and_then(|v| v.parse().ok()).unwrap_or(60);
+    pub static ref LOGIN_LOCKOUT_MAX_MINUTES: i64 = var("LOGIN_LOCKOUT_MAX_MINUTES").ok().and_then(|v| v.parse().ok()).unwrap_or(60);
+    /// Header set by a trusted reverse proxy with the client address, e.g. `X-Forwarded-For`.
+    pub static ref CLIENT_IP_HEADER: Option<String> = var("CLIENT_IP_HEADER").ok();
+    /// Trusted proxies in front of the server, each appending an address to `CLIENT_IP_HEADER`.
+    /// The client address is the one that many entries from the right.
+    pub static ref CLIENT_IP_PROXY_HOPS: usize = var("CLIENT_IP_PROXY_HOPS").ok().and_then(|v| v.parse().ok()).filter(|hops| *hops > 0).unwrap_or(1);
+    //
+    pub static ref PASSWORD_RESET_URL: String = var("PASSWORD_RESET_URL").unwrap_or(format!("{}/reset-password", *DOMAIN));
+    pub static ref PASSWORD_RESET_TOKEN_TTL_MINUTES: i64 = var("PASSWORD_RESET_TOKEN_TTL_MINUTES").ok().and_then(|v| v.parse().ok()).unwrap_or(60);
+    pub static ref INVITATION_URL: String = var("INVITATION_URL").unwrap_or(format!("{}/invitation", *DOMAIN));
+    pub static ref INVITATION_TTL_DAYS: i64 = var("INVITATION_TTL_DAYS").ok().and_then(|v| v.parse().ok()).unwrap_or(7);
+    pub static ref EMAIL_VERIFICATION_URL: String = var("EMAIL_VERIFICATION_URL").unwrap_or(format!("{}/verify-email", *DOMAIN));
+    pub static ref EMAIL_VERIFICATION_TOKEN_TTL_HOURS: i64 = var("EMAIL_VERIFICATION_TOKEN_TTL_HOURS").ok().and_then(|v| v.parse().ok()).unwrap_or(48);
+    //
+    /// Deepest selection a GraphQL query may nest, introspection included.
+    pub static ref GRAPHQL_MAX_DEPTH: usize = var("GRAPHQL_MAX_DEPTH").ok().and_then(|v| v.parse().ok()).unwrap_or(15);
+    /// Highest estimated cost of a GraphQL query, where lists multiply the cost of their items.
+    pub static ref GRAPHQL_MAX_COMPLEXITY: usize = var("GRAPHQL_MAX_COMPLEXITY").ok().and_then(|v| v.parse().ok()).unwrap_or(20000);
+    pub static ref GRAPHQL_REQUEST_TIMEOUT_SECONDS: u64 = var("GRAPHQL_REQUEST_TIMEOUT_SECONDS").ok().and_then(|v| v.parse().ok()).unwrap_or(30);
+    /// Queries kept parsed for automatic persisted queries, the least recently used go first.
+    pub static ref GRAPHQL_APQ_CACHE_SIZE: usize = var("GRAPHQL_APQ_CACHE_SIZE").ok().and_then(|v| v.parse().ok()).unwrap_or(1000);
+    /// Apollo persisted query manifest generated when building the clients.
+    pub static ref GRAPHQL_ALLOWED_QUERIES_PATH: Option<String> = var("GRAPHQL_ALLOWED_QUERIES_PATH").ok().filter(|path| !path.is_empty());
+    /// Rejects every query missing from the manifest, outside development mode.
+    pub static ref GRAPHQL_ALLOWED_QUERIES_ONLY: bool = var("GRAPHQL_ALLOWED_QUERIES_ONLY").map(|v| v.to_lowercase() == "true").unwrap_or(false);
     //
     // pub static ref STATIC_PAGE_ENABLED: bool = var("STATIC_PAGE_ENABLED").unwrap_or("false".into()).to_lowercase() == "true";
     //
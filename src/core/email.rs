@@ -7,3 +7,28 @@ pub struct FirstWelcomeTemplate {
     pub admin_password: String,
     pub plexo_url: String,
 }
+
+#[derive(Template)]
+#[template(path = "password_reset.html.jinja")]
+pub struct PasswordResetTemplate {
+    pub member_name: String,
+    pub reset_url: String,
+    pub expires_in_minutes: i64,
+}
+
+#[derive(Template)]
+#[template(path = "invitation.html.jinja")]
+pub struct InvitationTemplate {
+    pub inviter_name: String,
+    pub organization_name: String,
+    pub invitation_url: String,
+    pub expires_in_days: i64,
+}
+
+#[derive(Template)]
+#[template(path = "email_verification.html.jinja")]
+pub struct EmailVerificationTemplate {
+    pub member_name: String,
+    pub verification_url: String,
+    pub expires_in_hours: i64,
+}
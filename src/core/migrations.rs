@@ -0,0 +1,132 @@
+use sqlx::{Executor, Row};
+use tracing::info;
+
+use crate::errors::app::PlexoAppError;
+
+use super::app::Core;
+
+// The SDK owns `_sqlx_migrations`, so core tables are tracked in their own ledger
+// to keep both migrators from reporting each other's versions as missing.
+const CORE_MIGRATIONS: &[(i64, &str, &str)] = &[
+    (
+        20261016120000,
+        "api_keys",
+        include_str!("../../migrations/20261016120000_api_keys.sql"),
+    ),
+    (
+        20261016130000,
+        "refresh_tokens",
+        include_str!("../../migrations/20261016130000_refresh_tokens.sql"),
+    ),
+    (
+        20261016140000,
+        "sessions",
+        include_str!("../../migrations/20261016140000_sessions.sql"),
+    ),
+    (
+        20261016150000,
+        "project_roles",
+        include_str!("../../migrations/20261016150000_project_roles.sql"),
+    ),
+    (
+        20261016160000,
+        "password_reset_tokens",
+        include_str!("../../migrations/20261016160000_password_reset_tokens.sql"),
+    ),
+    (
+        20261016170000,
+        "invitations",
+        include_str!("../../migrations/20261016170000_invitations.sql"),
+    ),
+    (
+        20261016180000,
+        "totp",
+        include_str!("../../migrations/20261016180000_totp.sql"),
+    ),
+    (
+        20261016190000,
+        "login_attempts",
+        include_str!("../../migrations/20261016190000_login_attempts.sql"),
+    ),
+    (
+        20261016200000,
+        "member_identities",
+        include_str!("../../migrations/20261016200000_member_identities.sql"),
+    ),
+    (
+        20261016210000,
+        "member_identity_links",
+        include_str!("../../migrations/20261016210000_member_identity_links.sql"),
+    ),
+    (
+        20261016220000,
+        "service_accounts",
+        include_str!("../../migrations/20261016220000_service_accounts.sql"),
+    ),
+    (
+        20261016230000,
+        "member_deactivations",
+        include_str!("../../migrations/20261016230000_member_deactivations.sql"),
+    ),
+    (
+        20261016233000,
+        "scim",
+        include_str!("../../migrations/20261016233000_scim.sql"),
+    ),
+    (
+        20261017000000,
+        "email_verifications",
+        include_str!("../../migrations/20261017000000_email_verifications.sql"),
+    ),
+    (
+        20261017010000,
+        "deleted_resources",
+        include_str!("../../migrations/20261017010000_deleted_resources.sql"),
+    ),
+];
+
+impl Core {
+    pub async fn migrate(&self) -> Result<(), PlexoAppError> {
+        let pool = self.engine.db_pool.as_ref();
+
+        pool.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS _plexo_core_migrations (
+                version      bigint                                 NOT NULL PRIMARY KEY,
+                description  text                                   NOT NULL,
+                installed_on timestamp with time zone DEFAULT now() NOT NULL
+            )
+            "#,
+        )
+        .await?;
+
+        let applied: Vec<i64> = sqlx::query("SELECT version FROM _plexo_core_migrations")
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| row.get("version"))
+            .collect();
+
+        for (version, description, sql) in CORE_MIGRATIONS {
+            if applied.contains(version) {
+                continue;
+            }
+
+            let mut tx = pool.begin().await?;
+
+            (&mut *tx).execute(*sql).await?;
+
+            sqlx::query("INSERT INTO _plexo_core_migrations (version, description) VALUES ($1, $2)")
+                .bind(version)
+                .bind(description)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await?;
+
+            info!("core migration applied: {} {}", version, description);
+        }
+
+        Ok(())
+    }
+}
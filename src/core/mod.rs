@@ -1,4 +1,6 @@
 pub mod app;
+pub mod changes;
 pub mod config;
 pub mod email;
+pub mod migrations;
 pub mod prelude;
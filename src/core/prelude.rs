@@ -18,7 +18,7 @@ use plexo_sdk::{
         operations::{GetMembersInput, GetMembersInputBuilder, MemberCrudOperations},
     },
 };
-use tracing::info;
+use tracing::{info, warn};
 
 impl Core {
     pub async fn prelude(&self) -> Result<Organization, Box<dyn std::error::Error>> {
@@ -108,7 +108,10 @@ impl Core {
 
         let org_email = org.email.clone();
 
-        self.first_time_welcome_email(org_email)?;
+        // The organization is already set up, a missing welcome email shouldn't undo that
+        if let Err(err) = self.first_time_welcome_email(org_email) {
+            warn!("welcome email not sent: {:?}", err);
+        }
 
         Ok(org)
     }
@@ -127,7 +130,7 @@ impl Core {
             // organization_email: (*ORGANIZATION_EMAIL).to_owned(),
         };
 
-        let html = welcome.render().unwrap();
+        let html = welcome.render()?;
 
         self.send_email(from, to, subject, html.as_str()).map_err(|err| err.into())
     }
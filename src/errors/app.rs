@@ -1,3 +1,4 @@
+use async_graphql::ErrorExtensions;
 use plexo_sdk::errors::sdk::SDKError;
 use poem::{error::ResponseError, http::StatusCode};
 use thiserror::Error;
@@ -9,14 +10,98 @@ pub enum PlexoAppError {
     MissingAuthorizationToken,
     #[error("Invalid authorization token")]
     InvalidAuthorizationToken,
+    #[error("Authorization token has expired")]
+    ExpiredAuthorizationToken,
+    #[error("Authorization token signature isn't valid")]
+    InvalidAuthorizationTokenSignature,
+    #[error("Authorization token isn't meant for this service")]
+    InvalidAuthorizationTokenAudience,
+    #[error("Authorization token is malformed")]
+    MalformedAuthorizationToken,
     #[error("Email already in use")]
     EmailAlreadyInUse,
+    #[error("Email address isn't valid")]
+    InvalidEmail,
     #[error("Password isn't valid")]
     InvalidPassword,
     #[error("Email not found")]
     EmailNotFound,
     #[error("Email already exists")]
     EmailAlreadyExists,
+    #[error("API key scope does not allow this operation")]
+    InsufficientScope,
+    #[error("You don't have permission to perform this operation")]
+    Forbidden,
+    #[error("Password reset token is invalid or expired")]
+    InvalidPasswordResetToken,
+    #[error("Invitation is invalid or expired")]
+    InvalidInvitation,
+    #[error("Two-factor code isn't valid")]
+    InvalidTwoFactorCode,
+    #[error("Two-factor authentication is already enabled")]
+    TwoFactorAlreadyEnabled,
+    #[error("Two-factor authentication isn't enabled")]
+    TwoFactorNotEnabled,
+    #[error("Email or password isn't valid")]
+    InvalidCredentials,
+    #[error("Too many failed login attempts, try again later")]
+    TooManyLoginAttempts,
+    #[error("Sign-in provider not found")]
+    UnknownAuthProvider,
+    #[error("Sign-in state is invalid or expired")]
+    InvalidOAuthState,
+    #[error("Redirect URL isn't allowed")]
+    InvalidRedirect,
+    #[error("Sign-in provider error: {0}")]
+    AuthProviderError(String),
+    #[error("Sign-in provider didn't share an email")]
+    MissingIdentityEmail,
+    #[error("An account with this email already exists, sign in and link this identity from your profile")]
+    IdentityNotLinked,
+    #[error("This identity is already linked to another account")]
+    IdentityAlreadyLinked,
+    #[error("Identity not found")]
+    IdentityNotFound,
+    #[error("Can't unlink the only way to sign in to this account")]
+    LastLoginMethod,
+    #[error("Service account not found")]
+    ServiceAccountNotFound,
+    #[error("Service accounts can only authenticate with API keys")]
+    ServiceAccountLogin,
+    #[error("This account has been deactivated")]
+    MemberDeactivated,
+    #[error("Member not found")]
+    MemberNotFound,
+    #[error("Team not found")]
+    TeamNotFound,
+    #[error("Change not found")]
+    ChangeNotFound,
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("External id already in use")]
+    ExternalIdAlreadyInUse,
+    #[error("Invalid filter: {0}")]
+    InvalidFilter(String),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Email verification link is invalid or expired")]
+    InvalidEmailVerificationToken,
+    #[error("Email isn't verified yet, check your inbox for the verification link")]
+    EmailNotVerified,
+    #[error("This email domain isn't allowed to register")]
+    EmailDomainNotAllowed,
+    #[error("Invalid configuration: {0}")]
+    InvalidConfiguration(String),
+    #[error("Query is nested {depth} levels deep, the limit is {max_depth}")]
+    QueryTooDeep { depth: usize, max_depth: usize },
+    #[error("Query complexity is {complexity}, the limit is {max_complexity}")]
+    QueryTooComplex { complexity: usize, max_complexity: usize },
+    #[error("Query didn't finish within {0} seconds")]
+    QueryTimeout(u64),
+    #[error("Query isn't on the allow-list")]
+    QueryNotAllowed,
+    #[error("Email couldn't be sent: {0}")]
+    EmailNotSent(String),
 
     #[error("SDKError error")]
     SDKError(#[from] SDKError),
@@ -26,6 +111,12 @@ pub enum PlexoAppError {
 
     #[error("JSONWebToken error")]
     JSONWebTokenError(#[from] jsonwebtoken::errors::Error),
+
+    #[error("SQLX error")]
+    SQLXError(#[from] sqlx::Error),
+
+    #[error("Template error")]
+    TemplateError(#[from] askama::Error),
     // #[error("Resend error")]
     // ResendError(#[from] resend_rs::error::Error),
 }
@@ -35,13 +126,96 @@ impl ResponseError for PlexoAppError {
         match self {
             PlexoAppError::MissingAuthorizationToken => StatusCode::UNAUTHORIZED,
             PlexoAppError::InvalidAuthorizationToken => StatusCode::UNAUTHORIZED,
+            PlexoAppError::ExpiredAuthorizationToken => StatusCode::UNAUTHORIZED,
+            PlexoAppError::InvalidAuthorizationTokenSignature => StatusCode::UNAUTHORIZED,
+            PlexoAppError::InvalidAuthorizationTokenAudience => StatusCode::UNAUTHORIZED,
+            PlexoAppError::MalformedAuthorizationToken => StatusCode::UNAUTHORIZED,
             PlexoAppError::EmailAlreadyInUse => StatusCode::BAD_REQUEST,
+            PlexoAppError::InvalidEmail => StatusCode::BAD_REQUEST,
             PlexoAppError::InvalidPassword => StatusCode::BAD_REQUEST,
             PlexoAppError::EmailNotFound => StatusCode::BAD_REQUEST,
             PlexoAppError::EmailAlreadyExists => StatusCode::BAD_REQUEST,
+            PlexoAppError::InsufficientScope => StatusCode::FORBIDDEN,
+            PlexoAppError::Forbidden => StatusCode::FORBIDDEN,
+            PlexoAppError::InvalidPasswordResetToken => StatusCode::BAD_REQUEST,
+            PlexoAppError::InvalidInvitation => StatusCode::BAD_REQUEST,
+            PlexoAppError::InvalidTwoFactorCode => StatusCode::UNAUTHORIZED,
+            PlexoAppError::TwoFactorAlreadyEnabled => StatusCode::BAD_REQUEST,
+            PlexoAppError::TwoFactorNotEnabled => StatusCode::BAD_REQUEST,
+            PlexoAppError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            PlexoAppError::TooManyLoginAttempts => StatusCode::TOO_MANY_REQUESTS,
+            PlexoAppError::UnknownAuthProvider => StatusCode::NOT_FOUND,
+            PlexoAppError::InvalidOAuthState => StatusCode::BAD_REQUEST,
+            PlexoAppError::InvalidRedirect => StatusCode::BAD_REQUEST,
+            PlexoAppError::AuthProviderError(_) => StatusCode::BAD_GATEWAY,
+            PlexoAppError::MissingIdentityEmail => StatusCode::BAD_REQUEST,
+            PlexoAppError::IdentityNotLinked => StatusCode::CONFLICT,
+            PlexoAppError::IdentityAlreadyLinked => StatusCode::CONFLICT,
+            PlexoAppError::IdentityNotFound => StatusCode::NOT_FOUND,
+            PlexoAppError::LastLoginMethod => StatusCode::BAD_REQUEST,
+            PlexoAppError::ServiceAccountNotFound => StatusCode::NOT_FOUND,
+            PlexoAppError::ServiceAccountLogin => StatusCode::FORBIDDEN,
+            PlexoAppError::MemberDeactivated => StatusCode::UNAUTHORIZED,
+            PlexoAppError::MemberNotFound => StatusCode::NOT_FOUND,
+            PlexoAppError::TeamNotFound => StatusCode::NOT_FOUND,
+            PlexoAppError::ChangeNotFound => StatusCode::NOT_FOUND,
+            PlexoAppError::SessionNotFound => StatusCode::NOT_FOUND,
+            PlexoAppError::ExternalIdAlreadyInUse => StatusCode::CONFLICT,
+            PlexoAppError::InvalidFilter(_) => StatusCode::BAD_REQUEST,
+            PlexoAppError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            PlexoAppError::InvalidEmailVerificationToken => StatusCode::BAD_REQUEST,
+            PlexoAppError::EmailNotVerified => StatusCode::FORBIDDEN,
+            PlexoAppError::EmailDomainNotAllowed => StatusCode::FORBIDDEN,
+            PlexoAppError::InvalidConfiguration(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            PlexoAppError::QueryTooDeep { .. } => StatusCode::BAD_REQUEST,
+            PlexoAppError::QueryTooComplex { .. } => StatusCode::BAD_REQUEST,
+            PlexoAppError::QueryTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            PlexoAppError::QueryNotAllowed => StatusCode::FORBIDDEN,
+            PlexoAppError::EmailNotSent(_) => StatusCode::BAD_GATEWAY,
             PlexoAppError::SDKError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             PlexoAppError::NotFoundPoemError(_) => StatusCode::NOT_FOUND,
             PlexoAppError::JSONWebTokenError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            PlexoAppError::SQLXError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            PlexoAppError::TemplateError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl PlexoAppError {
+    /// Stable identifier for clients, sent as the `code` extension of GraphQL errors.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PlexoAppError::MissingAuthorizationToken => "MISSING_AUTHORIZATION_TOKEN",
+            PlexoAppError::InvalidAuthorizationToken => "INVALID_AUTHORIZATION_TOKEN",
+            PlexoAppError::ExpiredAuthorizationToken => "EXPIRED_AUTHORIZATION_TOKEN",
+            PlexoAppError::InvalidAuthorizationTokenSignature => "INVALID_AUTHORIZATION_TOKEN_SIGNATURE",
+            PlexoAppError::InvalidAuthorizationTokenAudience => "INVALID_AUTHORIZATION_TOKEN_AUDIENCE",
+            PlexoAppError::MalformedAuthorizationToken => "MALFORMED_AUTHORIZATION_TOKEN",
+            PlexoAppError::InsufficientScope => "INSUFFICIENT_SCOPE",
+            PlexoAppError::MemberDeactivated => "MEMBER_DEACTIVATED",
+            PlexoAppError::EmailNotVerified => "EMAIL_NOT_VERIFIED",
+            PlexoAppError::QueryTooDeep { .. } => "QUERY_TOO_DEEP",
+            PlexoAppError::QueryTooComplex { .. } => "QUERY_TOO_COMPLEX",
+            PlexoAppError::QueryTimeout(_) => "QUERY_TIMEOUT",
+            PlexoAppError::QueryNotAllowed => "QUERY_NOT_ALLOWED",
+            _ => match self.status() {
+                StatusCode::UNAUTHORIZED => "UNAUTHENTICATED",
+                StatusCode::FORBIDDEN => "FORBIDDEN",
+                StatusCode::NOT_FOUND => "NOT_FOUND",
+                StatusCode::CONFLICT => "CONFLICT",
+                StatusCode::TOO_MANY_REQUESTS => "TOO_MANY_REQUESTS",
+                status if status.is_client_error() => "BAD_REQUEST",
+                _ => "INTERNAL_ERROR",
+            },
         }
     }
 }
+
+impl ErrorExtensions for PlexoAppError {
+    fn extend(&self) -> async_graphql::Error {
+        async_graphql::Error::new(self.to_string()).extend_with(|_, extensions| {
+            extensions.set("code", self.code());
+            extensions.set("status", self.status().as_u16());
+        })
+    }
+}
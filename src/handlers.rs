@@ -1,6 +1,6 @@
 use async_graphql::{
     http::{GraphiQLSource, ALL_WEBSOCKET_PROTOCOLS},
-    Data, Schema,
+    Data, ErrorExtensions, Schema,
 };
 
 use async_graphql_poem::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
@@ -10,17 +10,17 @@ use poem::{
     handler,
     http::HeaderMap,
     // web::Html,
-    web::{websocket::WebSocket, Data as PoemData, Html},
+    web::{websocket::WebSocket, Data as PoemData, Html, RemoteAddr},
     IntoResponse,
 };
 
 use crate::{
     api::graphql::schema::{MutationRoot, QueryRoot, SubscriptionRoot},
     auth::{
-        commons::{get_token_from_cookie, get_token_from_headers},
-        resources::PlexoAuthToken,
+        commons::{get_client_ip, get_token_from_cookie, get_token_from_headers, parse_authorization_header},
+        resources::{AuthenticationFailure, ClientIp},
     },
-    core::config::DOMAIN,
+    core::{app::Core, config::DOMAIN},
 };
 
 const VERSION: Option<&str> = option_env!("CARGO_PKG_VERSION");
@@ -37,20 +37,23 @@ pub async fn graphiq_handler() -> impl IntoResponse {
 #[handler]
 pub async fn graphql_handler(
     schema: PoemData<&Schema<QueryRoot, MutationRoot, SubscriptionRoot>>,
+    core: PoemData<&Core>,
     headers: &HeaderMap,
+    remote_addr: &RemoteAddr,
     req: GraphQLRequest,
 ) -> GraphQLResponse {
-    let mut req = req.0;
-    // let mut with_token = false;
+    let mut req = req.0.data(get_client_ip(headers, remote_addr));
 
-    if let Some(token) = get_token_from_headers(headers) {
-        req = req.data(token);
-        // with_token = true;
-    }
+    // the session cookie takes precedence over the Authorization header
+    let token = get_token_from_cookie(headers).or_else(|| get_token_from_headers(headers));
+
+    if let Some(token) = token {
+        match core.authenticate(&token).await {
+            Ok(principal) => req = req.data(principal),
+            Err(err) => req = req.data(AuthenticationFailure(err)),
+        }
 
-    if let Some(token) = get_token_from_cookie(headers) {
         req = req.data(token);
-        // with_token = true;
     }
 
     schema.execute(req).await.into()
@@ -59,23 +62,34 @@ pub async fn graphql_handler(
 #[handler]
 pub async fn ws_switch_handler(
     schema: PoemData<&Schema<QueryRoot, MutationRoot, SubscriptionRoot>>,
+    core: PoemData<&Core>,
+    headers: &HeaderMap,
+    remote_addr: &RemoteAddr,
     protocol: GraphQLProtocol,
     websocket: WebSocket,
 ) -> impl IntoResponse {
     let schema = schema.0.clone();
+    let core = core.0.clone();
+    let client_ip = get_client_ip(headers, remote_addr);
+
     websocket.protocols(ALL_WEBSOCKET_PROTOCOLS).on_upgrade(move |stream| {
         GraphQLWebSocket::new(stream, schema, protocol)
-            .on_connection_init(on_connection_init)
+            .on_connection_init(move |value| on_connection_init(core, client_ip, value))
             .serve()
     })
 }
 
-pub async fn on_connection_init(value: Value) -> async_graphql::Result<Data> {
+pub async fn on_connection_init(core: Core, client_ip: ClientIp, value: Value) -> async_graphql::Result<Data> {
     match &value {
         Value::Object(map) => {
             if let Some(Value::String(token)) = map.get("Authorization") {
+                let token = parse_authorization_header(token);
+                let principal = core.authenticate(&token).await.map_err(|err| err.extend())?;
+
                 let mut data = Data::default();
-                data.insert(PlexoAuthToken(token.to_string()));
+                data.insert(principal);
+                data.insert(token);
+                data.insert(client_ip);
 
                 Ok(data)
             } else {
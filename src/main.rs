@@ -2,11 +2,24 @@ use std::{error::Error, str::FromStr};
 
 use dotenv::dotenv;
 use plexo_core::{
-    api::{graphql::schema::GraphQLSchema, openapi::api::PlexoOpenAPI},
-    auth::handlers::{email_basic_login_handler, github_callback_handler, github_sign_in_handler, logout_handler},
+    api::{
+        graphql::schema::GraphQLSchema,
+        openapi::api::PlexoOpenAPI,
+        scim::handlers::{
+            scim_auth, scim_create_group_handler, scim_create_user_handler, scim_delete_group_handler, scim_delete_user_handler,
+            scim_get_group_handler, scim_get_groups_handler, scim_get_user_handler, scim_get_users_handler,
+            scim_patch_group_handler, scim_patch_user_handler, scim_replace_group_handler, scim_replace_user_handler,
+            scim_service_provider_config_handler,
+        },
+    },
+    auth::handlers::{
+        accept_invitation_handler, email_basic_login_handler, email_login_challenge_handler, forgot_password_handler,
+        github_callback_handler, github_sign_in_handler, jwks_handler, logout_handler, oidc_callback_handler, oidc_sign_in_handler,
+        refresh_handler, reset_password_handler, verify_email_handler,
+    },
     core::{
         app::new_core_from_env,
-        config::{DOMAIN, TRACING_LEVEL, URL},
+        config::{DOMAIN, SCIM_BEARER_TOKEN, TRACING_LEVEL, URL},
     },
     handlers::{graphiq_handler, graphql_handler, version_handler, ws_switch_handler},
 };
@@ -31,7 +44,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     info!("welcome to {:?}", org.name);
 
-    let graphql_schema = core.graphql_api_schema();
+    let graphql_schema = core.graphql_api_schema()?;
 
     let api_prefix = "/v1/api";
 
@@ -52,19 +65,57 @@ async fn main() -> Result<(), Box<dyn Error>> {
         // .nest("/", static_page)
         // Non authenticated routes
         .at("/auth/email/login", post(email_basic_login_handler))
+        .at("/auth/email/login/challenge", post(email_login_challenge_handler))
         // .at("/auth/email/register", post(email_basic_register_handler))
+        .at("/auth/password/forgot", post(forgot_password_handler))
+        .at("/auth/password/reset", post(reset_password_handler))
+        .at("/auth/email/verify", post(verify_email_handler))
+        .at("/auth/invitations/accept", post(accept_invitation_handler))
         //
         .at("/auth/github", get(github_sign_in_handler))
         .at("/auth/github/callback", get(github_callback_handler))
         //
+        .at("/auth/oidc/:provider", get(oidc_sign_in_handler))
+        .at("/auth/oidc/:provider/callback", get(oidc_callback_handler))
+        //
+        .at("/auth/refresh", post(refresh_handler))
         .at("/auth/logout", get(logout_handler))
         //
+        .at("/.well-known/jwks.json", get(jwks_handler))
+        //
         .at("/version", get(version_handler))
         //
         .at("/playground", get(graphiq_handler))
         .at("/graphql", post(graphql_handler))
         .at("/graphql/ws", get(ws_switch_handler));
 
+    // SCIM provisioning, only served once a bearer token is configured
+    let app = match *SCIM_BEARER_TOKEN {
+        Some(_) => app.nest(
+            "/scim/v2",
+            Route::new()
+                .at("/ServiceProviderConfig", get(scim_service_provider_config_handler))
+                .at("/Users", get(scim_get_users_handler).post(scim_create_user_handler))
+                .at(
+                    "/Users/:id",
+                    get(scim_get_user_handler)
+                        .put(scim_replace_user_handler)
+                        .patch(scim_patch_user_handler)
+                        .delete(scim_delete_user_handler),
+                )
+                .at("/Groups", get(scim_get_groups_handler).post(scim_create_group_handler))
+                .at(
+                    "/Groups/:id",
+                    get(scim_get_group_handler)
+                        .put(scim_replace_group_handler)
+                        .patch(scim_patch_group_handler)
+                        .delete(scim_delete_group_handler),
+                )
+                .around(scim_auth),
+        ),
+        None => app,
+    };
+
     let app = app
         .with(Cors::new().allow_credentials(true))
         .data(graphql_schema)